@@ -1,17 +1,30 @@
-use actix_web::{get, post, web, HttpMessage, HttpRequest, HttpResponse, Responder};
-
-use crate::db::{self, AppData, DefaultReturn, UserFollow, UserMetadata};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+
+use crate::avatar::{self, Image};
+use crate::captcha::Challenge;
+use crate::db::{self, AppData, DefaultReturn, RejectedAttempt, UserFollow, UserSummary};
+use crate::identicon;
+use crate::media::{self, MediaError, Slot};
+use crate::metadata::{self, FieldErrors, ProfileField};
+use crate::pagination::{self, Page, PageQuery};
+use crate::pages::base;
+use crate::ratelimit;
 use dorsal::utility;
 
 #[derive(Default, PartialEq, serde::Deserialize)]
-pub struct OffsetQueryProps {
-    pub offset: Option<i32>,
+pub struct AvatarQueryProps {
+    pub size: Option<u32>,
 }
 
 #[derive(serde::Deserialize)]
 struct RegisterInfo {
     username: String,
     invite_code: Option<String>,
+    /// challenge given by `/api/v1/auth/challenge`
+    challenge: Option<String>,
+    /// solution for `challenge`
+    solution: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -24,69 +37,254 @@ struct UpdateAboutInfo {
     about: String,
 }
 
+#[derive(serde::Deserialize)]
+pub struct CallbackQuery {
+    /// Where to go after logging in (a path, or a URL on one of `forward_auth.allowed_hosts`)
+    #[serde(default)]
+    next: Option<String>,
+}
+
+/// Check if users can be sent to `next` after logging in
+///
+/// # Arguments:
+/// * `next` - a path (ex: `/settings`) or a URL
+/// * `allowed_hosts` - hosts URLs may point to
+pub fn is_allowed_redirect(next: &str, allowed_hosts: &[String]) -> bool {
+    if next.starts_with("/") {
+        // `//host` and `/\host` are other hosts to browsers
+        return !next.starts_with("//") & !next.starts_with("/\\");
+    }
+
+    let uri = match next.parse::<actix_web::http::Uri>() {
+        Ok(u) => u,
+        Err(_) => return false,
+    };
+
+    match (uri.scheme_str(), uri.host()) {
+        (Some("http") | Some("https"), Some(host)) => {
+            allowed_hosts.iter().any(|h| h.eq_ignore_ascii_case(host))
+        }
+        _ => false,
+    }
+}
+
 #[get("/api/v1/auth/callback")]
+/// Redirect to `next` after logging in (or to the homepage if it isn't allowed)
+///
 /// We also accept the callback on Guppy, but it just redirects here
-pub async fn callback_request() -> impl Responder {
+pub async fn callback_request(
+    info: web::Query<CallbackQuery>,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    let next = match info.next {
+        Some(ref next) if is_allowed_redirect(next, &data.config.forward_auth.allowed_hosts) => {
+            next.as_str()
+        }
+        _ => "/",
+    };
+
     // return
-    return HttpResponse::Ok()
-        .append_header(("Content-Type", "text/html"))
+    HttpResponse::Found()
+        .append_header(("Location", next))
+        .append_header(("Cache-Control", "no-store"))
+        .finish()
+}
+
+/// Count a registration/login attempt in the metrics
+///
+/// # Arguments:
+/// * `data` - [`AppData`]
+/// * `action` - "register" or "login"
+/// * `success` - if the attempt succeeded
+fn count_attempt(data: &web::Data<AppData>, action: &str, success: bool) {
+    let name = if action == "register" {
+        "shuttle_registrations_total"
+    } else {
+        "shuttle_logins_total"
+    };
+
+    data.db.metrics.inc(
+        name,
+        &[("result", if success { "success" } else { "failure" })],
+    );
+}
+
+/// Log a rejected registration/login attempt and return an error response for it
+async fn reject_attempt(
+    data: &web::Data<AppData>,
+    status: StatusCode,
+    action: &str,
+    ip: String,
+    reason: &str,
+) -> HttpResponse {
+    count_attempt(data, action, false);
+    log::warn!(
+        target: "shuttle::auth",
+        action = action,
+        reason = reason,
+        ip = ip.as_str();
+        "{action} rejected: {reason}"
+    );
+
+    let _ = data
+        .db
+        .log_rejected_attempt(RejectedAttempt {
+            action: action.to_string(),
+            ip,
+            reason: reason.to_string(),
+        })
+        .await;
+
+    HttpResponse::build(status)
+        .append_header(("Content-Type", "application/json"))
         .body(
-            "<head>
-                <meta http-equiv=\"Refresh\" content=\"0; URL=/\" />
-            </head>",
-        );
+            serde_json::to_string::<DefaultReturn<()>>(&DefaultReturn {
+                success: false,
+                message: reason.to_string(),
+                payload: (),
+            })
+            .unwrap(),
+        )
+}
+
+#[get("/api/v1/auth/challenge")]
+/// Get a new registration challenge (`payload` is `null` if no challenge is required)
+pub async fn challenge_request(data: web::Data<AppData>) -> impl Responder {
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<Option<Challenge>>>(&DefaultReturn {
+                success: true,
+                message: String::new(),
+                payload: data.captcha.challenge(),
+            })
+            .unwrap(),
+        )
 }
 
 #[post("/api/v1/auth/register")]
-pub async fn register(body: web::Json<RegisterInfo>, data: web::Data<AppData>) -> impl Responder {
+pub async fn register(
+    req: HttpRequest,
+    body: web::Json<RegisterInfo>,
+    data: web::Data<AppData>,
+) -> impl Responder {
     // if server disabled registration, return
-    let disabled = crate::config::get_var("REGISTRATION_DISABLED");
-
-    if disabled.is_some() {
+    if data.config.registration.disabled {
+        count_attempt(&data, "register", false);
         return HttpResponse::NotAcceptable()
             .body("This server requires has registration disabled.");
     }
 
-    // check invite codes
-    let invite_codes = crate::config::get_var("INVITE_CODES");
+    // check rate limit
+    let ip = ratelimit::get_ip(&req);
+    let budget = data.limiter.budget("register").unwrap(); // always has a default
+
+    if !data.limiter.check(format!("register:{ip}"), &budget).await {
+        return reject_attempt(
+            &data,
+            StatusCode::TOO_MANY_REQUESTS,
+            "register",
+            ip,
+            "Too many registration attempts, please try again later.",
+        )
+        .await;
+    }
+
+    // check captcha
+    let challenge = body.challenge.clone().unwrap_or_default();
+    let solution = body.solution.clone().unwrap_or_default();
+
+    if !data.captcha.verify(&challenge, &solution) {
+        return reject_attempt(
+            &data,
+            StatusCode::NOT_ACCEPTABLE,
+            "register",
+            ip,
+            "Invalid or expired challenge solution.",
+        )
+        .await;
+    }
 
-    if invite_codes.is_some() {
-        let invite_codes = invite_codes.unwrap();
-        let codes: Vec<&str> = invite_codes.split(",").collect();
+    // check invite codes
+    let codes = &data.config.registration.invite_codes;
 
+    if !codes.is_empty() {
         // check body for invite code
         if body.invite_code.is_none() {
+            count_attempt(&data, "register", false);
             return HttpResponse::NotAcceptable()
                 .body("This server requires an invite code to register.");
         }
 
         let invite_code = body.invite_code.clone().unwrap();
 
-        if codes.contains(&invite_code.as_str()) == false {
-            return HttpResponse::NotAcceptable().body("Invalid invite code.");
+        if !codes.contains(&invite_code) {
+            return reject_attempt(
+                &data,
+                StatusCode::NOT_ACCEPTABLE,
+                "register",
+                ip,
+                "Invalid invite code.",
+            )
+            .await;
         }
     }
 
     // ...
-    let username = &body.username.trim();
+    let username = body.username.trim();
     let res = data.db.create_user(username.to_string()).await;
+    count_attempt(&data, "register", res.success);
+
+    if res.success {
+        log::info!(target: "shuttle::auth", username = username; "registration succeeded");
+    } else {
+        log::warn!(
+            target: "shuttle::auth",
+            action = "register",
+            reason = res.message.as_str(),
+            ip = ip.as_str();
+            "register rejected: {}",
+            res.message
+        );
+    }
 
     let c = res.clone();
     let set_cookie = if res.success && res.payload.is_some() {
-        format!("__Secure-Token={}; SameSite=Lax; Secure; Path=/; HostOnly=true; HttpOnly=true; Max-Age={}", c.message, 60 * 60 * 24 * 365)
+        base::token_cookie(&data.config, Option::Some(&c.message))
     } else {
         String::new()
     };
 
     // return
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Set-Cookie", if res.success { &set_cookie } else { "" }))
         .append_header(("Content-Type", "application/json"))
-        .body(serde_json::to_string(&res).unwrap());
+        .body(serde_json::to_string(&res).unwrap())
 }
 
 #[post("/api/v1/auth/login")]
-pub async fn login(body: web::Json<LoginInfo>, data: web::Data<AppData>) -> impl Responder {
+pub async fn login(
+    req: HttpRequest,
+    body: web::Json<LoginInfo>,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    // check rate limit
+    let ip = ratelimit::get_ip(&req);
+    let budget = data.limiter.budget("login").unwrap(); // always has a default
+
+    if !data.limiter.check(format!("login:{ip}"), &budget).await {
+        return reject_attempt(
+            &data,
+            StatusCode::TOO_MANY_REQUESTS,
+            "login",
+            ip,
+            "Too many login attempts, please try again later.",
+        )
+        .await;
+    }
+
+    // ...
     let id = body.uid.trim();
     let id_hashed = utility::hash(id.to_string());
 
@@ -96,8 +294,15 @@ pub async fn login(body: web::Json<LoginInfo>, data: web::Data<AppData>) -> impl
         .get_user_by_hashed(id_hashed) // if the user is returned, that means the ID is valid
         .await
     {
-        Ok(_) => {
-            let set_cookie = format!("__Secure-Token={}; SameSite=Lax; Secure; Path=/; HostOnly=true; HttpOnly=true; Max-Age={}", body.uid, 60 * 60 * 24 * 365);
+        Ok(user) => {
+            count_attempt(&data, "login", true);
+            log::info!(
+                target: "shuttle::auth",
+                username = user.user.username.as_str();
+                "login succeeded"
+            );
+
+            let set_cookie = base::token_cookie(&data.config, Option::Some(&body.uid));
 
             HttpResponse::Ok()
                 .append_header(("Set-Cookie", set_cookie.as_str()))
@@ -111,17 +316,56 @@ pub async fn login(body: web::Json<LoginInfo>, data: web::Data<AppData>) -> impl
                     .unwrap(),
                 )
         }
-        Err(e) => HttpResponse::NotAcceptable()
-            .append_header(("Content-Type", "application/json"))
-            .body(serde_json::to_string::<DefaultReturn<()>>(&e.into()).unwrap()),
+        Err(e) => {
+            count_attempt(&data, "login", false);
+
+            let reason = e.to_string();
+            log::warn!(
+                target: "shuttle::auth",
+                action = "login",
+                reason = reason.as_str(),
+                ip = ip.as_str();
+                "login rejected: {reason}"
+            );
+
+            let _ = data
+                .db
+                .log_rejected_attempt(RejectedAttempt {
+                    action: String::from("login"),
+                    ip,
+                    reason,
+                })
+                .await;
+
+            HttpResponse::NotAcceptable()
+                .append_header(("Content-Type", "application/json"))
+                .body(serde_json::to_string::<DefaultReturn<()>>(&e.into()).unwrap())
+        }
     }
 }
 
 #[post("/api/v1/auth/login-st")]
 pub async fn login_secondary_token(
+    req: HttpRequest,
     body: web::Json<LoginInfo>,
     data: web::Data<AppData>,
 ) -> impl Responder {
+    // check rate limit (shared with regular login)
+    let ip = ratelimit::get_ip(&req);
+    let budget = data.limiter.budget("login").unwrap(); // always has a default
+
+    if !data.limiter.check(format!("login:{ip}"), &budget).await {
+        return reject_attempt(
+            &data,
+            StatusCode::TOO_MANY_REQUESTS,
+            "login",
+            ip,
+            "Too many login attempts, please try again later.",
+        )
+        .await;
+    }
+
+    // ...
     let id = body.uid.trim();
     let id_unhashed = id.to_string();
 
@@ -131,8 +375,15 @@ pub async fn login_secondary_token(
         .get_user_by_unhashed_st(id_unhashed) // if the user is returned, that means the ID is valid
         .await
     {
-        Ok(_) => {
-            let set_cookie = format!("__Secure-Token={}; SameSite=Lax; Secure; Path=/; HostOnly=true; HttpOnly=true; Max-Age={}", body.uid, 60 * 60 * 24 * 365);
+        Ok(user) => {
+            count_attempt(&data, "login", true);
+            log::info!(
+                target: "shuttle::auth",
+                username = user.user.username.as_str();
+                "login succeeded"
+            );
+
+            let set_cookie = base::token_cookie(&data.config, Option::Some(&body.uid));
 
             HttpResponse::Ok()
                 .append_header(("Set-Cookie", set_cookie.as_str()))
@@ -146,9 +397,31 @@ pub async fn login_secondary_token(
                     .unwrap(),
                 )
         }
-        Err(e) => HttpResponse::NotAcceptable()
-            .append_header(("Content-Type", "application/json"))
-            .body(serde_json::to_string::<DefaultReturn<()>>(&e.into()).unwrap()),
+        Err(e) => {
+            count_attempt(&data, "login", false);
+
+            let reason = e.to_string();
+            log::warn!(
+                target: "shuttle::auth",
+                action = "login",
+                reason = reason.as_str(),
+                ip = ip.as_str();
+                "login rejected: {reason}"
+            );
+
+            let _ = data
+                .db
+                .log_rejected_attempt(RejectedAttempt {
+                    action: String::from("login"),
+                    ip,
+                    reason,
+                })
+                .await;
+
+            HttpResponse::NotAcceptable()
+                .append_header(("Content-Type", "application/json"))
+                .body(serde_json::to_string::<DefaultReturn<()>>(&e.into()).unwrap())
+        }
     }
 }
 
@@ -165,15 +438,15 @@ pub async fn logout(req: HttpRequest, data: web::Data<AppData>) -> impl Responde
         .get_user_by_unhashed(cookie.unwrap().value().to_string()) // if the user is returned, that means the ID is valid
         .await;
 
-    if !res.is_ok() {
+    if res.is_err() {
         return HttpResponse::NotAcceptable().body("Invalid token");
     }
 
     // return
-    return HttpResponse::Ok()
-        .append_header(("Set-Cookie", "__Secure-Token=refresh; SameSite=Strict; Secure; Path=/; HostOnly=true; HttpOnly=true; Max-Age=0"))
+    HttpResponse::Ok()
+        .append_header(("Set-Cookie", base::token_cookie(&data.config, Option::None)))
         .append_header(("Content-Type", "text/plain"))
-        .body("You have been signed out. You can now close this tab.");
+        .body("You have been signed out. You can now close this tab.")
 }
 
 #[get("/api/v1/auth/whoami")]
@@ -190,14 +463,14 @@ pub async fn whoami(req: HttpRequest, data: web::Data<AppData>) -> impl Responde
         .get_user_by_unhashed(cookie.unwrap().value().to_string()) // if the user is returned, that means the ID is valid
         .await;
 
-    if !res.is_ok() {
+    if res.is_err() {
         return HttpResponse::Ok().body("");
     }
 
     // return
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Content-Type", "text/plain"))
-        .body(res.ok().unwrap().user.username);
+        .body(res.ok().unwrap().user.username)
 }
 
 #[post("/api/v1/auth/users/{name:.*}/about")]
@@ -222,7 +495,7 @@ pub async fn edit_about_request(
     // make sure profile exists
     let profile = data.db.get_user_by_username(name.to_owned()).await;
 
-    if !profile.is_ok() {
+    if profile.is_err() {
         return HttpResponse::NotFound()
             .append_header(("Content-Type", "application/json"))
             .body(
@@ -246,7 +519,7 @@ pub async fn edit_about_request(
             .permissions
             .contains(&String::from("ManageUsers")));
 
-    if can_update == false {
+    if !can_update {
         return HttpResponse::NotFound()
             .body("You do not have permission to manage this user's contents.");
     }
@@ -269,12 +542,19 @@ pub async fn edit_about_request(
     user.about = body.about.clone();
 
     // ...
-    let res = data.db.edit_user_metadata_by_name(name, user).await;
+    let res = data.db.edit_user_metadata_by_name(name.clone(), user).await;
+
+    if res.success {
+        // users are notified when staff edit their profile
+        data.db
+            .notify_profile_edit(name, token_user.user.username)
+            .await;
+    }
 
     // return
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Content-Type", "application/json"))
-        .body(serde_json::to_string(&res).unwrap());
+        .body(serde_json::to_string(&res).unwrap())
 }
 
 #[post("/api/v1/auth/users/{name:.*}/secondary-token")]
@@ -298,7 +578,7 @@ pub async fn refresh_secondary_token_request(
     // make sure profile exists
     let profile = data.db.get_user_by_username(name.to_owned()).await;
 
-    if !profile.is_ok() {
+    if profile.is_err() {
         return HttpResponse::NotFound()
             .append_header(("Content-Type", "application/json"))
             .body(
@@ -322,7 +602,7 @@ pub async fn refresh_secondary_token_request(
             .permissions
             .contains(&String::from("ManageUsers")));
 
-    if can_update == false {
+    if !can_update {
         return HttpResponse::NotFound()
             .body("You do not have permission to manage this user's contents.");
     }
@@ -332,10 +612,17 @@ pub async fn refresh_secondary_token_request(
     user.secondary_token = Option::Some(utility::hash(token.clone())); // this is essentially just a second ID the user can signin with
 
     // ...
-    let res = data.db.edit_user_metadata_by_name(name, user).await;
+    let res = data.db.edit_user_metadata_by_name(name.clone(), user).await;
+
+    if res.success {
+        // users are notified when staff edit their profile
+        data.db
+            .notify_profile_edit(name, token_user.user.username)
+            .await;
+    }
 
     // return
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Content-Type", "application/json"))
         .body(
             serde_json::to_string::<DefaultReturn<String>>(&DefaultReturn {
@@ -344,11 +631,12 @@ pub async fn refresh_secondary_token_request(
                 payload: token,
             })
             .unwrap(),
-        );
+        )
 }
 
-#[post("/api/v1/auth/users/{name:.*}/follow")]
-pub async fn follow_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+#[get("/api/v1/auth/users/{name:.*}/relationship")]
+/// Get the [`Relationship`](db::Relationship) between the current user and a user
+pub async fn relationship_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
     let name: String = req.match_info().get("name").unwrap().to_string();
 
     // get token user
@@ -362,37 +650,58 @@ pub async fn follow_request(req: HttpRequest, data: web::Data<AppData>) -> impl
 
     let token_user = token_user.unwrap().ok().unwrap();
 
-    // ...
-    let res = data
+    // make sure profile exists (and hasn't blocked us)
+    if data.db.get_user_by_username(name.to_owned()).await.is_err()
+        | data
+            .db
+            .get_block(name.clone(), token_user.user.username.clone())
+            .await
+            .is_some()
+    {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<db::Relationship>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Profile does not exist!"),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
+
+    // return
+    let relationship = data
         .db
-        .toggle_user_follow(&mut UserFollow {
-            user: token_user.user.username,
-            is_following: name,
-        })
+        .get_relationship(token_user.user.username, name.clone())
         .await;
 
-    // return
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Content-Type", "application/json"))
         .body(
-            serde_json::to_string(&match res {
-                Ok(r) => DefaultReturn {
-                    success: true,
-                    message: String::from("Followed user."),
-                    payload: r,
-                },
-                Err(e) => e.into(),
+            serde_json::to_string::<DefaultReturn<Option<db::Relationship>>>(&DefaultReturn {
+                success: true,
+                message: name,
+                payload: Option::Some(relationship),
             })
             .unwrap(),
-        );
+        )
 }
 
-#[post("/api/v1/auth/users/{name:.*}/update")]
-pub async fn update_request(
-    req: HttpRequest,
-    body: web::Json<UserMetadata>,
-    data: web::Data<AppData>,
-) -> impl Responder {
+#[post("/api/v1/auth/users/{name:.*}/follow")]
+/// Follow a user
+pub async fn follow_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_follow(req, data, true).await
+}
+
+#[delete("/api/v1/auth/users/{name:.*}/follow")]
+/// Unfollow a user
+pub async fn unfollow_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_follow(req, data, false).await
+}
+
+/// Follow (or unfollow) a user, returning the resulting [`Relationship`](db::Relationship)
+async fn update_follow(req: HttpRequest, data: web::Data<AppData>, follow: bool) -> HttpResponse {
     let name: String = req.match_info().get("name").unwrap().to_string();
 
     // get token user
@@ -404,56 +713,140 @@ pub async fn update_request(
             .body("An account is required to do this");
     }
 
-    // make sure profile exists
-    let profile = data.db.get_user_by_username(name.to_owned()).await;
+    let token_user = token_user.unwrap().ok().unwrap();
 
-    if !profile.is_ok() {
-        return HttpResponse::NotFound()
+    if token_user.user.username == name {
+        return HttpResponse::BadRequest()
             .append_header(("Content-Type", "application/json"))
             .body(
-                serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                serde_json::to_string::<DefaultReturn<Option<db::Relationship>>>(&DefaultReturn {
                     success: false,
-                    message: String::from("Profile does not exist!"),
+                    message: String::from("You cannot follow yourself."),
                     payload: Option::None,
                 })
                 .unwrap(),
             );
     }
 
-    let token_user = token_user.unwrap().ok().unwrap();
-    let profile = profile.ok().unwrap();
+    if follow
+        && data
+            .db
+            .is_blocked_either(token_user.user.username.clone(), name.clone())
+            .await
+    {
+        return HttpResponse::Forbidden()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<db::Relationship>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("You cannot follow this user."),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
 
-    // check if we can update this user
-    // must be authenticated AND same user OR staff
-    let can_update: bool = (token_user.user.username == profile.user.username)
-        | (token_user
-            .level
-            .permissions
-            .contains(&String::from("ManageUsers")));
+    // ...
+    let props = UserFollow {
+        user: token_user.user.username.clone(),
+        is_following: name.clone(),
+    };
 
-    if can_update == false {
-        return HttpResponse::NotFound()
-            .body("You do not have permission to manage this user's contents.");
+    // private accounts must approve follows
+    let request = follow
+        && data.db.get_user_settings(name.clone()).await.private
+        && !data
+            .db
+            .get_follow_by_user(props.user.clone(), name.clone())
+            .await
+            .success;
+
+    // unfollowing users with a pending request cancels the request
+    let cancel = !follow
+        && data
+            .db
+            .get_follow_request(props.user.clone(), name.clone())
+            .await
+            .success;
+
+    let res = if request {
+        data.db.request_follow(&props).await
+    } else if follow {
+        data.db.follow_user(&props).await
+    } else if cancel {
+        data.db.remove_follow_request(&props).await
+    } else {
+        data.db.unfollow_user(&props).await
+    };
+
+    let changed = match res {
+        Ok(c) => c,
+        Err(e) => {
+            return HttpResponse::Ok()
+                .append_header(("Content-Type", "application/json"))
+                .body(
+                    serde_json::to_string::<DefaultReturn<Option<db::Relationship>>>(&e.into())
+                        .unwrap(),
+                );
+        }
+    };
+
+    if changed {
+        let action = if request {
+            "request"
+        } else if follow {
+            "follow"
+        } else if cancel {
+            "cancel"
+        } else {
+            "unfollow"
+        };
+
+        data.db
+            .metrics
+            .inc("shuttle_follow_toggles_total", &[("action", action)]);
     }
 
-    // ...
-    let res = data
+    // return
+    let relationship = data
         .db
-        .edit_user_metadata_by_name(
-            name,            // select user
-            body.to_owned(), // new metadata
-        )
+        .get_relationship(token_user.user.username, name)
         .await;
 
-    // return
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Content-Type", "application/json"))
-        .body(serde_json::to_string(&res).unwrap());
+        .body(
+            serde_json::to_string::<DefaultReturn<Option<db::Relationship>>>(&DefaultReturn {
+                success: true,
+                message: String::from(match (follow, request, changed) {
+                    (true, true, true) => "Requested to follow user.",
+                    (true, true, false) => "Already requested to follow user.",
+                    (true, false, true) => "Followed user.",
+                    (true, false, false) => "Already following user.",
+                    (false, _, true) if cancel => "Cancelled follow request.",
+                    (false, _, true) => "Unfollowed user.",
+                    (false, _, false) => "Not following user.",
+                }),
+                payload: Option::Some(relationship),
+            })
+            .unwrap(),
+        )
 }
 
-#[post("/api/v1/auth/users/{name:.*?}/ban")]
-/// Ban user
-pub async fn ban_request(req: HttpRequest, data: web::Data<db::AppData>) -> impl Responder {
+#[post("/api/v1/auth/users/{name:.*}/block")]
+/// Block a user
+pub async fn block_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_block(req, data, true).await
+}
+
+#[delete("/api/v1/auth/users/{name:.*}/block")]
+/// Unblock a user
+pub async fn unblock_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_block(req, data, false).await
+}
+
+/// Block (or unblock) a user, returning the resulting [`Relationship`](db::Relationship)
+async fn update_block(req: HttpRequest, data: web::Data<AppData>, block: bool) -> HttpResponse {
     let name: String = req.match_info().get("name").unwrap().to_string();
 
     // get token user
@@ -465,78 +858,644 @@ pub async fn ban_request(req: HttpRequest, data: web::Data<db::AppData>) -> impl
             .body("An account is required to do this");
     }
 
-    // make sure token_user is of role "staff"
-    if !token_user
-        .unwrap()
-        .ok()
-        .unwrap()
-        .level
-        .permissions
-        .contains(&String::from("ManageUsers"))
-    {
-        return HttpResponse::NotAcceptable().body("Only staff can do this");
-    }
-
-    // ban user
-    let res: db::DefaultReturn<Option<String>> = data.db.ban_user_by_name(name).await;
+    let token_user = token_user.unwrap().ok().unwrap();
 
-    // return
-    return HttpResponse::Ok()
-        .append_header(("Content-Type", "application/json"))
-        .body(serde_json::to_string::<db::DefaultReturn<Option<String>>>(&res).unwrap());
-}
+    // ...
+    let res = if block {
+        data.db
+            .block_user(token_user.user.username.clone(), name.clone())
+            .await
+    } else {
+        data.db
+            .unblock_user(token_user.user.username.clone(), name.clone())
+            .await
+    };
 
-#[get("/api/v1/auth/users/{name:.*}/followers")]
-pub async fn followers_request(
-    req: HttpRequest,
-    data: web::Data<AppData>,
-    info: web::Query<OffsetQueryProps>,
-) -> impl Responder {
-    let name: String = req.match_info().get("name").unwrap().to_string();
+    if !res.success {
+        return HttpResponse::BadRequest()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<db::Relationship>>>(&DefaultReturn {
+                    success: false,
+                    message: res.message,
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
 
-    // get followers
-    let res: DefaultReturn<Option<Vec<db::Log>>> = data
+    // return
+    let relationship = data
         .db
-        .get_user_followers(name.to_owned(), info.offset)
+        .get_relationship(token_user.user.username, name)
         .await;
 
-    // return
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Content-Type", "application/json"))
-        .body(serde_json::to_string::<DefaultReturn<Option<Vec<db::Log>>>>(&res).unwrap());
+        .body(
+            serde_json::to_string::<DefaultReturn<Option<db::Relationship>>>(&DefaultReturn {
+                success: true,
+                message: res.message,
+                payload: Option::Some(relationship),
+            })
+            .unwrap(),
+        )
 }
 
-#[get("/api/v1/auth/users/{name:.*}/following")]
-pub async fn following_request(
+#[get("/api/v1/auth/users/{name:.*}/blocks")]
+/// Get the users a user has blocked
+pub async fn blocks_request(
     req: HttpRequest,
     data: web::Data<AppData>,
-    info: web::Query<OffsetQueryProps>,
+    info: web::Query<PageQuery>,
 ) -> impl Responder {
-    let name: String = req.match_info().get("name").unwrap().to_string();
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
 
-    // get following
-    let res: DefaultReturn<Option<Vec<db::Log>>> = data
-        .db
-        .get_user_following(name.to_owned(), info.offset)
-        .await;
-
-    // return
-    return HttpResponse::Ok()
-        .append_header(("Content-Type", "application/json"))
-        .body(serde_json::to_string::<DefaultReturn<Option<Vec<db::Log>>>>(&res).unwrap());
-}
-
-#[get("/api/v1/auth/users/{name:.*}/avatar")]
-pub async fn avatar_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
     let name: String = req.match_info().get("name").unwrap().to_string();
 
-    // make sure profile exists
-    let profile = data.db.get_user_by_username(name.to_owned()).await;
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req.clone(), data.clone()).await;
 
-    if !profile.is_ok() {
-        return HttpResponse::NotFound()
-            .append_header(("Content-Type", "application/json"))
-            .body(
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    // make sure we have permission to view this user's blocks
+    let token_user = token_user.unwrap().ok().unwrap();
+    let can_view: bool = (token_user.user.username == name)
+        | (token_user
+            .level
+            .permissions
+            .contains(&String::from("ManageUsers")));
+
+    if !can_view {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("You do not have permission to manage this user's contents.");
+    }
+
+    // return
+    let res = data.db.get_user_blocks(name, &info).await;
+    pagination::page_response(&req, &info, res)
+}
+
+#[post("/api/v1/auth/users/{name:.*}/update")]
+pub async fn update_request(
+    req: HttpRequest,
+    body: web::Json<serde_json::Map<String, serde_json::Value>>,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    // make sure profile exists
+    let profile = data.db.get_user_by_username(name.to_owned()).await;
+
+    if profile.is_err() {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Profile does not exist!"),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+    let profile = profile.ok().unwrap();
+
+    // check if we can update this user
+    // must be authenticated AND same user OR staff
+    let can_update: bool = (token_user.user.username == profile.user.username)
+        | (token_user
+            .level
+            .permissions
+            .contains(&String::from("ManageUsers")));
+
+    if !can_update {
+        return HttpResponse::NotFound()
+            .body("You do not have permission to manage this user's contents.");
+    }
+
+    // apply update (only the fields that were sent are changed)
+    let fields = data.db.get_profile_fields(name.clone()).await;
+    let schema = &data.config.profile_fields;
+
+    let update = match metadata::apply_update(&profile.user.metadata, &fields, schema, &body) {
+        Ok(u) => u,
+        Err(errors) => {
+            return HttpResponse::BadRequest()
+                .append_header(("Content-Type", "application/json"))
+                .body(
+                    serde_json::to_string::<DefaultReturn<FieldErrors>>(&DefaultReturn {
+                        success: false,
+                        message: String::from("Some fields are invalid."),
+                        payload: errors,
+                    })
+                    .unwrap(),
+                );
+        }
+    };
+
+    // metadata first, nothing else is changed if it fails
+    let res = data
+        .db
+        .edit_user_metadata_by_name(
+            name.clone(),    // select user
+            update.metadata, // new metadata
+        )
+        .await;
+
+    if !res.success {
+        return HttpResponse::Ok()
+            .append_header(("Content-Type", "application/json"))
+            .body(serde_json::to_string(&res).unwrap());
+    }
+
+    if let Some(fields) = update.fields {
+        let res = data.db.set_profile_fields(name.clone(), fields).await;
+
+        if !res.success {
+            return HttpResponse::Ok()
+                .append_header(("Content-Type", "application/json"))
+                .body(serde_json::to_string(&res).unwrap());
+        }
+    }
+
+    if let Some(private) = update.private {
+        let mut settings = data.db.get_user_settings(name.clone()).await;
+        settings.private = private;
+
+        let res = data.db.set_user_settings(name.clone(), settings).await;
+
+        if !res.success {
+            return HttpResponse::Ok()
+                .append_header(("Content-Type", "application/json"))
+                .body(serde_json::to_string(&res).unwrap());
+        }
+    }
+
+    // users are notified when staff edit their profile
+    data.db
+        .notify_profile_edit(name, token_user.user.username)
+        .await;
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(serde_json::to_string(&res).unwrap())
+}
+
+#[post("/api/v1/auth/users/{name:.*?}/ban")]
+/// Ban user (staff only)
+///
+/// Only users with a level elevation of 0 can be banned here. Staff accounts
+/// (and users who are already banned) are rejected with "User must be of level
+/// elevation 0", use the `shuttle ban` command to ban them.
+pub async fn ban_request(req: HttpRequest, data: web::Data<db::AppData>) -> impl Responder {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    // make sure token_user is of role "staff"
+    if !token_user
+        .unwrap()
+        .ok()
+        .unwrap()
+        .level
+        .permissions
+        .contains(&String::from("ManageUsers"))
+    {
+        return HttpResponse::NotAcceptable().body("Only staff can do this");
+    }
+
+    // ban user
+    let res: db::DefaultReturn<Option<String>> = data.db.ban_user_by_name(name, false).await;
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(serde_json::to_string::<db::DefaultReturn<Option<String>>>(&res).unwrap())
+}
+
+#[get("/api/v1/auth/users/{name}")]
+/// Get a user's [`Profile`](db::Profile)
+pub async fn profile_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+
+    // get user
+    let profile = match data.db.get_user_by_username(name.to_owned()).await {
+        Ok(p) => p,
+        Err(_) => {
+            return HttpResponse::NotFound()
+                .append_header(("Content-Type", "application/json"))
+                .body(
+                    serde_json::to_string::<DefaultReturn<Option<db::Profile>>>(&DefaultReturn {
+                        success: false,
+                        message: String::from("Profile does not exist!"),
+                        payload: Option::None,
+                    })
+                    .unwrap(),
+                );
+        }
+    };
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    // users that have been blocked by the profile can't view it
+    if let Some(Ok(ref token_user)) = token_user {
+        if data
+            .db
+            .get_block(name.clone(), token_user.user.username.clone())
+            .await
+            .is_some()
+        {
+            return HttpResponse::NotFound()
+                .append_header(("Content-Type", "application/json"))
+                .body(
+                    serde_json::to_string::<DefaultReturn<Option<db::Profile>>>(&DefaultReturn {
+                        success: false,
+                        message: String::from("Profile does not exist!"),
+                        payload: Option::None,
+                    })
+                    .unwrap(),
+                );
+        }
+    }
+
+    // private fields are only shown to the user themselves and staff
+    let can_view_private: bool = match token_user {
+        Some(Ok(token_user)) => {
+            (token_user.user.username == profile.user.username)
+                | (token_user
+                    .level
+                    .permissions
+                    .contains(&String::from("ManageUsers")))
+        }
+        _ => false,
+    };
+
+    let meta = profile.user.metadata;
+
+    let private = if can_view_private {
+        Option::Some(db::PrivateProfile {
+            avatar_url: meta.avatar_url.clone(),
+            has_secondary_token: meta.secondary_token.is_some(),
+        })
+    } else {
+        Option::None
+    };
+
+    // return
+    return HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<Option<db::Profile>>>(&DefaultReturn {
+                success: true,
+                message: profile.user.username.clone(),
+                payload: Option::Some(db::Profile {
+                    nickname: meta.nickname.unwrap_or(profile.user.username.clone()),
+                    about_html: crate::markup::render(&meta.about),
+                    about: meta.about,
+                    role: profile.user.role,
+                    level: profile.level,
+                    joined: profile.user.timestamp,
+                    followers_count: data.db.get_user_follow_count(name.clone()).await.payload,
+                    following_count: data.db.get_user_following_count(name.clone()).await.payload,
+                    fields: data.db.get_profile_fields(name.clone()).await,
+                    is_private: data.db.get_user_settings(name).await.private,
+                    username: profile.user.username,
+                    private,
+                }),
+            })
+            .unwrap(),
+        );
+}
+
+#[get("/api/v1/auth/users/{name:.*}/followers")]
+pub async fn followers_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<PageQuery>,
+) -> impl Responder {
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
+
+    let name: String = req.match_info().get("name").unwrap().to_string();
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req.clone(), data.clone()).await;
+    let (viewer, is_staff) = match token_user {
+        Some(Ok(u)) => (
+            Option::Some(u.user.username),
+            u.level.permissions.contains(&String::from("ManageUsers")),
+        ),
+        _ => (Option::None, false),
+    };
+
+    if data.db.is_hidden_from(name.clone(), viewer.clone()).await {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("404: Not Found");
+    }
+
+    if !is_staff && !data.db.can_view_follows(name.clone(), viewer.clone()).await {
+        return HttpResponse::Forbidden()
+            .append_header(("Content-Type", "text/plain"))
+            .body("This account is private.");
+    }
+
+    // get followers
+    let res: DefaultReturn<Option<Page<db::Log>>> =
+        data.db.get_user_followers(name.to_owned(), &info).await;
+
+    let res: DefaultReturn<Option<Page<UserSummary>>> = match res.payload {
+        Some(Page { items, next, prev }) => DefaultReturn {
+            success: true,
+            message: res.message,
+            payload: Option::Some(Page {
+                items: data.db.summarize_follows(items, true, viewer).await,
+                next,
+                prev,
+            }),
+        },
+        None => DefaultReturn {
+            success: false,
+            message: res.message,
+            payload: Option::None,
+        },
+    };
+
+    // return
+    pagination::page_response(&req, &info, res)
+}
+
+#[get("/api/v1/auth/users/{name:.*}/following")]
+pub async fn following_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<PageQuery>,
+) -> impl Responder {
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
+
+    let name: String = req.match_info().get("name").unwrap().to_string();
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req.clone(), data.clone()).await;
+    let (viewer, is_staff) = match token_user {
+        Some(Ok(u)) => (
+            Option::Some(u.user.username),
+            u.level.permissions.contains(&String::from("ManageUsers")),
+        ),
+        _ => (Option::None, false),
+    };
+
+    if data.db.is_hidden_from(name.clone(), viewer.clone()).await {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("404: Not Found");
+    }
+
+    if !is_staff && !data.db.can_view_follows(name.clone(), viewer.clone()).await {
+        return HttpResponse::Forbidden()
+            .append_header(("Content-Type", "text/plain"))
+            .body("This account is private.");
+    }
+
+    // get following
+    let res: DefaultReturn<Option<Page<db::Log>>> =
+        data.db.get_user_following(name.to_owned(), &info).await;
+
+    let res: DefaultReturn<Option<Page<UserSummary>>> = match res.payload {
+        Some(Page { items, next, prev }) => DefaultReturn {
+            success: true,
+            message: res.message,
+            payload: Option::Some(Page {
+                items: data.db.summarize_follows(items, false, viewer).await,
+                next,
+                prev,
+            }),
+        },
+        None => DefaultReturn {
+            success: false,
+            message: res.message,
+            payload: Option::None,
+        },
+    };
+
+    // return
+    pagination::page_response(&req, &info, res)
+}
+
+#[get("/api/v1/auth/users/{name:.*}/follow-requests")]
+/// Get the pending follow requests sent to a user
+pub async fn follow_requests_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<PageQuery>,
+) -> impl Responder {
+    list_follow_requests(req, data, info, true).await
+}
+
+#[get("/api/v1/auth/users/{name:.*}/follow-requests/sent")]
+/// Get the pending follow requests sent by a user
+pub async fn sent_follow_requests_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<PageQuery>,
+) -> impl Responder {
+    list_follow_requests(req, data, info, false).await
+}
+
+/// List the pending follow requests sent to (or by) a user
+async fn list_follow_requests(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<PageQuery>,
+    incoming: bool,
+) -> HttpResponse {
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
+
+    let name: String = req.match_info().get("name").unwrap().to_string();
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req.clone(), data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    // make sure we have permission to view this user's requests
+    let token_user = token_user.unwrap().ok().unwrap();
+    let can_view: bool = (token_user.user.username == name)
+        | (token_user
+            .level
+            .permissions
+            .contains(&String::from("ManageUsers")));
+
+    if !can_view {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("You do not have permission to manage this user's contents.");
+    }
+
+    // get requests
+    let res: DefaultReturn<Option<Page<db::Log>>> = if incoming {
+        data.db.get_follow_requests(name.clone(), &info).await
+    } else {
+        data.db.get_sent_follow_requests(name.clone(), &info).await
+    };
+
+    let res: DefaultReturn<Option<Page<UserSummary>>> = match res.payload {
+        Some(Page { items, next, prev }) => DefaultReturn {
+            success: true,
+            message: res.message,
+            payload: Option::Some(Page {
+                items: data
+                    .db
+                    .summarize_follows(items, incoming, Option::Some(name))
+                    .await,
+                next,
+                prev,
+            }),
+        },
+        None => DefaultReturn {
+            success: false,
+            message: res.message,
+            payload: Option::None,
+        },
+    };
+
+    // return
+    pagination::page_response(&req, &info, res)
+}
+
+#[post("/api/v1/auth/users/{name:.*}/follow-requests/{requester}")]
+/// Approve a pending follow request
+pub async fn approve_follow_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    answer_follow_request(req, data, true).await
+}
+
+#[delete("/api/v1/auth/users/{name:.*}/follow-requests/{requester}")]
+/// Reject a pending follow request
+pub async fn reject_follow_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    answer_follow_request(req, data, false).await
+}
+
+/// Approve (or reject) a pending follow request
+async fn answer_follow_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    approve: bool,
+) -> HttpResponse {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+    let requester: String = req.match_info().get("requester").unwrap().to_string();
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    // make sure we have permission to manage this user's requests
+    let token_user = token_user.unwrap().ok().unwrap();
+    let can_manage: bool = (token_user.user.username == name)
+        | (token_user
+            .level
+            .permissions
+            .contains(&String::from("ManageUsers")));
+
+    if !can_manage {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("You do not have permission to manage this user's contents.");
+    }
+
+    // ...
+    let props = UserFollow {
+        user: requester,
+        is_following: name,
+    };
+
+    let res = if approve {
+        data.db.approve_follow_request(&props).await
+    } else {
+        data.db.remove_follow_request(&props).await
+    };
+
+    let res: DefaultReturn<bool> = match res {
+        Ok(changed) => DefaultReturn {
+            success: true,
+            message: String::from(match (approve, changed) {
+                (true, _) => "Follow request approved.",
+                (false, true) => "Follow request rejected.",
+                (false, false) => "Follow request does not exist.",
+            }),
+            payload: changed,
+        },
+        Err(db::LogError::NotFound) => DefaultReturn {
+            success: false,
+            message: String::from("Follow request does not exist."),
+            payload: false,
+        },
+        Err(e) => e.into(),
+    };
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(serde_json::to_string::<DefaultReturn<bool>>(&res).unwrap())
+}
+
+#[get("/api/v1/auth/users/{name:.*}/avatar")]
+pub async fn avatar_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<AvatarQueryProps>,
+) -> impl Responder {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+    let size = info.size.unwrap_or(identicon::DEFAULT_SIZE);
+
+    // make sure profile exists
+    let profile = data.db.get_user_by_username(name.to_owned()).await;
+
+    if profile.is_err() {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "application/json"))
+            .body(
                 serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
                     success: false,
                     message: String::from("Profile does not exist!"),
@@ -549,44 +1508,402 @@ pub async fn avatar_request(req: HttpRequest, data: web::Data<AppData>) -> impl
     let profile = profile.ok().unwrap();
     let user = profile.user.metadata;
 
+    // check uploads
+    if let Some(bytes) = data
+        .media
+        .get(&Slot::Avatar.key(&profile.user.username))
+        .await
+    {
+        if let Some(content_type) = avatar::detect_image_type(&bytes) {
+            return avatar::image_response(
+                &req,
+                &Image {
+                    content_type,
+                    bytes,
+                },
+                300,
+            );
+        }
+    }
+
+    // check remote avatar
     if user.avatar_url.is_none() {
-        return HttpResponse::NotFound().body("User does not have an avatar set");
+        return avatar::default_response(&profile.user.username, size, Option::None);
     }
 
-    let avatar = user.avatar_url.unwrap();
+    let avatar_url = user.avatar_url.unwrap();
+
+    // check cache
+    if let Some(image) = avatar::get_cached(&data.config.avatars, &avatar_url).await {
+        data.db
+            .metrics
+            .inc("shuttle_avatar_fetches_total", &[("outcome", "cache_hit")]);
+
+        return avatar::image_response(&req, &image, data.config.avatars.cache_ttl);
+    }
 
     // fetch avatar
-    let res = data
-        .http_client
-        .get(avatar)
-        .timeout(std::time::Duration::from_millis(5_000))
-        .insert_header(("User-Agent", "swmff-shuttle/1.0"))
-        .send()
-        .await;
+    let image = match avatar::fetch(
+        &data.http_client,
+        avatar_url.clone(),
+        data.config.avatars.max_size,
+    )
+    .await
+    {
+        Ok(i) => i,
+        Err(e) => {
+            data.db
+                .metrics
+                .inc("shuttle_avatar_fetches_total", &[("outcome", e.kind())]);
 
-    if res.is_err() {
-        return HttpResponse::NotFound().body(format!(
-            "Failed to fetch avatar on server: {}",
-            res.err().unwrap()
-        ));
+            return avatar::default_response(&profile.user.username, size, Option::Some(e));
+        }
+    };
+
+    data.db
+        .metrics
+        .inc("shuttle_avatar_fetches_total", &[("outcome", "fetched")]);
+
+    avatar::set_cached(&data.config.avatars, &avatar_url, &image).await;
+
+    // return
+    avatar::image_response(&req, &image, data.config.avatars.cache_ttl)
+}
+
+#[get("/api/v1/auth/users/{name:.*}/identicon")]
+/// Generated default avatar, this never changes so it can be cached forever
+pub async fn identicon_request(
+    req: HttpRequest,
+    info: web::Query<AvatarQueryProps>,
+) -> impl Responder {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+    let size = info.size.unwrap_or(identicon::DEFAULT_SIZE);
+
+    // return
+    avatar::image_response(
+        &req,
+        &Image {
+            content_type: "image/svg+xml",
+            bytes: identicon::generate(&name, size).into_bytes(),
+        },
+        60 * 60 * 24 * 365,
+    )
+}
+
+#[get("/api/v1/auth/users/{name:.*}/banner")]
+pub async fn banner_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+
+    // make sure profile exists
+    let profile = data.db.get_user_by_username(name.to_owned()).await;
+
+    if profile.is_err() {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Profile does not exist!"),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
+
+    let profile = profile.ok().unwrap();
+
+    // check uploads
+    let bytes = match data
+        .media
+        .get(&Slot::Banner.key(&profile.user.username))
+        .await
+    {
+        Some(b) => b,
+        None => return HttpResponse::NotFound().body("User does not have a banner set"),
+    };
+
+    let content_type = match avatar::detect_image_type(&bytes) {
+        Some(c) => c,
+        None => return HttpResponse::NotFound().body("User does not have a banner set"),
+    };
+
+    // return
+    avatar::image_response(
+        &req,
+        &Image {
+            content_type,
+            bytes,
+        },
+        300,
+    )
+}
+
+/// Upload an image to a user's `slot`, or remove it if `payload` is `None`
+async fn update_media(
+    req: HttpRequest,
+    payload: Option<web::Payload>,
+    data: web::Data<AppData>,
+    slot: Slot,
+) -> HttpResponse {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    // make sure profile exists
+    let profile = data.db.get_user_by_username(name.to_owned()).await;
+
+    if profile.is_err() {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Profile does not exist!"),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+    let profile = profile.ok().unwrap();
+
+    // check if we can update this user
+    // must be authenticated AND same user OR staff
+    let can_update: bool = (token_user.user.username == profile.user.username)
+        | (token_user
+            .level
+            .permissions
+            .contains(&String::from("ManageUsers")));
+
+    if !can_update {
+        return HttpResponse::NotFound()
+            .body("You do not have permission to manage this user's contents.");
     }
 
     // ...
-    let mut res = res.unwrap();
-    let body = res.body().limit(10_000_000).await;
+    let key = slot.key(&profile.user.username);
+
+    let res = match payload {
+        Some(payload) => {
+            // read upload
+            let bytes = match payload.to_bytes_limited(data.config.media.max_size).await {
+                Ok(Ok(b)) => b,
+                _ => {
+                    return HttpResponse::PayloadTooLarge()
+                        .append_header(("Content-Type", "application/json"))
+                        .body(
+                            serde_json::to_string::<DefaultReturn<()>>(&DefaultReturn {
+                                success: false,
+                                message: MediaError::TooLarge.to_string(),
+                                payload: (),
+                            })
+                            .unwrap(),
+                        )
+                }
+            };
+
+            // process (resizing is slow, so it's done off the worker thread)
+            match web::block(move || media::process_upload(&bytes, slot)).await {
+                Ok(Ok(image)) => data.media.put(&key, image.bytes).await,
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err(MediaError::Storage),
+            }
+        }
+        None => data.media.delete(&key).await,
+    };
 
-    if body.is_err() {
-        return HttpResponse::NotFound().body(
-            "Failed to fetch avatar on server (image likely too large, please keep under 10 MB)",
-        );
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<()>>(&match res {
+                Ok(_) => DefaultReturn {
+                    success: true,
+                    message: String::from("Image updated!"),
+                    payload: (),
+                },
+                Err(e) => DefaultReturn {
+                    success: false,
+                    message: e.to_string(),
+                    payload: (),
+                },
+            })
+            .unwrap(),
+        )
+}
+
+#[post("/api/v1/auth/users/{name:.*}/avatar")]
+/// Upload an avatar (raw image body)
+pub async fn upload_avatar_request(
+    req: HttpRequest,
+    body: web::Payload,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    update_media(req, Option::Some(body), data, Slot::Avatar).await
+}
+
+#[delete("/api/v1/auth/users/{name:.*}/avatar")]
+/// Remove an uploaded avatar
+pub async fn delete_avatar_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_media(req, Option::None, data, Slot::Avatar).await
+}
+
+#[post("/api/v1/auth/users/{name:.*}/banner")]
+/// Upload a banner (raw image body)
+pub async fn upload_banner_request(
+    req: HttpRequest,
+    body: web::Payload,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    update_media(req, Option::Some(body), data, Slot::Banner).await
+}
+
+#[delete("/api/v1/auth/users/{name:.*}/banner")]
+/// Remove an uploaded banner
+pub async fn delete_banner_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_media(req, Option::None, data, Slot::Banner).await
+}
+
+#[post("/api/v1/auth/users/{name:.*}/fields/{key}/verify")]
+/// Verify a custom field's link (the linked page must link back with `rel="me"`)
+pub async fn verify_field_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    let name: String = req.match_info().get("name").unwrap().to_string();
+    let key: String = req.match_info().get("key").unwrap().to_string();
+
+    // the link back is checked against our public URL, which we can't take
+    // from the request (the client controls the Host header)
+    if data.config.public_url.is_empty() {
+        return HttpResponse::NotImplemented()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Link verification is not enabled on this server"),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req.clone(), data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    // make sure profile exists
+    let profile = data.db.get_user_by_username(name.to_owned()).await;
+
+    if profile.is_err() {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Profile does not exist!"),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+    let profile = profile.ok().unwrap();
+
+    // check if we can update this user
+    // must be authenticated AND same user OR staff
+    let can_update: bool = (token_user.user.username == profile.user.username)
+        | (token_user
+            .level
+            .permissions
+            .contains(&String::from("ManageUsers")));
+
+    if !can_update {
+        return HttpResponse::NotFound()
+            .body("You do not have permission to manage this user's contents.");
     }
 
-    let body = body.unwrap();
+    // get field
+    let mut fields = data.db.get_profile_fields(name.clone()).await;
+
+    let field = match fields.iter_mut().find(|f| f.key == key) {
+        Some(f) if f.is_link() => f,
+        _ => {
+            return HttpResponse::NotFound()
+                .append_header(("Content-Type", "application/json"))
+                .body(
+                    serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                        success: false,
+                        message: String::from("Field does not exist or is not a link!"),
+                        payload: Option::None,
+                    })
+                    .unwrap(),
+                );
+        }
+    };
+
+    // the linked page must link back to this profile
+    let profile_url = format!(
+        "{}/{}",
+        data.config.public_url.trim_end_matches("/"),
+        profile.user.username
+    );
+
+    // fetch linked page
+    let page = match avatar::fetch_bytes(&data.http_client, field.value.clone(), 1_000_000).await {
+        Ok(p) => String::from_utf8_lossy(&p).to_string(),
+        Err(e) => {
+            return HttpResponse::Ok()
+                .append_header(("Content-Type", "application/json"))
+                .body(
+                    serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                        success: false,
+                        message: format!("Failed to fetch linked page: {e}"),
+                        payload: Option::None,
+                    })
+                    .unwrap(),
+                );
+        }
+    };
+
+    // check for link back
+    field.verified = metadata::has_rel_me_link(&page, &profile_url);
+    let field = field.clone();
+
+    let res = data.db.set_profile_fields(name, fields).await;
+
+    if !res.success {
+        return HttpResponse::Ok()
+            .append_header(("Content-Type", "application/json"))
+            .body(serde_json::to_string(&res).unwrap());
+    }
 
     // return
-    return HttpResponse::Ok()
-        .append_header(("Content-Type", res.content_type()))
-        .body(body);
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<ProfileField>>(&DefaultReturn {
+                success: field.verified,
+                message: if field.verified {
+                    String::from("Link verified!")
+                } else {
+                    format!("Linked page does not contain a rel=\"me\" link to {profile_url}")
+                },
+                payload: field,
+            })
+            .unwrap(),
+        )
 }
 
 #[get("/api/v1/auth/users/{name:.*}/level")]
@@ -596,7 +1913,7 @@ pub async fn level_request(req: HttpRequest, data: web::Data<AppData>) -> impl R
     // get user
     let res = data.db.get_user_by_username(name.to_owned()).await;
 
-    if res.is_ok() == false {
+    if res.is_err() {
         return HttpResponse::Ok()
             .append_header(("Content-Type", "application/json"))
             .body(
@@ -610,7 +1927,7 @@ pub async fn level_request(req: HttpRequest, data: web::Data<AppData>) -> impl R
     }
 
     // return
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Content-Type", "application/json"))
-        .body(serde_json::to_string::<db::RoleLevel>(&res.ok().unwrap().level).unwrap());
+        .body(serde_json::to_string::<db::RoleLevel>(&res.ok().unwrap().level).unwrap())
 }
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use crate::db::AppData;
+use crate::events::Event;
+
+/// How often a comment is sent to keep idle streams open
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Default, PartialEq, serde::Deserialize)]
+pub struct EventsQuery {
+    /// Only send events involving this user (staff only, users always only
+    /// receive events involving themselves)
+    pub user: Option<String>,
+    /// Only send events of these kinds (comma separated)
+    pub kinds: Option<String>,
+}
+
+/// Which events a stream receives
+struct EventFilter {
+    user: Option<String>,
+    kinds: Option<Vec<String>>,
+}
+
+impl EventFilter {
+    fn matches(&self, event: &Event) -> bool {
+        if let Some(ref user) = self.user {
+            if !event.involves(user) {
+                return false;
+            }
+        }
+
+        match self.kinds {
+            Some(ref kinds) => kinds.contains(&event.kind),
+            None => true,
+        }
+    }
+}
+
+#[get("/api/v1/events")]
+/// Stream realtime [`Event`]s as Server-Sent Events
+pub async fn events_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<EventsQuery>,
+) -> impl Responder {
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+
+    // staff can see every event, everybody else only sees their own
+    let is_staff = token_user
+        .level
+        .permissions
+        .contains(&String::from("ManageUsers"));
+
+    let filter = EventFilter {
+        user: if is_staff {
+            info.user.clone()
+        } else {
+            Option::Some(token_user.user.username)
+        },
+        kinds: info
+            .kinds
+            .as_ref()
+            .map(|k| k.split(",").map(|k| k.trim().to_string()).collect()),
+    };
+
+    // stream
+    let stream = futures_util::stream::unfold(
+        (data.db.events.subscribe(), filter),
+        |(mut receiver, filter)| async move {
+            let message = next_message(&mut receiver, &filter).await?;
+            Some((
+                Ok::<Bytes, actix_web::Error>(Bytes::from(message)),
+                (receiver, filter),
+            ))
+        },
+    );
+
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "text/event-stream"))
+        .append_header(("Cache-Control", "no-cache"))
+        .append_header(("X-Accel-Buffering", "no"))
+        .streaming(stream)
+}
+
+/// Wait for the next message to send (`None` if the stream should end)
+async fn next_message(receiver: &mut Receiver<Event>, filter: &EventFilter) -> Option<String> {
+    loop {
+        tokio::select! {
+            res = receiver.recv() => match res {
+                Ok(event) if filter.matches(&event) => return Some(event.to_sse()),
+                Ok(_) => continue,
+                // the client fell behind, let them know events were missed
+                Err(RecvError::Lagged(count)) => return Some(format!(": missed {count} events\n\n")),
+                Err(RecvError::Closed) => return None,
+            },
+            _ = tokio::time::sleep(KEEPALIVE) => return Some(String::from(": keepalive\n\n")),
+        }
+    }
+}
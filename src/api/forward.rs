@@ -0,0 +1,157 @@
+//! Forward authentication for reverse proxies (nginx `auth_request`, traefik
+//! `forwardAuth`, caddy `forward_auth`).
+//!
+//! The proxy sends every request for a protected tool to
+//! `/api/v1/auth/forward` first, and only lets it through if it gets a `200`.
+//! The `X-Shuttle-User` and `X-Shuttle-Role` response headers can be copied onto
+//! the proxied request.
+//!
+//! Tools on other hosts need `forward_auth.cookie_domain` (so they receive the
+//! session cookie) and must be listed in `forward_auth.allowed_hosts` (so users
+//! are sent back to them after logging in).
+use actix_web::{route, web, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+
+use crate::db::AppData;
+use crate::logging;
+
+#[derive(Default, Deserialize)]
+pub struct ForwardQuery {
+    /// Comma separated roles, the user must have one of them
+    #[serde(default)]
+    pub role: Option<String>,
+    /// Comma separated permissions, the user must have all of them
+    #[serde(default)]
+    pub permission: Option<String>,
+    /// Redirect to the login page instead of returning `401` (for proxies which
+    /// pass the response on to the client, like traefik and caddy)
+    #[serde(default)]
+    pub redirect: bool,
+}
+
+/// Get the URL of the original request from the headers set by the proxy
+///
+/// Uses `X-Forwarded-Proto`, `X-Forwarded-Host` and `X-Forwarded-Uri` (traefik,
+/// caddy), falling back to `X-Original-URL` (nginx).
+fn original_url(req: &HttpRequest) -> Option<String> {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.to_string())
+    };
+
+    if let (Some(host), Some(uri)) = (header("X-Forwarded-Host"), header("X-Forwarded-Uri")) {
+        let proto = header("X-Forwarded-Proto").unwrap_or(String::from("https"));
+        return Option::Some(format!("{proto}://{host}{uri}"));
+    }
+
+    header("X-Original-URL")
+}
+
+/// Build the response for a request without a valid login
+fn unauthorized(req: &HttpRequest, data: &AppData, redirect: bool) -> HttpResponse {
+    if !redirect {
+        return HttpResponse::Unauthorized()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    // `forward_auth.root` is the public URL of this instance, needed when the
+    // protected tool is on another host
+    let root = data.config.forward_auth.root.trim_end_matches("/");
+
+    // always log in through our own callback (the login page hands the token to
+    // it), which then sends the user back to the tool (see `forward_auth.allowed_hosts`)
+    let mut location = format!(
+        "{root}/flow/auth/login?callback={}",
+        urlencoding::encode(&format!("{root}/api/v1/auth/callback"))
+    );
+
+    if let Some(next) = original_url(req) {
+        location.push_str(&format!("&next={}", urlencoding::encode(&next)));
+    }
+
+    HttpResponse::Found()
+        .append_header(("Location", location))
+        .finish()
+}
+
+#[route("/api/v1/auth/forward", method = "GET", method = "HEAD")]
+/// Check the `__Secure-Token` cookie of a request forwarded by a reverse proxy
+///
+/// Returns `200` with `X-Shuttle-User`/`X-Shuttle-Role` if the user is allowed
+/// through, `401` (or a redirect to the login page) if they aren't logged in, and
+/// `403` if they don't have the required role/permissions (see [`ForwardQuery`]).
+pub async fn forward_request(
+    req: HttpRequest,
+    info: web::Query<ForwardQuery>,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    // get user
+    let token = match req.cookie("__Secure-Token") {
+        Some(c) => c.value().to_string(),
+        None => {
+            log::debug!(target: "shuttle::auth", "forward auth denied: no token");
+            return unauthorized(&req, &data, info.redirect);
+        }
+    };
+
+    let user = match data.db.get_user_by_unhashed(token.clone()).await {
+        Ok(u) => u,
+        Err(_) => {
+            log::info!(
+                target: "shuttle::auth",
+                token = logging::fingerprint(&token).as_str();
+                "forward auth denied: invalid token"
+            );
+
+            return unauthorized(&req, &data, info.redirect);
+        }
+    };
+
+    // check role and permissions
+    let denied = if user.user.role == "banned" {
+        Option::Some("banned")
+    } else if info
+        .role
+        .as_ref()
+        .is_some_and(|roles| !roles.split(",").any(|r| r.trim() == user.level.name))
+    {
+        Option::Some("missing role")
+    } else if info.permission.as_ref().is_some_and(|permissions| {
+        !permissions
+            .split(",")
+            .all(|p| user.level.permissions.contains(&p.trim().to_string()))
+    }) {
+        Option::Some("missing permission")
+    } else {
+        Option::None
+    };
+
+    if let Some(reason) = denied {
+        log::info!(
+            target: "shuttle::auth",
+            username = user.user.username.as_str(),
+            reason = reason;
+            "forward auth denied: {reason}"
+        );
+
+        return HttpResponse::Forbidden()
+            .append_header(("Content-Type", "text/plain"))
+            .body("You do not have permission to do this");
+    }
+
+    log::debug!(
+        target: "shuttle::auth",
+        username = user.user.username.as_str();
+        "forward auth allowed"
+    );
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("X-Shuttle-User", user.user.username))
+        .append_header(("X-Shuttle-Role", user.level.name))
+        .append_header(("Cache-Control", "no-store"))
+        .finish()
+}
@@ -0,0 +1,83 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use serde::Serialize;
+
+use crate::db::{AppData, DefaultReturn};
+use crate::services::constant_time_eq;
+
+/// The result of a readiness check
+#[derive(Serialize)]
+pub struct Readiness {
+    /// If the database is reachable
+    pub database: bool,
+    /// If the cache (redis) is reachable
+    pub cache: bool,
+}
+
+#[get("/healthz")]
+/// Check if the process is up
+pub async fn healthz_request() -> impl Responder {
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "text/plain"))
+        .append_header(("Cache-Control", "no-store"))
+        .body("ok")
+}
+
+#[get("/readyz")]
+/// Check if the database and cache are reachable (`503` if either isn't)
+pub async fn readyz_request(data: web::Data<AppData>) -> impl Responder {
+    let (database, cache) = data.db.check_ready().await;
+    let ready = database & cache;
+
+    if ready {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    }
+    .append_header(("Content-Type", "application/json"))
+    .append_header(("Cache-Control", "no-store"))
+    .body(
+        serde_json::to_string::<DefaultReturn<Readiness>>(&DefaultReturn {
+            success: ready,
+            message: String::from(if ready { "Ready" } else { "Not ready" }),
+            payload: Readiness { database, cache },
+        })
+        .unwrap(),
+    )
+}
+
+#[get("/metrics")]
+/// Export metrics in the Prometheus text format (see [`crate::metrics`])
+///
+/// Only served if `metrics.enabled` is set, and only to scrapers sending
+/// `metrics.token` (if one is set).
+pub async fn metrics_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    let config = &data.config.metrics;
+
+    if !config.enabled {
+        return HttpResponse::NotFound().body("Metrics are disabled");
+    }
+
+    if !config.token.is_empty() {
+        let given = req
+            .headers()
+            .get("Authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        // compare hashes so the token's length isn't leaked either
+        if !constant_time_eq(
+            dorsal::utility::hash(given.to_string()).as_bytes(),
+            dorsal::utility::hash(config.token.clone()).as_bytes(),
+        ) {
+            return HttpResponse::Unauthorized()
+                .append_header(("WWW-Authenticate", "Bearer"))
+                .body("Invalid metrics token");
+        }
+    }
+
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "text/plain; version=0.0.4"))
+        .append_header(("Cache-Control", "no-store"))
+        .body(data.db.metrics.render())
+}
@@ -0,0 +1,120 @@
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppData, DefaultReturn};
+use crate::logging;
+
+#[derive(Deserialize)]
+pub struct IntrospectRequest {
+    pub token: String,
+    /// `token` or `secondary_token` (both are tried if not given)
+    #[serde(default)]
+    pub token_type_hint: Option<String>,
+}
+
+/// Information about a user token, returned to service clients
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Introspection {
+    /// If the token belongs to a user which isn't banned (every other field is
+    /// empty if `false`)
+    pub active: bool,
+    pub username: Option<String>,
+    pub role: Option<String>,
+    pub permissions: Vec<String>,
+    pub elevation: Option<i32>,
+    /// `token` or `secondary_token`
+    pub token_type: Option<String>,
+    /// How long this result may be cached (seconds)
+    pub cache_ttl: u64,
+}
+
+#[post("/api/v1/auth/introspect")]
+/// Get information about a user token (service clients only, see [`crate::services`])
+pub async fn introspect_request(
+    req: HttpRequest,
+    body: web::Json<IntrospectRequest>,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    // check client
+    let client = match data.services.authenticate(&req) {
+        Some(c) => c,
+        None => {
+            log::warn!(target: "shuttle::auth", "introspection denied: invalid client credentials");
+
+            return HttpResponse::Unauthorized()
+                .append_header(("WWW-Authenticate", "Basic realm=\"shuttle\""))
+                .append_header(("Content-Type", "application/json"))
+                .body(
+                    serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                        success: false,
+                        message: String::from("Invalid client credentials"),
+                        payload: Option::None,
+                    })
+                    .unwrap(),
+                );
+        }
+    };
+
+    // get user
+    let hint = body.token_type_hint.as_deref();
+    let mut res = Option::None;
+
+    if hint != Some("secondary_token") {
+        if let Ok(user) = data.db.get_user_by_unhashed(body.token.clone()).await {
+            res = Option::Some((user, "token"));
+        }
+    }
+
+    if res.is_none() & (hint != Some("token")) {
+        if let Ok(user) = data.db.get_user_by_unhashed_st(body.token.clone()).await {
+            res = Option::Some((user, "secondary_token"));
+        }
+    }
+
+    // build result
+    let introspection = match res {
+        Some((user, token_type)) if user.user.role != "banned" => Introspection {
+            active: true,
+            username: Option::Some(user.user.username),
+            role: Option::Some(user.level.name),
+            permissions: user.level.permissions,
+            elevation: Option::Some(user.level.elevation),
+            token_type: Option::Some(token_type.to_string()),
+            cache_ttl: data.config.introspection.cache_ttl,
+        },
+        _ => Introspection::default(),
+    };
+
+    log::debug!(
+        target: "shuttle::auth",
+        client = client.as_str(),
+        token = logging::fingerprint(&body.token).as_str(),
+        active = introspection.active;
+        "introspected token"
+    );
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .append_header((
+            "Cache-Control",
+            if introspection.active {
+                format!("private, max-age={}", introspection.cache_ttl)
+            } else {
+                String::from("no-store")
+            },
+        ))
+        .append_header(("Vary", "Authorization"))
+        .body(
+            serde_json::to_string::<DefaultReturn<Introspection>>(&DefaultReturn {
+                success: true,
+                message: if introspection.active {
+                    String::from("Token is active")
+                } else {
+                    String::from("Token is not active")
+                },
+                payload: introspection,
+            })
+            .unwrap(),
+        )
+}
@@ -0,0 +1,168 @@
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppData, DefaultReturn};
+use crate::jwt::{self, Claims, Jwks, SigningKey};
+
+#[derive(Deserialize)]
+pub struct IssueToken {
+    /// Must be one of `jwt.audiences`
+    pub audience: String,
+    /// Seconds until the token expires (capped at `jwt.ttl`)
+    #[serde(default)]
+    pub ttl: Option<u64>,
+}
+
+/// A newly issued token
+#[derive(Serialize, Deserialize)]
+pub struct IssuedToken {
+    pub token: String,
+    pub token_type: String,
+    pub expires_in: u64,
+    pub claims: Claims,
+}
+
+/// Build an error response
+fn error(status: StatusCode, message: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                success: false,
+                message: message.to_string(),
+                payload: Option::None,
+            })
+            .unwrap(),
+        )
+}
+
+#[post("/api/v1/auth/jwt")]
+/// Issue a signed JWT for the current user
+pub async fn issue_request(
+    req: HttpRequest,
+    body: web::Json<IssueToken>,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    let config = &data.config.jwt;
+
+    if config.audiences.is_empty() {
+        return error(StatusCode::NOT_FOUND, "JWT issuing is disabled");
+    }
+
+    if !config.audiences.contains(&body.audience) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            &format!("Audience must be any of: {}", config.audiences.join(", ")),
+        );
+    }
+
+    // get current user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    if token_user.is_none() {
+        return error(
+            StatusCode::NOT_ACCEPTABLE,
+            "An account is required to do this",
+        );
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+
+    if token_user.user.role == "banned" {
+        return error(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to do this",
+        );
+    }
+
+    // sign
+    let key = match data.db.get_current_signing_key().await {
+        Some(k) => k,
+        None => {
+            return error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "No signing key has been published yet",
+            )
+        }
+    };
+
+    let ttl = body.ttl.unwrap_or(config.ttl).clamp(1, config.ttl);
+    let now = (dorsal::utility::unix_epoch_timestamp() / 1000) as u64;
+
+    let claims = Claims {
+        iss: config.issuer.clone(),
+        sub: token_user.user.username,
+        aud: body.audience.clone(),
+        iat: now,
+        exp: now + ttl,
+        jti: dorsal::utility::random_id(),
+        role: token_user.level.name,
+        permissions: token_user.level.permissions,
+        elevation: token_user.level.elevation,
+    };
+
+    let token = match key.sign(&claims) {
+        Some(t) => t,
+        None => return error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to sign token"),
+    };
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .append_header(("Cache-Control", "no-store"))
+        .body(
+            serde_json::to_string::<DefaultReturn<IssuedToken>>(&DefaultReturn {
+                success: true,
+                message: String::from("Token issued!"),
+                payload: IssuedToken {
+                    token,
+                    token_type: String::from("Bearer"),
+                    expires_in: ttl,
+                    claims,
+                },
+            })
+            .unwrap(),
+        )
+}
+
+#[get("/.well-known/jwks.json")]
+/// Get the [`Jwks`] tokens are signed with
+pub async fn jwks_request(data: web::Data<AppData>) -> impl Responder {
+    let jwks = Jwks {
+        keys: data
+            .db
+            .get_signing_keys()
+            .await
+            .iter()
+            .map(SigningKey::jwk)
+            .collect(),
+    };
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .append_header((
+            "Cache-Control",
+            format!("public, max-age={}", jwt::PUBLISH_DELAY),
+        ))
+        .body(serde_json::to_string::<Jwks>(&jwks).unwrap())
+}
+
+#[post("/api/v1/auth/jwks/rotate")]
+/// Publish a new signing key, used for new tokens once it has been published for
+/// [`jwt::PUBLISH_DELAY`] (staff only)
+///
+/// Old keys stay published until every token they signed has expired.
+pub async fn rotate_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    if let Err(res) = crate::api::webhooks::get_staff(req, data.clone()).await {
+        return res;
+    }
+
+    let res = data.db.create_signing_key().await;
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(serde_json::to_string::<DefaultReturn<Option<SigningKey>>>(&res).unwrap())
+}
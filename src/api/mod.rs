@@ -1 +1,9 @@
 pub mod auth;
+pub mod events;
+pub mod forward;
+pub mod health;
+pub mod introspect;
+pub mod jwt;
+pub mod notifications;
+pub mod setup;
+pub mod webhooks;
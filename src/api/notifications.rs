@@ -0,0 +1,129 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+
+use crate::db::{AppData, DefaultReturn, Notification};
+use crate::pagination::{self, Page, PageQuery};
+
+#[get("/api/v1/notifications")]
+/// Get the current user's [`Notification`]s
+pub async fn notifications_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<PageQuery>,
+) -> impl Responder {
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req.clone(), data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+
+    // return
+    let res: DefaultReturn<Option<Page<Notification>>> = data
+        .db
+        .get_user_notifications(token_user.user.username, &info)
+        .await;
+
+    pagination::page_response(&req, &info, res)
+}
+
+#[get("/api/v1/notifications/count")]
+/// Get the amount of unread [`Notification`]s the current user has
+pub async fn count_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+
+    // return
+    let count = data
+        .db
+        .get_unread_notification_count(token_user.user.username)
+        .await;
+
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<usize>>(&DefaultReturn {
+                success: true,
+                message: String::from("Unread notifications"),
+                payload: count,
+            })
+            .unwrap(),
+        )
+}
+
+#[post("/api/v1/notifications/read")]
+/// Mark all of the current user's [`Notification`]s as read
+pub async fn read_all_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_notifications(req, data, true).await
+}
+
+#[post("/api/v1/notifications/{id}/read")]
+/// Mark a [`Notification`] as read
+pub async fn read_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_notifications(req, data, true).await
+}
+
+#[delete("/api/v1/notifications")]
+/// Clear all of the current user's [`Notification`]s
+pub async fn clear_all_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_notifications(req, data, false).await
+}
+
+#[delete("/api/v1/notifications/{id}")]
+/// Delete a [`Notification`]
+pub async fn clear_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    update_notifications(req, data, false).await
+}
+
+/// Mark the current user's notifications as read (or clear them)
+///
+/// Only the notification with the `id` in the path is changed if one is given.
+async fn update_notifications(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    read: bool,
+) -> HttpResponse {
+    let id: Option<String> = req.match_info().get("id").map(|id| id.to_string());
+
+    // get token user
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this");
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+
+    // ...
+    let res = if read {
+        data.db
+            .mark_notifications_read(token_user.user.username, id)
+            .await
+    } else {
+        data.db
+            .clear_notifications(token_user.user.username, id)
+            .await
+    };
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(serde_json::to_string::<DefaultReturn<bool>>(&res).unwrap())
+}
@@ -0,0 +1,89 @@
+use actix_web::{post, web, HttpResponse, Responder};
+use serde::Deserialize;
+
+use crate::db::{AppData, DefaultReturn};
+use crate::setup;
+
+#[derive(Deserialize)]
+pub struct SetupInfo {
+    /// the setup token logged at startup
+    pub token: String,
+    pub username: String,
+}
+
+#[post("/api/v1/setup")]
+/// Create the first admin account (see [`crate::setup`])
+pub async fn setup_request(body: web::Json<SetupInfo>, data: web::Data<AppData>) -> impl Responder {
+    // check token (it's consumed so setup can't run twice at once)
+    if !data.setup.take(body.token.trim()) {
+        return HttpResponse::Forbidden()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Invalid setup token, or setup is already finished."),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
+
+    // an admin could have been created since startup (ex: through the cli)
+    if data.db.has_admin().await {
+        return HttpResponse::Forbidden()
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<Option<String>>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Setup is already finished."),
+                    payload: Option::None,
+                })
+                .unwrap(),
+            );
+    }
+
+    // seed levels (before creating the user, so the admin role exists)
+    data.db.seed_role_levels().await;
+
+    // create user
+    let username = body.username.trim().to_string();
+    let res = data.db.create_user(username.clone()).await;
+
+    if !res.success {
+        data.setup.restore(body.token.trim().to_string());
+
+        return HttpResponse::Ok()
+            .append_header(("Content-Type", "application/json"))
+            .body(serde_json::to_string(&res).unwrap());
+    }
+
+    // make user admin
+    let role = data
+        .db
+        .set_user_role(username.clone(), String::from(setup::ADMIN_ROLE))
+        .await;
+
+    if !role.success {
+        // don't leave a user behind which setup can't be retried with
+        data.db.delete_user(username).await;
+        data.setup.restore(body.token.trim().to_string());
+
+        return HttpResponse::Ok()
+            .append_header(("Content-Type", "application/json"))
+            .body(serde_json::to_string(&role).unwrap());
+    }
+
+    log::info!(
+        target: "shuttle::auth",
+        username = body.username.trim();
+        "setup finished, the setup token is no longer valid"
+    );
+
+    // return
+    let set_cookie = crate::pages::base::token_cookie(&data.config, Option::Some(&res.message));
+
+    HttpResponse::Ok()
+        .append_header(("Set-Cookie", set_cookie.as_str()))
+        .append_header(("Content-Type", "application/json"))
+        .body(serde_json::to_string(&res).unwrap())
+}
@@ -0,0 +1,225 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppData, DefaultReturn};
+use crate::pagination::{self, Page, PageQuery};
+use crate::webhooks::{self, Webhook, WebhookDelivery, WebhookPayload};
+
+#[derive(Deserialize)]
+pub struct CreateWebhook {
+    pub url: String,
+    /// Generated if not given
+    #[serde(default)]
+    pub secret: Option<String>,
+    pub events: Vec<String>,
+}
+
+/// A newly created [`Webhook`] (the secret is never shown again)
+#[derive(Serialize)]
+pub struct CreatedWebhook {
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+/// Get the username of the current user if they're staff (`ManageUsers`)
+pub async fn get_staff(req: HttpRequest, data: web::Data<AppData>) -> Result<String, HttpResponse> {
+    let (_, _, token_user) = crate::pages::base::check_auth_status(req, data).await;
+
+    if token_user.is_none() {
+        return Err(HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("An account is required to do this"));
+    }
+
+    let token_user = token_user.unwrap().ok().unwrap();
+
+    if !token_user
+        .level
+        .permissions
+        .contains(&String::from("ManageUsers"))
+    {
+        return Err(HttpResponse::NotAcceptable()
+            .append_header(("Content-Type", "text/plain"))
+            .body("Only staff can do this"));
+    }
+
+    Ok(token_user.user.username)
+}
+
+#[get("/api/v1/webhooks")]
+/// Get every registered [`Webhook`] (staff only)
+pub async fn list_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    if let Err(res) = get_staff(req, data.clone()).await {
+        return res;
+    }
+
+    // return
+    return HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<Vec<Webhook>>>(&DefaultReturn {
+                success: true,
+                message: String::from("Webhooks exist"),
+                payload: data.db.get_webhooks().await,
+            })
+            .unwrap(),
+        );
+}
+
+#[post("/api/v1/webhooks")]
+/// Register a new [`Webhook`] (staff only)
+pub async fn create_request(
+    req: HttpRequest,
+    body: web::Json<CreateWebhook>,
+    data: web::Data<AppData>,
+) -> impl Responder {
+    let username = match get_staff(req, data.clone()).await {
+        Ok(u) => u,
+        Err(res) => return res,
+    };
+
+    // ...
+    let secret = body
+        .secret
+        .clone()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(dorsal::utility::random_id);
+
+    let res = data
+        .db
+        .create_webhook(
+            body.url.trim().to_string(),
+            secret.clone(),
+            body.events.clone(),
+            username,
+            data.config.webhooks.allow_private_addresses,
+        )
+        .await;
+
+    let res: DefaultReturn<Option<CreatedWebhook>> = DefaultReturn {
+        success: res.success,
+        message: res.message,
+        payload: res
+            .payload
+            .map(|webhook| CreatedWebhook { webhook, secret }),
+    };
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(serde_json::to_string(&res).unwrap())
+}
+
+#[delete("/api/v1/webhooks/{id}")]
+/// Delete a [`Webhook`] (staff only)
+pub async fn delete_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    let id: String = req.match_info().get("id").unwrap().to_string();
+
+    if let Err(res) = get_staff(req, data.clone()).await {
+        return res;
+    }
+
+    // return
+    return HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<bool>>(&data.db.delete_webhook(id).await)
+                .unwrap(),
+        );
+}
+
+#[get("/api/v1/webhooks/{id}/deliveries")]
+/// Get the delivery log of a [`Webhook`] (staff only)
+pub async fn deliveries_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<PageQuery>,
+) -> impl Responder {
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
+
+    let id: String = req.match_info().get("id").unwrap().to_string();
+
+    if let Err(res) = get_staff(req.clone(), data.clone()).await {
+        return res;
+    }
+
+    // ...
+    let res = data.db.get_webhook_deliveries(id, &info).await;
+
+    let res: DefaultReturn<Option<Page<WebhookDelivery>>> = DefaultReturn {
+        success: res.success,
+        message: res.message,
+        payload: res.payload.map(|p| {
+            p.map(|log| serde_json::from_str::<WebhookDelivery>(&log.content).unwrap_or_default())
+        }),
+    };
+
+    // return
+    pagination::page_response(&req, &info, res)
+}
+
+#[post("/api/v1/webhooks/{id}/test")]
+/// Send a `ping` event to a [`Webhook`] (staff only, not retried)
+pub async fn test_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    let id: String = req.match_info().get("id").unwrap().to_string();
+
+    let username = match get_staff(req, data.clone()).await {
+        Ok(u) => u,
+        Err(res) => return res,
+    };
+
+    let webhook = match data.db.get_webhook(id).await {
+        Some(w) => w,
+        None => {
+            return HttpResponse::NotFound()
+                .append_header(("Content-Type", "application/json"))
+                .body(
+                    serde_json::to_string::<DefaultReturn<Option<WebhookDelivery>>>(
+                        &DefaultReturn {
+                            success: false,
+                            message: String::from("Webhook does not exist!"),
+                            payload: Option::None,
+                        },
+                    )
+                    .unwrap(),
+                );
+        }
+    };
+
+    // ...
+    let delivery = webhooks::deliver(
+        data.db.clone(),
+        data.http_client.clone(),
+        webhook,
+        WebhookPayload {
+            id: dorsal::utility::random_id(),
+            event: String::from("ping"),
+            timestamp: dorsal::utility::unix_epoch_timestamp(),
+            username: username.clone(),
+            actor: Option::Some(username),
+        },
+        webhooks::RetryPolicy {
+            max_attempts: 1,
+            base_delay: std::time::Duration::ZERO,
+            allow_private_addresses: data.config.webhooks.allow_private_addresses,
+        },
+    )
+    .await;
+
+    // return
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "application/json"))
+        .body(
+            serde_json::to_string::<DefaultReturn<Option<WebhookDelivery>>>(&DefaultReturn {
+                success: delivery.success,
+                message: match delivery.status {
+                    Some(status) => format!("Receiver responded with {status}"),
+                    None => delivery.error.clone().unwrap_or_default(),
+                },
+                payload: Option::Some(delivery),
+            })
+            .unwrap(),
+        )
+}
@@ -0,0 +1,324 @@
+//! # Avatars
+//!
+//! Safely fetching and caching remote avatars.
+//!
+//! Avatars are only fetched from public addresses (checked after DNS resolution,
+//! and the request is pinned to the checked address), and are only served if
+//! their contents are actually an image.
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use actix_web::http::Uri;
+use actix_web::{web, HttpRequest, HttpResponse};
+use hex_fmt::HexFmt;
+use sha2::{Digest, Sha256};
+
+use crate::config::AvatarsConfig;
+use crate::identicon;
+use crate::net::{self, NetError};
+
+/// The most redirects followed when fetching an avatar
+const MAX_REDIRECTS: usize = 3;
+
+/// A fetched (or cached) image
+#[derive(Clone)]
+pub struct Image {
+    /// The image's content type (detected from its contents)
+    pub content_type: &'static str,
+    /// The image's contents
+    pub bytes: Vec<u8>,
+}
+
+impl Image {
+    /// Get the `ETag` of this image
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", HexFmt(Sha256::digest(&self.bytes)))
+    }
+}
+
+/// Avatar errors
+#[derive(Debug)]
+pub enum AvatarError {
+    /// The URL is not a valid `http`/`https` URL
+    InvalidUrl,
+    /// The URL resolves to an address we won't fetch from
+    ForbiddenAddress,
+    /// The upstream request failed
+    Upstream,
+    /// The upstream response is too large
+    TooLarge,
+    /// The upstream response is not a supported image
+    NotAnImage,
+}
+
+impl std::fmt::Display for AvatarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use AvatarError::*;
+        f.write_str(match self {
+            InvalidUrl => "Avatar URL is invalid.",
+            ForbiddenAddress => "Avatar URL points to a forbidden address.",
+            Upstream => "Failed to fetch avatar on server.",
+            TooLarge => "Avatar is too large.",
+            NotAnImage => "Avatar is not a supported image.",
+        })
+    }
+}
+
+impl AvatarError {
+    /// A short name for this error (used as a metrics label)
+    pub fn kind(&self) -> &'static str {
+        use AvatarError::*;
+        match self {
+            InvalidUrl => "invalid_url",
+            ForbiddenAddress => "forbidden_address",
+            Upstream => "upstream",
+            TooLarge => "too_large",
+            NotAnImage => "not_an_image",
+        }
+    }
+}
+
+impl From<NetError> for AvatarError {
+    fn from(e: NetError) -> Self {
+        match e {
+            NetError::InvalidUrl => AvatarError::InvalidUrl,
+            NetError::Unresolvable => AvatarError::Upstream,
+            NetError::ForbiddenAddress => AvatarError::ForbiddenAddress,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AvatarError>;
+
+/// Detect the content type of an image from its magic bytes
+///
+/// Only raster formats are accepted (SVGs can carry scripts).
+///
+/// # Arguments:
+/// * `bytes` - the image's contents
+pub fn detect_image_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Option::Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Option::Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") | bytes.starts_with(b"GIF89a") {
+        Option::Some("image/gif")
+    } else if (bytes.len() >= 12) && (&bytes[0..4] == b"RIFF") && (&bytes[8..12] == b"WEBP") {
+        Option::Some("image/webp")
+    } else if (bytes.len() >= 12) && (&bytes[4..12] == b"ftypavif") {
+        Option::Some("image/avif")
+    } else {
+        Option::None
+    }
+}
+
+/// Fetch a remote avatar
+///
+/// # Arguments:
+/// * `client` - the HTTP client to use
+/// * `url` - the avatar's URL
+/// * `max_size` - the largest avatar accepted (in bytes)
+pub async fn fetch(client: &awc::Client, url: String, max_size: usize) -> Result<Image> {
+    let bytes = fetch_bytes(client, url, max_size).await?;
+
+    match detect_image_type(&bytes) {
+        Some(content_type) => Ok(Image {
+            content_type,
+            bytes,
+        }),
+        None => Err(AvatarError::NotAnImage),
+    }
+}
+
+/// Fetch the body of a remote URL, only connecting to public addresses
+///
+/// Redirects are followed manually (up to [`MAX_REDIRECTS`]) so that every hop
+/// is checked, meaning `client` should have redirects disabled.
+///
+/// # Arguments:
+/// * `client` - the HTTP client to use
+/// * `url` - the URL to fetch
+/// * `limit` - the largest body accepted (in bytes)
+pub async fn fetch_bytes(client: &awc::Client, url: String, limit: usize) -> Result<Vec<u8>> {
+    let mut uri: Uri = match url.parse() {
+        Ok(u) => u,
+        Err(_) => return Err(AvatarError::InvalidUrl),
+    };
+
+    for _ in 0..=MAX_REDIRECTS {
+        let addr = net::resolve_public(&uri).await?;
+
+        let mut res = match client
+            .get(uri.clone())
+            .address(addr) // connect to the address we checked, not whatever DNS says now
+            .timeout(Duration::from_millis(5_000))
+            .insert_header(("User-Agent", "swmff-shuttle/1.0"))
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(_) => return Err(AvatarError::Upstream),
+        };
+
+        // follow redirect
+        if res.status().is_redirection() {
+            let location = match res.headers().get("Location").and_then(|l| l.to_str().ok()) {
+                Some(l) => l.to_string(),
+                None => return Err(AvatarError::Upstream),
+            };
+
+            uri = match location.parse::<Uri>() {
+                // relative redirect
+                Ok(l) if l.host().is_none() => {
+                    let mut parts = uri.clone().into_parts();
+                    parts.path_and_query = l.path_and_query().cloned();
+
+                    match Uri::from_parts(parts) {
+                        Ok(u) => u,
+                        Err(_) => return Err(AvatarError::InvalidUrl),
+                    }
+                }
+                Ok(l) => l,
+                Err(_) => return Err(AvatarError::InvalidUrl),
+            };
+
+            continue;
+        }
+
+        if !res.status().is_success() {
+            return Err(AvatarError::Upstream);
+        }
+
+        // read body
+        return match res.body().limit(limit).await {
+            Ok(b) => Ok(b.to_vec()),
+            Err(_) => Err(AvatarError::TooLarge),
+        };
+    }
+
+    Err(AvatarError::Upstream)
+}
+
+/// Get the path an avatar URL is cached at
+///
+/// # Arguments:
+/// * `config` - [`AvatarsConfig`]
+/// * `url` - the avatar's URL
+fn cache_path(config: &AvatarsConfig, url: &str) -> PathBuf {
+    PathBuf::from(&config.cache_dir).join(dorsal::utility::hash(url.to_string()))
+}
+
+/// Get an avatar from the cache if it hasn't expired
+///
+/// The file is read on the blocking thread pool.
+///
+/// # Arguments:
+/// * `config` - [`AvatarsConfig`]
+/// * `url` - the avatar's URL
+pub async fn get_cached(config: &AvatarsConfig, url: &str) -> Option<Image> {
+    let path = cache_path(config, url);
+    let ttl = config.cache_ttl;
+
+    web::block(move || {
+        let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+
+        if age.as_secs() >= ttl {
+            return Option::None;
+        }
+
+        let bytes = std::fs::read(path).ok()?;
+
+        Option::Some(Image {
+            content_type: detect_image_type(&bytes)?,
+            bytes,
+        })
+    })
+    .await
+    .ok()?
+}
+
+/// Store an avatar in the cache
+///
+/// The file is written on the blocking thread pool.
+///
+/// # Arguments:
+/// * `config` - [`AvatarsConfig`]
+/// * `url` - the avatar's URL
+/// * `image` - the fetched [`Image`]
+pub async fn set_cached(config: &AvatarsConfig, url: &str, image: &Image) {
+    let path = cache_path(config, url);
+    let bytes = image.bytes.clone();
+
+    let _ = web::block(move || {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+
+        std::fs::write(path, bytes)
+    })
+    .await;
+}
+
+/// Build the response for an [`Image`], answering with `304 Not Modified` if the
+/// client already has it
+///
+/// # Arguments:
+/// * `req` - the request being answered
+/// * `image` - the [`Image`] being served
+/// * `max_age` - how long the image can be cached by clients (in seconds)
+pub fn image_response(req: &HttpRequest, image: &Image, max_age: u64) -> HttpResponse {
+    let etag = image.etag();
+    let cache_control = format!("public, max-age={max_age}");
+
+    let if_none_match = req
+        .headers()
+        .get("If-None-Match")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+
+    if if_none_match.split(",").any(|t| t.trim() == etag) {
+        return HttpResponse::NotModified()
+            .append_header(("ETag", etag))
+            .append_header(("Cache-Control", cache_control))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .append_header(("Content-Type", image.content_type))
+        .append_header(("ETag", etag))
+        .append_header(("Cache-Control", cache_control))
+        .append_header(("X-Content-Type-Options", "nosniff"))
+        .append_header(("Content-Security-Policy", "default-src 'none'"))
+        .body(image.bytes.clone())
+}
+
+/// Build the response for a user without a (working) avatar
+///
+/// Users without an avatar are redirected to their identicon (which can be
+/// cached for much longer, since it never changes), while failed avatars are
+/// answered with the identicon directly so the error can be included.
+///
+/// # Arguments:
+/// * `username` - the user's username
+/// * `size` - the requested identicon size
+/// * `error` - why the default avatar is being served instead (if it isn't just unset)
+pub fn default_response(username: &str, size: u32, error: Option<AvatarError>) -> HttpResponse {
+    match error {
+        Some(e) => HttpResponse::Ok()
+            .append_header(("Content-Type", "image/svg+xml"))
+            .append_header(("X-Content-Type-Options", "nosniff"))
+            .append_header(("Content-Security-Policy", "default-src 'none'"))
+            .append_header(("Cache-Control", "no-cache"))
+            .append_header(("X-Avatar-Error", e.to_string()))
+            .body(identicon::generate(username, size)),
+        // relative to ".../{name}/avatar"
+        None => HttpResponse::Found()
+            .append_header(("Location", format!("identicon?size={size}")))
+            .append_header(("Cache-Control", "public, max-age=300"))
+            .finish(),
+    }
+}
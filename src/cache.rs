@@ -0,0 +1,58 @@
+//! # Cache
+//!
+//! A shared async redis connection.
+//!
+//! Anything which talks to redis while a request is being handled must go
+//! through an [`AsyncRedis`], so a slow (or missing) redis never blocks an actix
+//! worker thread.
+use std::sync::Arc;
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+use redis::RedisResult;
+use tokio::sync::OnceCell;
+
+/// How long connecting to redis (or a single command) may take
+pub const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A redis connection which is created on first use and reconnects whenever it
+/// drops
+///
+/// Cloning an [`AsyncRedis`] shares the underlying connection.
+#[derive(Clone)]
+pub struct AsyncRedis {
+    client: redis::Client,
+    connection: Arc<OnceCell<ConnectionManager>>,
+}
+
+impl AsyncRedis {
+    /// Create a new [`AsyncRedis`] (without connecting)
+    ///
+    /// # Arguments:
+    /// * `client` - the redis client to connect with
+    pub fn new(client: redis::Client) -> AsyncRedis {
+        AsyncRedis {
+            client,
+            connection: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// Get the connection, connecting first if needed
+    ///
+    /// If connecting fails, the next call tries again.
+    pub async fn get(&self) -> RedisResult<ConnectionManager> {
+        self.connection
+            .get_or_try_init(|| {
+                ConnectionManager::new_with_backoff_and_timeouts(
+                    self.client.clone(),
+                    2,
+                    100,
+                    1, // callers fail open, don't keep them waiting
+                    TIMEOUT,
+                    TIMEOUT,
+                )
+            })
+            .await
+            .cloned()
+    }
+}
@@ -0,0 +1,141 @@
+//! # Captcha
+//!
+//! Pluggable human (or at least "expensive robot") verification for registration.
+//!
+//! The default verifier is [`ProofOfWork`], which is enabled by setting
+//! `registration.pow_difficulty` to the amount of leading zeros required.
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+/// A challenge handed to the client before it registers
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct Challenge {
+    /// The challenge identifier, sent back with the solution
+    pub challenge: String,
+    /// Verifier specific difficulty (leading zeros for [`ProofOfWork`])
+    pub difficulty: usize,
+}
+
+/// A captcha verifier
+///
+/// Implement this to plug any other verification method into registration.
+pub trait CaptchaVerifier: Send + Sync {
+    /// Create a new [`Challenge`]. Returns `None` if this verifier is disabled.
+    fn challenge(&self) -> Option<Challenge>;
+
+    /// Verify a `solution` for `challenge`. Challenges should only be accepted once.
+    ///
+    /// # Arguments:
+    /// * `challenge` - the challenge identifier given by [`CaptchaVerifier::challenge`]
+    /// * `solution` - the solution sent by the client
+    fn verify(&self, challenge: &str, solution: &str) -> bool;
+}
+
+/// Verifier which accepts everything
+pub struct NoCaptcha;
+
+impl CaptchaVerifier for NoCaptcha {
+    fn challenge(&self) -> Option<Challenge> {
+        Option::None
+    }
+
+    fn verify(&self, _challenge: &str, _solution: &str) -> bool {
+        true
+    }
+}
+
+/// Hashcash-style proof of work
+///
+/// The client must find a `solution` such that `sha256(challenge + solution)`
+/// (as hex) starts with `difficulty` zeros.
+#[derive(Clone)]
+pub struct ProofOfWork {
+    pub difficulty: usize,
+    issued: Arc<Mutex<Issued>>,
+}
+
+/// Challenges that have been issued but not solved yet
+#[derive(Default)]
+struct Issued {
+    /// Challenge -> when it was issued
+    challenges: HashMap<String, u128>,
+    /// Every issued challenge (solved ones included) in the order it was issued
+    order: VecDeque<(String, u128)>,
+}
+
+/// How long a challenge can go unsolved before it expires (10 minutes)
+const CHALLENGE_LIFETIME: u128 = 1000 * 60 * 10;
+
+/// The most challenges kept at once (the oldest are dropped first)
+const MAX_ISSUED: usize = 10_000;
+
+impl ProofOfWork {
+    /// Create a new [`ProofOfWork`] verifier
+    ///
+    /// # Arguments:
+    /// * `difficulty` - the amount of leading zeros required
+    pub fn new(difficulty: usize) -> ProofOfWork {
+        ProofOfWork {
+            difficulty,
+            issued: Arc::new(Mutex::new(Issued::default())),
+        }
+    }
+}
+
+impl CaptchaVerifier for ProofOfWork {
+    fn challenge(&self) -> Option<Challenge> {
+        let now = dorsal::utility::unix_epoch_timestamp();
+        let challenge = dorsal::utility::random_id();
+
+        let mut issued = self.issued.lock().unwrap();
+
+        // drop expired challenges, and the oldest ones when there are too many
+        while let Some((_, t)) = issued.order.front() {
+            if (issued.order.len() < MAX_ISSUED) && (now.saturating_sub(*t) < CHALLENGE_LIFETIME) {
+                break;
+            }
+
+            if let Some((id, _)) = issued.order.pop_front() {
+                issued.challenges.remove(&id);
+            }
+        }
+
+        issued.challenges.insert(challenge.clone(), now);
+        issued.order.push_back((challenge.clone(), now));
+
+        Option::Some(Challenge {
+            challenge,
+            difficulty: self.difficulty,
+        })
+    }
+
+    fn verify(&self, challenge: &str, solution: &str) -> bool {
+        // challenges can only be used once
+        let issued_at = match self.issued.lock().unwrap().challenges.remove(challenge) {
+            Some(t) => t,
+            None => return false,
+        };
+
+        if dorsal::utility::unix_epoch_timestamp().saturating_sub(issued_at) >= CHALLENGE_LIFETIME {
+            return false;
+        }
+
+        // check work
+        let hash = dorsal::utility::hash(format!("{challenge}{solution}"));
+        hash.starts_with(&"0".repeat(self.difficulty))
+    }
+}
+
+/// Create the [`CaptchaVerifier`] for a proof-of-work difficulty ([`NoCaptcha`] if `0`)
+///
+/// # Arguments:
+/// * `difficulty` - the amount of leading zeros required
+pub fn from_difficulty(difficulty: usize) -> Arc<dyn CaptchaVerifier> {
+    if difficulty == 0 {
+        return Arc::new(NoCaptcha);
+    }
+
+    Arc::new(ProofOfWork::new(difficulty))
+}
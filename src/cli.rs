@@ -0,0 +1,265 @@
+//! Command line interface
+//!
+//! Admin commands operate directly on the [`Database`], without going through HTTP.
+use serde::Serialize;
+
+use shuttle::db::{Database, DefaultReturn, UserSettings};
+use shuttle::metadata::ProfileField;
+
+pub const HELP: &str = "Shuttle - a bare-bones user authentication system
+
+Usage: shuttle [COMMAND] [OPTIONS]
+
+Commands:
+  serve                      Start the server (default)
+  create-user <USERNAME>     Create a user and print their ID
+  set-role <USERNAME> <ROLE> Set the role of a user (banned works like ban)
+  ban <USERNAME>             Ban a user (staff accounts too)
+  unban <USERNAME>           Unban a user (resetting their role to member)
+  rotate-id <USERNAME>       Replace the ID of a user and print it
+  list-users                 List every user
+  migrate                    Create missing database tables and role levels
+  export [USERNAME]          Print every user (or one user) as JSON
+  help                       Print this message
+
+Options:
+  --config <PATH>            Load configuration from PATH (default: ./shuttle.toml)
+  --port <PORT>              Port to serve on
+  --static-dir <PATH>        Directory to serve static files from
+  --db-type <TYPE>           sqlite, mysql or postgres
+  --log-level <FILTER>       Log level (ex: info, or info,shuttle::db=debug)
+  --log-format <FORMAT>      text or json
+  --print-config             Print the resolved configuration and exit
+  -h, --help                 Print this message
+";
+
+/// Options which take a value (so the value isn't mistaken for a positional argument)
+const VALUE_OPTIONS: [&str; 6] = [
+    "--config",
+    "--port",
+    "--static-dir",
+    "--db-type",
+    "--log-level",
+    "--log-format",
+];
+
+/// A parsed command
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Serve,
+    CreateUser(String),
+    SetRole(String, String),
+    Ban(String),
+    Unban(String),
+    RotateId(String),
+    ListUsers,
+    Migrate,
+    Export(Option<String>),
+    Help,
+}
+
+impl Command {
+    /// If this command needs the server to be configured (rather than just the database)
+    pub fn is_serve(&self) -> bool {
+        *self == Command::Serve
+    }
+}
+
+/// Get every positional argument (skipping the binary name and options)
+fn positional(args: &[String]) -> Vec<String> {
+    let mut output: Vec<String> = Vec::new();
+    let mut skip_value = false;
+
+    for arg in args.iter().skip(1) {
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+
+        if VALUE_OPTIONS.contains(&arg.as_str()) {
+            skip_value = true;
+            continue;
+        }
+
+        if arg.starts_with("-") {
+            continue;
+        }
+
+        output.push(arg.to_string());
+    }
+
+    output
+}
+
+/// Parse the [`Command`] given in `args`
+///
+/// # Arguments:
+/// * `args` - command line arguments (including the binary name)
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    if args.iter().any(|a| (a == "--help") | (a == "-h")) {
+        return Ok(Command::Help);
+    }
+
+    let positional = positional(args);
+    let command = positional.first().map(|c| c.as_str()).unwrap_or("serve");
+    let rest = &positional[positional.len().min(1)..];
+
+    // get the username (and other values) for commands which need them
+    let expect = |count: usize| -> Result<&[String], String> {
+        if rest.len() != count {
+            return Err(format!(
+                "{command} expects {count} argument{} (see shuttle --help)",
+                if count == 1 { "" } else { "s" }
+            ));
+        }
+
+        Ok(rest)
+    };
+
+    match command {
+        "serve" => expect(0).map(|_| Command::Serve),
+        "create-user" => expect(1).map(|a| Command::CreateUser(a[0].clone())),
+        "set-role" => expect(2).map(|a| Command::SetRole(a[0].clone(), a[1].clone())),
+        "ban" => expect(1).map(|a| Command::Ban(a[0].clone())),
+        "unban" => expect(1).map(|a| Command::Unban(a[0].clone())),
+        "rotate-id" => expect(1).map(|a| Command::RotateId(a[0].clone())),
+        "list-users" => expect(0).map(|_| Command::ListUsers),
+        "migrate" => expect(0).map(|_| Command::Migrate),
+        "export" if rest.len() <= 1 => Ok(Command::Export(rest.first().cloned())),
+        "export" => Err(String::from(
+            "export expects at most 1 argument (see shuttle --help)",
+        )),
+        "help" => Ok(Command::Help),
+        _ => Err(format!("unknown command {command:?} (see shuttle --help)")),
+    }
+}
+
+/// A user, as printed by `export`
+#[derive(Serialize)]
+struct ExportedUser {
+    username: String,
+    role: String,
+    timestamp: u128,
+    nickname: Option<String>,
+    about: String,
+    avatar_url: Option<String>,
+    fields: Vec<ProfileField>,
+    settings: UserSettings,
+}
+
+/// Print the message of a result (to stderr if it failed). Returns `res.success`
+fn report<T>(res: DefaultReturn<T>) -> bool {
+    if res.success {
+        println!("{}", res.message);
+    } else {
+        eprintln!("{}", res.message);
+    }
+
+    res.success
+}
+
+/// Run an admin [`Command`] (anything but [`Command::Serve`] and [`Command::Help`]).
+/// Returns `false` if the command failed
+///
+/// # Arguments:
+/// * `command` - the [`Command`]
+/// * `db` - [`Database`]
+pub async fn run(command: Command, db: Database) -> bool {
+    match command {
+        Command::CreateUser(username) => {
+            let res = db.create_user(username).await;
+
+            if !res.success {
+                eprintln!("{}", res.message);
+                return false;
+            }
+
+            // the message is the user's unhashed ID
+            println!("{}", res.message);
+        }
+        Command::SetRole(username, role) => {
+            // banning also notifies the user (and webhooks)
+            if role == "banned" {
+                return report(db.ban_user_by_name(username, true).await);
+            }
+
+            return report(db.set_user_role(username, role).await);
+        }
+        Command::Ban(username) => {
+            return report(db.ban_user_by_name(username, true).await);
+        }
+        Command::Unban(username) => {
+            return report(db.unban_user_by_name(username).await);
+        }
+        Command::RotateId(username) => {
+            let res = db.rotate_user_id(username).await;
+
+            if !res.success {
+                eprintln!("{}", res.message);
+                return false;
+            }
+
+            // the message is the user's new unhashed ID
+            println!("{}", res.message);
+        }
+        Command::ListUsers => {
+            let users = db.get_all_users().await;
+            let width = users
+                .iter()
+                .map(|u| u.username.len())
+                .max()
+                .unwrap_or(8)
+                .max(8);
+
+            println!("{:width$}  {:12}  JOINED", "USERNAME", "ROLE");
+
+            for user in users {
+                println!(
+                    "{:width$}  {:12}  {}",
+                    user.username, user.role, user.timestamp
+                );
+            }
+        }
+        Command::Migrate => {
+            db.init().await;
+
+            for level in db.seed_role_levels().await {
+                println!("Created role level: {level}");
+            }
+
+            println!("Database is up to date");
+        }
+        Command::Export(username) => {
+            let mut users = db.get_all_users().await;
+
+            if let Some(ref username) = username {
+                users.retain(|u| &u.username == username);
+
+                if users.is_empty() {
+                    eprintln!("User does not exist!");
+                    return false;
+                }
+            }
+
+            let mut output: Vec<ExportedUser> = Vec::new();
+
+            for user in users {
+                output.push(ExportedUser {
+                    fields: db.get_profile_fields(user.username.clone()).await,
+                    settings: db.get_user_settings(user.username.clone()).await,
+                    username: user.username,
+                    role: user.role,
+                    timestamp: user.timestamp,
+                    nickname: user.metadata.nickname,
+                    about: user.metadata.about,
+                    avatar_url: user.metadata.avatar_url,
+                });
+            }
+
+            println!("{}", serde_json::to_string_pretty(&output).unwrap());
+        }
+        Command::Serve | Command::Help => (),
+    }
+
+    true
+}
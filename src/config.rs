@@ -1,12 +1,51 @@
-use std::{env, ops::Index};
+//! # Config
+//!
+//! Server configuration, loaded (in order of priority, highest last) from:
+//! * defaults
+//! * a TOML file (`--config`, `SHUTTLE_CONFIG`, or `./shuttle.toml` if it exists)
+//! * environment variables (`DB_HOST`, `SITE_NAME`, `INVITE_CODES`, ...)
+//! * command line arguments (`--port`, `--db-type`, `--static-dir`, `--log-level`, `--log-format`)
+//!
+//! ```toml
+//! port = 8080
+//! site_name = "Shuttle"
+//!
+//! [database]
+//! type = "postgres"
+//! user = "shuttle"
+//! pass = "..."
+//! name = "shuttle"
+//!
+//! [registration]
+//! invite_codes = ["abc", "def"]
+//!
+//! [logging]
+//! level = "info,shuttle::db=debug"
+//! format = "json"
+//!
+//! [ratelimit.budgets]
+//! login = "10/600"
+//!
+//! [services.clients]
+//! billing = "..."
+//! ```
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::{env, fmt, path::Path};
+
+use crate::jwt::{self, JwtConfig};
+use crate::metadata::FieldSchema;
+use crate::ratelimit::{Budget, DEFAULT_BUDGETS};
 
 #[allow(dead_code)]
 pub fn collect_arguments() -> Vec<String> {
-    return env::args().collect::<Vec<String>>();
+    env::args().collect::<Vec<String>>()
 }
 
 #[allow(dead_code)]
-pub fn get_named_argument(args: &Vec<String>, name: &str) -> Option<String> {
+pub fn get_named_argument(args: &[String], name: &str) -> Option<String> {
     for (i, v) in args.iter().enumerate() {
         // if name does not match, continue
         if v != &format!("--{}", name) {
@@ -14,7 +53,7 @@ pub fn get_named_argument(args: &Vec<String>, name: &str) -> Option<String> {
         };
 
         // return value
-        let val: &String = args.index(i + 1);
+        let val: &String = args.get(i + 1)?;
 
         // ...make sure val exists (return None if it doesn't!)
         if val.is_empty() {
@@ -24,15 +63,929 @@ pub fn get_named_argument(args: &Vec<String>, name: &str) -> Option<String> {
         return Option::Some(String::from(val));
     }
 
-    return Option::None;
+    Option::None
+}
+
+/// Check if a flag (ex: `--print-config`) was given
+pub fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == &format!("--{}", name))
+}
+
+/// Get the value of a named argument, failing if the argument is given without one
+pub fn get_value_argument(args: &[String], name: &str) -> Result<Option<String>, ConfigError> {
+    match get_named_argument(args, name) {
+        Some(v) => Ok(Option::Some(v)),
+        None if has_flag(args, name) => Err(ConfigError::Value(format!("--{name}"), String::new())),
+        None => Ok(Option::None),
+    }
 }
 
 pub fn get_var(var: &str) -> Option<String> {
-    let res = env::var(var);
+    env::var(var).ok()
+}
+
+/// Parse an environment variable, failing if it's set to an invalid value
+fn parse_var<T: FromStr>(var: &str) -> Result<Option<T>, ConfigError> {
+    match get_var(var) {
+        Some(v) => match v.trim().parse::<T>() {
+            Ok(value) => Ok(Option::Some(value)),
+            Err(_) => Err(ConfigError::Value(var.to_string(), v)),
+        },
+        None => Ok(Option::None),
+    }
+}
+
+/// Split a comma separated list (ex: `a, b,c`), skipping empty entries
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(",")
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// Check that `value` is empty or an `http(s)://` URL
+fn is_url_or_empty(value: &str) -> bool {
+    value.is_empty() || value.starts_with("http://") || value.starts_with("https://")
+}
+
+/// An error loading the [`Config`]
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file couldn't be read
+    Read(String, std::io::Error),
+    /// The config file isn't valid TOML (or has unknown/mistyped keys)
+    Parse(String, toml::de::Error),
+    /// A command line argument or environment variable has an invalid value
+    Value(String, String),
+    /// The config failed [`Config::validate`]
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "failed to read config file {path}: {e}"),
+            ConfigError::Parse(path, e) => write!(f, "failed to parse config file {path}: {e}"),
+            ConfigError::Value(name, value) => write!(f, "invalid value for {name}: {value:?}"),
+            ConfigError::Invalid(errors) => {
+                write!(f, "invalid config:")?;
+
+                for e in errors {
+                    write!(f, "\n  - {e}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Database settings
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// `sqlite`, `mysql` or `postgres` (defaults to the compiled in database)
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub _type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    pub user: String,
+    pub pass: String,
+    pub name: String,
+}
+
+/// Registration settings
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistrationConfig {
+    /// Reject every new registration (the page still renders)
+    pub disabled: bool,
+    /// Require one of these codes to register (if not empty)
+    pub invite_codes: Vec<String>,
+    /// Leading zeros required by the proof-of-work captcha (disabled if `0`,
+    /// see [`crate::captcha`])
+    pub pow_difficulty: usize,
+}
+
+/// Logging settings (see [`crate::logging`])
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Log filter, either a level (ex: `info`) or `env_logger` directives
+    /// (ex: `info,shuttle::db=debug`)
+    pub level: String,
+    /// `text` or `json` (one object per line)
+    pub format: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: String::from("info"),
+            format: String::from("text"),
+        }
+    }
+}
+
+/// Rate limiting settings (see [`crate::ratelimit`])
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// `memory` (per-instance) or `redis` (shared between instances)
+    pub store: String,
+    /// Addresses of reverse proxies whose `X-Forwarded-For` header is trusted
+    /// (see [`crate::ratelimit::get_ip`])
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Budgets by name (`HITS/SECONDS`), replacing the ones in [`DEFAULT_BUDGETS`]
+    pub budgets: BTreeMap<String, Budget>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            store: String::from("memory"),
+            trusted_proxies: Vec::new(),
+            budgets: BTreeMap::new(),
+        }
+    }
+}
+
+/// Service client settings (see [`crate::services`])
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServicesConfig {
+    /// Client ID -> client secret
+    pub clients: BTreeMap<String, String>,
+}
+
+/// Webhook delivery settings (see [`crate::webhooks`])
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhooksConfig {
+    /// Attempts made to deliver an event before giving up
+    pub max_attempts: u32,
+    /// Seconds before the first retry (doubled after every attempt)
+    pub retry_delay: u64,
+    /// Allow webhooks on loopback and private addresses (for receivers inside
+    /// the same network, only enable this if every staff member is trusted)
+    pub allow_private_addresses: bool,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        WebhooksConfig {
+            max_attempts: 5,
+            retry_delay: 5,
+            allow_private_addresses: false,
+        }
+    }
+}
+
+/// Forward authentication settings (see `api::forward`)
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForwardAuthConfig {
+    /// The public URL of this instance, needed to send users to the login page
+    /// when the protected tool is on another host
+    pub root: String,
+    /// Set the session cookie for this domain (ex: `example.com`) instead of only
+    /// this host, so tools on other subdomains receive it
+    pub cookie_domain: String,
+    /// Hosts users can be sent back to after logging in (ex: `tool.example.com`)
+    pub allowed_hosts: Vec<String>,
+}
+
+/// Token introspection settings (see `api::introspect`)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntrospectionConfig {
+    /// How long services may cache an active token (seconds)
+    pub cache_ttl: u64,
+}
+
+impl Default for IntrospectionConfig {
+    fn default() -> Self {
+        IntrospectionConfig { cache_ttl: 60 }
+    }
+}
+
+/// Remote avatar settings (see [`crate::avatar`])
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AvatarsConfig {
+    /// The largest avatar fetched (in bytes)
+    pub max_size: usize,
+    /// How long fetched avatars are cached (seconds)
+    pub cache_ttl: u64,
+    /// Where fetched avatars are cached
+    pub cache_dir: String,
+}
+
+impl Default for AvatarsConfig {
+    fn default() -> Self {
+        AvatarsConfig {
+            max_size: 5_000_000,
+            cache_ttl: 60 * 60,
+            cache_dir: String::from("./.cache/avatars"),
+        }
+    }
+}
+
+/// Uploaded media settings (see [`crate::media`])
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MediaConfig {
+    /// Where uploaded images are stored
+    pub dir: String,
+    /// The largest upload accepted (in bytes)
+    pub max_size: usize,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        MediaConfig {
+            dir: String::from("./media"),
+            max_size: 5_000_000,
+        }
+    }
+}
+
+/// Realtime event settings (see [`crate::events`])
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventsConfig {
+    /// `memory` (per-instance) or `redis` (shared between instances)
+    pub store: String,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        EventsConfig {
+            store: String::from("memory"),
+        }
+    }
+}
+
+/// List pagination settings (see [`crate::pagination`])
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaginationConfig {
+    /// The largest page size a client can ask for
+    pub max_page_size: usize,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        PaginationConfig { max_page_size: 100 }
+    }
+}
+
+/// Metrics settings (see [`crate::metrics`])
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Serve `/metrics` (it's `404` otherwise)
+    pub enabled: bool,
+    /// Require `Authorization: Bearer {token}` for `/metrics` (if not empty)
+    pub token: String,
+}
+
+/// Server configuration
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    pub static_dir: String,
+    /// Shown in page titles and the navigation bar
+    pub site_name: String,
+    /// HTML embedded into the body of every page
+    pub body_embed: String,
+    /// Root URL of a Deducktive instance (used for reports)
+    pub deducktive_root: String,
+    /// The public URL of this instance (ex: `https://auth.example.com`), needed
+    /// to verify profile field links
+    pub public_url: String,
+    pub database: DatabaseConfig,
+    pub registration: RegistrationConfig,
+    pub logging: LoggingConfig,
+    pub ratelimit: RateLimitConfig,
+    pub services: ServicesConfig,
+    pub jwt: JwtConfig,
+    pub webhooks: WebhooksConfig,
+    pub forward_auth: ForwardAuthConfig,
+    pub introspection: IntrospectionConfig,
+    pub avatars: AvatarsConfig,
+    pub media: MediaConfig,
+    pub events: EventsConfig,
+    pub profile_fields: FieldSchema,
+    pub pagination: PaginationConfig,
+    pub metrics: MetricsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            port: 8080,
+            static_dir: String::from("./static"),
+            site_name: String::from("Shuttle"),
+            body_embed: String::new(),
+            deducktive_root: String::new(),
+            public_url: String::new(),
+            database: DatabaseConfig::default(),
+            registration: RegistrationConfig::default(),
+            logging: LoggingConfig::default(),
+            ratelimit: RateLimitConfig::default(),
+            services: ServicesConfig::default(),
+            jwt: JwtConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            forward_auth: ForwardAuthConfig::default(),
+            introspection: IntrospectionConfig::default(),
+            avatars: AvatarsConfig::default(),
+            media: MediaConfig::default(),
+            events: EventsConfig::default(),
+            profile_fields: FieldSchema::default(),
+            pagination: PaginationConfig::default(),
+            metrics: MetricsConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the [`Config`] from the config file, environment and `args`
+    ///
+    /// The result isn't validated, see [`Config::validate`].
+    ///
+    /// # Arguments:
+    /// * `args` - command line arguments
+    pub fn load(args: &[String]) -> Result<Config, ConfigError> {
+        let path = get_value_argument(args, "config")?.or(get_var("SHUTTLE_CONFIG"));
+
+        let mut config = match path {
+            Some(path) => Config::from_file(&path)?,
+            None if Path::new("shuttle.toml").exists() => Config::from_file("shuttle.toml")?,
+            None => Config::default(),
+        };
+
+        config.apply_env()?;
+        config.apply_args(args)?;
+
+        Ok(config)
+    }
+
+    /// Load a [`Config`] from a TOML file
+    ///
+    /// # Arguments:
+    /// * `path` - the path of the file
+    pub fn from_file(path: &str) -> Result<Config, ConfigError> {
+        let input =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_string(), e))?;
+
+        toml::from_str(&input).map_err(|e| ConfigError::Parse(path.to_string(), e))
+    }
+
+    /// Override values which are set in the environment
+    pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(v) = get_var("DB_TYPE") {
+            self.database._type = Option::Some(v);
+        }
+
+        if let Some(v) = get_var("DB_HOST") {
+            self.database.host = Option::Some(v);
+        }
+
+        if let Some(v) = get_var("DB_USER") {
+            self.database.user = v;
+        }
+
+        if let Some(v) = get_var("DB_PASS") {
+            self.database.pass = v;
+        }
+
+        if let Some(v) = get_var("DB_NAME") {
+            self.database.name = v;
+        }
+
+        if let Some(v) = get_var("SITE_NAME") {
+            self.site_name = v;
+        }
+
+        if let Some(v) = get_var("BODY_EMBED") {
+            self.body_embed = v;
+        }
+
+        if let Some(v) = get_var("DEDUCKTIVE_ROOT") {
+            self.deducktive_root = v;
+        }
+
+        if let Some(v) = get_var("PUBLIC_URL") {
+            self.public_url = v;
+        }
+
+        if let Some(v) = get_var("INVITE_CODES") {
+            self.registration.invite_codes = split_list(&v);
+        }
+
+        if let Some(v) = parse_var("REGISTRATION_DISABLED")? {
+            self.registration.disabled = v;
+        }
+
+        // RUST_LOG is still supported, but LOG_LEVEL takes priority
+        if let Some(v) = get_var("LOG_LEVEL").or(get_var("RUST_LOG")) {
+            self.logging.level = v;
+        }
+
+        if let Some(v) = get_var("LOG_FORMAT") {
+            self.logging.format = v;
+        }
+
+        if let Some(v) = parse_var("REGISTRATION_POW_DIFFICULTY")? {
+            self.registration.pow_difficulty = v;
+        }
+
+        // rate limits
+        if let Some(v) = get_var("RATELIMIT_STORE") {
+            self.ratelimit.store = v;
+        }
+
+        if let Some(v) = get_var("TRUSTED_PROXIES") {
+            self.ratelimit.trusted_proxies = Vec::new();
+
+            for ip in split_list(&v) {
+                match ip.parse::<IpAddr>() {
+                    Ok(ip) => self.ratelimit.trusted_proxies.push(ip),
+                    Err(_) => return Err(ConfigError::Value(String::from("TRUSTED_PROXIES"), v)),
+                }
+            }
+        }
+
+        if let Some(v) = get_var("RATELIMIT_BUDGETS") {
+            // name=HITS/SECONDS,...
+            for entry in split_list(&v) {
+                let budget = entry
+                    .split_once("=")
+                    .and_then(|(name, budget)| Option::Some((name, Budget::parse(budget)?)));
+
+                match budget {
+                    Some((name, budget)) => {
+                        self.ratelimit
+                            .budgets
+                            .insert(name.trim().to_string(), budget);
+                    }
+                    None => return Err(ConfigError::Value(String::from("RATELIMIT_BUDGETS"), v)),
+                }
+            }
+        }
+
+        if let Some(v) = get_var("REGISTER_RATE_LIMIT") {
+            match Budget::parse(&v) {
+                Some(b) => self.ratelimit.budgets.insert(String::from("register"), b),
+                None => return Err(ConfigError::Value(String::from("REGISTER_RATE_LIMIT"), v)),
+            };
+        }
+
+        if let Some(v) = get_var("LOGIN_RATE_LIMIT") {
+            match Budget::parse(&v) {
+                Some(b) => self.ratelimit.budgets.insert(String::from("login"), b),
+                None => return Err(ConfigError::Value(String::from("LOGIN_RATE_LIMIT"), v)),
+            };
+        }
+
+        // services
+        if let Some(v) = get_var("SERVICE_CLIENTS") {
+            // id:secret,...
+            for entry in split_list(&v) {
+                match entry.split_once(":") {
+                    Some((id, secret)) => {
+                        self.services
+                            .clients
+                            .insert(id.trim().to_string(), secret.trim().to_string());
+                    }
+                    None => {
+                        // don't echo the secrets back
+                        return Err(ConfigError::Value(
+                            String::from("SERVICE_CLIENTS"),
+                            String::from("(expected id:secret,...)"),
+                        ));
+                    }
+                }
+            }
+        }
+
+        // jwt
+        if let Some(v) = get_var("JWT_ISSUER") {
+            self.jwt.issuer = v;
+        }
+
+        if let Some(v) = get_var("JWT_AUDIENCES") {
+            self.jwt.audiences = split_list(&v);
+        }
+
+        if let Some(v) = parse_var("JWT_TTL")? {
+            self.jwt.ttl = v;
+        }
+
+        if let Some(v) = parse_var("JWT_KEY_ROTATION")? {
+            self.jwt.rotation = v;
+        }
+
+        // webhooks
+        if let Some(v) = parse_var("WEBHOOK_MAX_ATTEMPTS")? {
+            self.webhooks.max_attempts = v;
+        }
+
+        if let Some(v) = parse_var("WEBHOOK_RETRY_DELAY")? {
+            self.webhooks.retry_delay = v;
+        }
+
+        if let Some(v) = parse_var("WEBHOOK_ALLOW_PRIVATE_ADDRESSES")? {
+            self.webhooks.allow_private_addresses = v;
+        }
+
+        // service integrations
+        if let Some(v) = get_var("FORWARD_AUTH_ROOT") {
+            self.forward_auth.root = v;
+        }
+
+        if let Some(v) = get_var("FORWARD_AUTH_COOKIE_DOMAIN") {
+            self.forward_auth.cookie_domain = v;
+        }
+
+        if let Some(v) = get_var("FORWARD_AUTH_ALLOWED_HOSTS") {
+            self.forward_auth.allowed_hosts = split_list(&v);
+        }
+
+        if let Some(v) = parse_var("INTROSPECTION_CACHE_TTL")? {
+            self.introspection.cache_ttl = v;
+        }
+
+        // avatars and media
+        if let Some(v) = parse_var("AVATAR_MAX_SIZE")? {
+            self.avatars.max_size = v;
+        }
+
+        if let Some(v) = parse_var("AVATAR_CACHE_TTL")? {
+            self.avatars.cache_ttl = v;
+        }
+
+        if let Some(v) = get_var("AVATAR_CACHE_DIR") {
+            self.avatars.cache_dir = v;
+        }
+
+        if let Some(v) = get_var("MEDIA_DIR") {
+            self.media.dir = v;
+        }
+
+        if let Some(v) = parse_var("MEDIA_MAX_SIZE")? {
+            self.media.max_size = v;
+        }
+
+        // events
+        if let Some(v) = get_var("EVENTS_STORE") {
+            self.events.store = v;
+        }
+
+        // profile fields
+        if let Some(v) = parse_var("PROFILE_FIELDS_MAX")? {
+            self.profile_fields.max_fields = v;
+        }
+
+        if let Some(v) = get_var("PROFILE_FIELDS_KEYS") {
+            self.profile_fields.allowed_keys = split_list(&v);
+        }
+
+        if let Some(v) = parse_var("PROFILE_FIELDS_LABEL_LEN")? {
+            self.profile_fields.max_label_len = v;
+        }
+
+        if let Some(v) = parse_var("PROFILE_FIELDS_VALUE_LEN")? {
+            self.profile_fields.max_value_len = v;
+        }
+
+        // pagination
+        if let Some(v) = parse_var("PAGE_SIZE_MAX")? {
+            self.pagination.max_page_size = v;
+        }
+
+        // metrics
+        if let Some(v) = parse_var("METRICS_ENABLED")? {
+            self.metrics.enabled = v;
+        }
+
+        if let Some(v) = get_var("METRICS_TOKEN") {
+            self.metrics.token = v;
+        }
+
+        Ok(())
+    }
+
+    /// Override values which are given as command line arguments
+    ///
+    /// # Arguments:
+    /// * `args` - command line arguments
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), ConfigError> {
+        if let Some(port) = get_value_argument(args, "port")? {
+            self.port = port
+                .parse::<u16>()
+                .map_err(|_| ConfigError::Value(String::from("--port"), port))?;
+        }
+
+        if let Some(v) = get_value_argument(args, "db-type")? {
+            self.database._type = Option::Some(v);
+        }
+
+        if let Some(v) = get_value_argument(args, "static-dir")? {
+            self.static_dir = v;
+        }
+
+        if let Some(v) = get_value_argument(args, "log-level")? {
+            self.logging.level = v;
+        }
+
+        if let Some(v) = get_value_argument(args, "log-format")? {
+            self.logging.format = v;
+        }
+
+        Ok(())
+    }
+
+    /// Check that every value makes sense, returning every problem found
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors: Vec<String> = Vec::new();
+
+        if self.port == 0 {
+            errors.push(String::from("port must not be 0"));
+        }
+
+        if self.site_name.trim().is_empty() {
+            errors.push(String::from("site_name must not be empty"));
+        }
+
+        if !is_url_or_empty(&self.deducktive_root) {
+            errors.push(String::from(
+                "deducktive_root must start with http:// or https://",
+            ));
+        }
+
+        if !is_url_or_empty(&self.public_url) {
+            errors.push(String::from(
+                "public_url must start with http:// or https://",
+            ));
+        }
+
+        // database
+        match self.database._type.as_deref() {
+            None | Some("sqlite") => (),
+            Some("mysql") | Some("postgres") => {
+                for (key, value) in [
+                    ("user", &self.database.user),
+                    ("pass", &self.database.pass),
+                    ("name", &self.database.name),
+                ] {
+                    if value.is_empty() {
+                        errors.push(format!(
+                            "database.{key} is required for {} databases",
+                            self.database._type.as_ref().unwrap()
+                        ));
+                    }
+                }
+            }
+            Some(t) => errors.push(format!(
+                "database.type must be sqlite, mysql or postgres (got {t:?})"
+            )),
+        }
+
+        // registration
+        if self
+            .registration
+            .invite_codes
+            .iter()
+            .any(|c| c.trim().is_empty())
+        {
+            errors.push(String::from(
+                "registration.invite_codes must not contain empty codes",
+            ));
+        }
+
+        // logging
+        for directive in self.logging.level.split(",") {
+            // directives are either `LEVEL`, `MODULE` or `MODULE=LEVEL`
+            let level = match directive.split_once("=") {
+                Some((_, level)) => level,
+                None if directive.contains("::") => continue,
+                None => directive,
+            };
+
+            if level.trim().parse::<log::LevelFilter>().is_err() {
+                errors.push(format!(
+                    "logging.level has an invalid level {:?}",
+                    level.trim()
+                ));
+            }
+        }
+
+        if !["text", "json"].contains(&self.logging.format.as_str()) {
+            errors.push(format!(
+                "logging.format must be text or json (got {:?})",
+                self.logging.format
+            ));
+        }
+
+        // rate limits
+        if !["memory", "redis"].contains(&self.ratelimit.store.as_str()) {
+            errors.push(format!(
+                "ratelimit.store must be memory or redis (got {:?})",
+                self.ratelimit.store
+            ));
+        }
+
+        for (name, budget) in &self.ratelimit.budgets {
+            if !DEFAULT_BUDGETS.iter().any(|(n, _)| n == name) {
+                errors.push(format!("ratelimit.budgets has an unknown budget {name:?}"));
+            }
+
+            if budget.window == 0 {
+                errors.push(format!(
+                    "ratelimit.budgets.{name} must have a window of at least 1 second"
+                ));
+            }
+        }
+
+        // services
+        for (id, secret) in &self.services.clients {
+            if id.trim().is_empty() | id.contains(":") {
+                errors.push(format!("services.clients has an invalid client ID {id:?}"));
+            }
+
+            if secret.trim().is_empty() {
+                errors.push(format!(
+                    "services.clients.{id} must not have an empty secret"
+                ));
+            }
+        }
+
+        // jwt
+        if self.jwt.issuer.trim().is_empty() {
+            errors.push(String::from("jwt.issuer must not be empty"));
+        }
+
+        if self.jwt.audiences.iter().any(|a| a.trim().is_empty()) {
+            errors.push(String::from(
+                "jwt.audiences must not contain empty audiences",
+            ));
+        }
+
+        if self.jwt.ttl == 0 {
+            errors.push(String::from("jwt.ttl must not be 0"));
+        }
+
+        if self.jwt.rotation <= jwt::PUBLISH_DELAY {
+            // the next key is published this long before it's used
+            errors.push(format!(
+                "jwt.rotation must be greater than {}",
+                jwt::PUBLISH_DELAY
+            ));
+        }
+
+        // webhooks
+        if self.webhooks.max_attempts == 0 {
+            errors.push(String::from("webhooks.max_attempts must not be 0"));
+        }
+
+        // service integrations
+        if !is_url_or_empty(&self.forward_auth.root) {
+            errors.push(String::from(
+                "forward_auth.root must start with http:// or https://",
+            ));
+        }
+
+        if self
+            .forward_auth
+            .cookie_domain
+            .contains(|c: char| c.is_whitespace() | (c == ';') | (c == ','))
+        {
+            errors.push(String::from(
+                "forward_auth.cookie_domain must be a domain (ex: example.com)",
+            ));
+        }
+
+        for host in &self.forward_auth.allowed_hosts {
+            if host.is_empty() | host.contains(|c: char| c.is_whitespace() | (c == '/')) {
+                errors.push(format!(
+                    "forward_auth.allowed_hosts has an invalid host {host:?} (expected ex: tool.example.com)"
+                ));
+            }
+        }
+
+        // avatars and media
+        if self.avatars.max_size == 0 {
+            errors.push(String::from("avatars.max_size must not be 0"));
+        }
+
+        if self.avatars.cache_dir.trim().is_empty() {
+            errors.push(String::from("avatars.cache_dir must not be empty"));
+        }
+
+        if self.media.max_size == 0 {
+            errors.push(String::from("media.max_size must not be 0"));
+        }
+
+        if self.media.dir.trim().is_empty() {
+            errors.push(String::from("media.dir must not be empty"));
+        }
+
+        // events
+        if !["memory", "redis"].contains(&self.events.store.as_str()) {
+            errors.push(format!(
+                "events.store must be memory or redis (got {:?})",
+                self.events.store
+            ));
+        }
+
+        // profile fields
+        if self
+            .profile_fields
+            .allowed_keys
+            .iter()
+            .any(|k| k.trim().is_empty())
+        {
+            errors.push(String::from(
+                "profile_fields.allowed_keys must not contain empty keys",
+            ));
+        }
+
+        if self.profile_fields.max_label_len == 0 {
+            errors.push(String::from("profile_fields.max_label_len must not be 0"));
+        }
+
+        if self.profile_fields.max_value_len == 0 {
+            errors.push(String::from("profile_fields.max_value_len must not be 0"));
+        }
+
+        // pagination
+        if self.pagination.max_page_size == 0 {
+            errors.push(String::from("pagination.max_page_size must not be 0"));
+        }
+
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+
+        Ok(())
+    }
+
+    /// Get the [`dorsal::DatabaseOpts`] for this config
+    pub fn database_opts(&self) -> dorsal::DatabaseOpts {
+        let is_other = self
+            .database
+            ._type
+            .as_ref()
+            .is_some_and(|x| (x == "postgres") | (x == "mysql"));
+
+        dorsal::DatabaseOpts {
+            _type: self.database._type.clone(),
+            host: self.database.host.clone(),
+            user: if is_other {
+                self.database.user.clone()
+            } else {
+                String::new()
+            },
+            pass: if is_other {
+                self.database.pass.clone()
+            } else {
+                String::new()
+            },
+            name: if is_other {
+                self.database.name.clone()
+            } else {
+                String::new()
+            },
+        }
+    }
+
+    /// Format this config as TOML (with secrets redacted)
+    pub fn to_redacted_toml(&self) -> String {
+        let mut config = self.clone();
+        let redacted = String::from("********");
+
+        if !config.database.pass.is_empty() {
+            config.database.pass = redacted.clone();
+        }
+
+        for code in config.registration.invite_codes.iter_mut() {
+            *code = redacted.clone();
+        }
+
+        for secret in config.services.clients.values_mut() {
+            *secret = redacted.clone();
+        }
+
+        if !config.metrics.token.is_empty() {
+            config.metrics.token = redacted.clone();
+        }
 
-    if res.is_ok() {
-        Option::Some(res.unwrap())
-    } else {
-        Option::None
+        toml::to_string(&config).unwrap()
     }
 }
@@ -1,10 +1,33 @@
 use dorsal::query as sqlquery;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::cache::{self, AsyncRedis};
+use crate::captcha::CaptchaVerifier;
+use crate::config::{Config, EventsConfig};
+use crate::events::{Event, EventBus};
+use crate::jwt::{self, JwtConfig, SigningKey};
+use crate::media::MediaStore;
+use crate::metrics::Metrics;
+use crate::metadata::ProfileField;
+use crate::net::NetError;
+use crate::pagination::{Cursor, Direction, Page, PageQuery};
+use crate::ratelimit::RateLimiter;
+use crate::services::ServiceClients;
+use crate::setup::{self, SetupToken};
+use crate::webhooks::{self, Webhook, WebhookDelivery};
 
 #[derive(Clone)]
 pub struct AppData {
     pub db: Database,
     pub http_client: awc::Client,
+    pub limiter: RateLimiter,
+    pub captcha: Arc<dyn CaptchaVerifier>,
+    pub media: Arc<dyn MediaStore>,
+    pub services: ServiceClients,
+    pub config: Arc<Config>,
+    pub setup: SetupToken,
 }
 
 pub use dorsal::db::special::auth_db::{
@@ -20,9 +43,144 @@ pub struct UserFollow {
     pub is_following: String, // use user that `user` is following
 }
 
-#[allow(dead_code)]
-pub fn deserialize_userfollow(input: String) -> UserFollow {
-    serde_json::from_str::<UserFollow>(&input).unwrap()
+/// A short summary of a user, shown in follower and following lists
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserSummary {
+    pub username: String,
+    pub nickname: String,   // falls back to `username`
+    pub avatar_url: String, // the user's avatar (through the avatar proxy)
+    pub role: String,       // name of the user's role
+    pub followed_at: u128,  // when the follow was created
+    pub follows_you: bool,  // if this user follows the current viewer
+    pub you_follow: bool,   // if the current viewer follows this user
+}
+
+/// A user's public profile (see `api::auth::profile_request`)
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Profile {
+    pub username: String,
+    pub nickname: String,          // falls back to `username`
+    pub about: String,             // raw markdown
+    pub about_html: String,        // rendered `about`
+    pub role: String,              // name of the user's role
+    pub level: RoleLevel,          // the user's role level
+    pub joined: u128,              // when the user registered
+    pub followers_count: usize,    // amount of users following this user
+    pub following_count: usize,    // amount of users this user is following
+    pub fields: Vec<ProfileField>, // custom profile fields
+    pub is_private: bool,          // if the account is private (see [`UserSettings`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private: Option<PrivateProfile>, // only included for the user themselves and staff
+}
+
+/// Parts of a [`Profile`] only shown to the user themselves and staff
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PrivateProfile {
+    pub avatar_url: Option<String>, // remote avatar (public avatars are served through the proxy)
+    pub has_secondary_token: bool,  // the token itself (or its hash) is never included
+}
+
+/// A user's account settings
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserSettings {
+    #[serde(default)]
+    pub private: bool, // if follows must be approved (and follow lists are hidden from non-followers)
+}
+
+/// A user blocking another user
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserBlock {
+    pub id: String,
+    pub username: String, // the user that is blocking `blocked`
+    pub blocked: String,  // the user that `username` blocked
+    pub timestamp: u128,
+}
+
+fn deserialize_block(row: HashMap<String, String>) -> UserBlock {
+    UserBlock {
+        id: row.get("id").cloned().unwrap_or_default(),
+        username: row.get("username").cloned().unwrap_or_default(),
+        blocked: row.get("blocked").cloned().unwrap_or_default(),
+        timestamp: row
+            .get("timestamp")
+            .and_then(|t| t.parse::<u128>().ok())
+            .unwrap_or(0),
+    }
+}
+
+/// A notification sent to a user
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Notification {
+    pub id: String,
+    pub username: String, // the user the notification was sent to
+    pub kind: String,     // "follow", "follow_request", "follow_approved", "ban" or "profile_edit"
+    pub title: String,
+    pub link: String, // where the notification leads (can be empty)
+    pub read: bool,
+    pub timestamp: u128,
+}
+
+fn deserialize_notification(row: HashMap<String, String>) -> Notification {
+    Notification {
+        id: row.get("id").cloned().unwrap_or_default(),
+        username: row.get("username").cloned().unwrap_or_default(),
+        kind: row.get("kind").cloned().unwrap_or_default(),
+        title: row.get("title").cloned().unwrap_or_default(),
+        link: row.get("link").cloned().unwrap_or_default(),
+        read: row.get("is_read").is_some_and(|r| r == "true"),
+        timestamp: row
+            .get("timestamp")
+            .and_then(|t| t.parse::<u128>().ok())
+            .unwrap_or(0),
+    }
+}
+
+fn deserialize_webhook(row: HashMap<String, String>) -> Webhook {
+    Webhook {
+        id: row.get("id").cloned().unwrap_or_default(),
+        url: row.get("url").cloned().unwrap_or_default(),
+        secret: row.get("secret").cloned().unwrap_or_default(),
+        events: row
+            .get("events")
+            .map(|e| e.split(",").map(|e| e.to_string()).collect())
+            .unwrap_or_default(),
+        created_by: row.get("created_by").cloned().unwrap_or_default(),
+        timestamp: row
+            .get("timestamp")
+            .and_then(|t| t.parse::<u128>().ok())
+            .unwrap_or(0),
+    }
+}
+
+fn deserialize_signing_key(row: HashMap<String, String>) -> SigningKey {
+    SigningKey {
+        id: row.get("id").cloned().unwrap_or_default(),
+        private_key: row.get("private_key").cloned().unwrap_or_default(),
+        public_key: row.get("public_key").cloned().unwrap_or_default(),
+        timestamp: row
+            .get("timestamp")
+            .and_then(|t| t.parse::<u128>().ok())
+            .unwrap_or(0),
+    }
+}
+
+/// The follow state between two users (from the point of view of the first user)
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Relationship {
+    pub following: bool,              // if the user is following the other user
+    pub followed_by: bool,            // if the other user is following the user
+    pub mutual: bool,                 // if both users are following each other
+    pub followed_at: Option<u128>,    // when the user followed the other user
+    pub followed_by_at: Option<u128>, // when the other user followed the user
+    pub blocking: bool,               // if the user is blocking the other user
+    pub requested: bool,              // if the user has requested to follow the other user
+}
+
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RejectedAttempt {
+    pub action: String, // the action that was attempted ("register", "login")
+    pub ip: String,     // the IP the attempt came from
+    pub reason: String, // why the attempt was rejected
 }
 
 // propss
@@ -39,10 +197,59 @@ pub struct Database {
     pub base: dorsal::StarterDatabase,
     pub auth: dorsal::AuthDatabase,
     pub logs: dorsal::LogDatabase,
+    pub events: EventBus,
+    pub metrics: Metrics,
+    /// Async connection to the cache (redis), for anything called from a handler
+    pub cache: AsyncRedis,
+}
+
+/// Usernames which can't be registered, since profiles are served at `/{username}`
+/// and these are taken by other routes (compared case-insensitively)
+pub const RESERVED_USERNAMES: [&str; 9] = [
+    "api",
+    "flow",
+    "static",
+    "setup",
+    "notifications",
+    "healthz",
+    "readyz",
+    "metrics",
+    ".well-known",
+];
+
+/// Log a database error (callers usually only get the error as a message)
+///
+/// # Arguments:
+/// * `query` - the name of the query (ex: the [`Database`] method)
+/// * `error` - the error
+fn log_error(query: &str, error: &impl std::fmt::Display) {
+    log::error!(target: "shuttle::db", query = query; "{error}");
+}
+
+/// Get a `LIKE` pattern matching log content (compact JSON) where `key` is exactly
+/// `value` (used with `ESCAPE '!'`)
+///
+/// # Arguments:
+/// * `key` - the JSON key
+/// * `value` - the string value of the key
+fn json_field_like(key: &str, value: &str) -> String {
+    // usernames can contain `_`, which would match any character
+    let value = serde_json::to_string(value)
+        .unwrap()
+        .replace("!", "!!")
+        .replace("%", "!%")
+        .replace("_", "!_");
+
+    format!("%\"{key}\":{value}%")
 }
 
 impl Database {
-    pub async fn new(opts: dorsal::DatabaseOpts) -> Database {
+    /// Create a new [`Database`]
+    ///
+    /// # Arguments:
+    /// * `opts` - [`dorsal::DatabaseOpts`]
+    /// * `events` - [`EventsConfig`]
+    pub async fn new(opts: dorsal::DatabaseOpts, events: &EventsConfig) -> Database {
         let db = dorsal::StarterDatabase::new(opts).await;
 
         Database {
@@ -56,6 +263,9 @@ impl Database {
                     logs_prefix: String::from("sh_level"),
                 },
             },
+            events: EventBus::from_config(events, db.cachedb.client.clone()),
+            metrics: Metrics::default(),
+            cache: AsyncRedis::new(db.cachedb.client.clone()),
             logs: dorsal::LogDatabase {
                 base: db,
                 options: dorsal::db::special::log_db::DatabaseOptions {
@@ -67,6 +277,8 @@ impl Database {
     }
 
     pub async fn init(&self) {
+        let _timer = self.metrics.time_query("init");
+
         let c = &self.base.db.client;
 
         let _ = sqlquery(
@@ -81,6 +293,101 @@ impl Database {
         .execute(c)
         .await;
 
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"sh_fields\" (
+                username TEXT,
+                fields   TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"sh_settings\" (
+                username TEXT,
+                settings TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"sh_blocks\" (
+                id        TEXT,
+                username  TEXT,
+                blocked   TEXT,
+                timestamp TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        // a user can only block another user once (duplicates from before the
+        // index existed are removed first, keeping one of each)
+        let _ = sqlquery(
+            "DELETE FROM \"sh_blocks\" WHERE \"id\" NOT IN (
+                SELECT \"id\" FROM (
+                    SELECT MIN(\"id\") AS \"id\" FROM \"sh_blocks\" GROUP BY \"username\", \"blocked\"
+                ) AS \"keep\"
+            )",
+        )
+        .execute(c)
+        .await;
+
+        // mysql can't index TEXT without a prefix length (and fails harmlessly
+        // if the index exists, it has no IF NOT EXISTS)
+        let _ = sqlquery(if self.base.db._type == "mysql" {
+            "CREATE UNIQUE INDEX \"sh_blocks_pair\" ON \"sh_blocks\" (\"username\"(255), \"blocked\"(255))"
+        } else {
+            "CREATE UNIQUE INDEX IF NOT EXISTS \"sh_blocks_pair\" ON \"sh_blocks\" (\"username\", \"blocked\")"
+        })
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"sh_notifications\" (
+                id        TEXT,
+                username  TEXT,
+                kind      TEXT,
+                title     TEXT,
+                link      TEXT,
+                is_read   TEXT,
+                timestamp TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        // "read" is reserved in mysql, tables created before the rename are
+        // migrated (this fails harmlessly once they are)
+        let _ = sqlquery("ALTER TABLE \"sh_notifications\" RENAME COLUMN \"read\" TO \"is_read\"")
+            .execute(c)
+            .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"sh_webhooks\" (
+                id         TEXT,
+                url        TEXT,
+                secret     TEXT,
+                events     TEXT,
+                created_by TEXT,
+                timestamp  TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
+        let _ = sqlquery(
+            "CREATE TABLE IF NOT EXISTS \"sh_signing_keys\" (
+                id          TEXT,
+                private_key TEXT,
+                public_key  TEXT,
+                timestamp   TEXT
+            )",
+        )
+        .execute(c)
+        .await;
+
         let _ = sqlquery(
             "CREATE TABLE IF NOT EXISTS \"sh_logs\" (
                 id        TEXT,
@@ -93,6 +400,28 @@ impl Database {
         .await;
     }
 
+    /// Check if the database and the cache (redis) are reachable. Returns
+    /// `(database, cache)`
+    pub async fn check_ready(&self) -> (bool, bool) {
+        let _timer = self.metrics.time_query("check_ready");
+
+        let database = tokio::time::timeout(
+            cache::TIMEOUT,
+            sqlquery("SELECT 1").execute(&self.base.db.client),
+        )
+        .await
+        .is_ok_and(|r| r.is_ok());
+
+        let cache = tokio::time::timeout(cache::TIMEOUT, async {
+            let mut c = self.cache.get().await?;
+            redis::cmd("PING").query_async::<_, String>(&mut c).await
+        })
+        .await
+        .is_ok_and(|r| r.is_ok());
+
+        (database, cache)
+    }
+
     // users
 
     // GET
@@ -101,6 +430,7 @@ impl Database {
     /// # Arguments:
     /// * `hashed` - `String` of the user's hashed ID
     pub async fn get_user_by_hashed(&self, hashed: String) -> Result<FullUser<UserMetadata>> {
+        let _timer = self.metrics.time_query("get_user_by_hashed");
         self.auth.get_user_by_hashed(hashed).await
     }
 
@@ -111,6 +441,7 @@ impl Database {
     /// # Arguments:
     /// * `unhashed` - `String` of the user's unhashed ID
     pub async fn get_user_by_unhashed(&self, unhashed: String) -> Result<FullUser<UserMetadata>> {
+        let _timer = self.metrics.time_query("get_user_by_unhashed");
         self.auth.get_user_by_unhashed(unhashed).await
     }
 
@@ -122,6 +453,7 @@ impl Database {
         &self,
         unhashed: String,
     ) -> Result<FullUser<UserMetadata>> {
+        let _timer = self.metrics.time_query("get_user_by_unhashed_st");
         self.auth.get_user_by_unhashed_st(unhashed).await
     }
 
@@ -130,6 +462,7 @@ impl Database {
     /// # Arguments:
     /// * `username` - `String` of the user's username
     pub async fn get_user_by_username(&self, username: String) -> Result<FullUser<UserMetadata>> {
+        let _timer = self.metrics.time_query("get_user_by_username");
         self.auth.get_user_by_username(username).await
     }
 
@@ -138,6 +471,8 @@ impl Database {
     /// # Arguments:
     /// * `name` - `String` of the level's role name
     pub async fn get_level_by_role(&self, name: String) -> DefaultReturn<RoleLevelLog> {
+        let _timer = self.metrics.time_query("get_level_by_role");
+
         DefaultReturn {
             success: true,
             message: String::new(),
@@ -175,7 +510,7 @@ impl Database {
             };
         }
 
-        if (username.len() < 2) | (username.len() > 500) {
+        if (username.len() < 2) | (username.len() > 32) {
             return DefaultReturn {
                 success: false,
                 message: String::from("Username is invalid"),
@@ -183,6 +518,19 @@ impl Database {
             };
         }
 
+        if RESERVED_USERNAMES
+            .iter()
+            .any(|r| r.eq_ignore_ascii_case(&username))
+        {
+            return DefaultReturn {
+                success: false,
+                message: String::from("Username is reserved"),
+                payload: Option::None,
+            };
+        }
+
+        let _timer = self.metrics.time_query("create_user");
+
         // ...
         let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
             "INSERT INTO \"sh_users\" VALUES (?, ?, ?, ?, ?)"
@@ -212,20 +560,25 @@ impl Database {
             .execute(c)
             .await;
 
-        if res.is_err() {
+        if let Err(e) = res {
+            log_error("create_user", &e);
+
             return DefaultReturn {
                 success: false,
-                message: String::from(res.err().unwrap().to_string()),
+                message: e.to_string(),
                 payload: Option::None,
             };
         }
 
+        self.events
+            .publish(Event::new("register", username.clone(), Option::None));
+
         // return
-        return DefaultReturn {
+        DefaultReturn {
             success: true,
             message: user_id_unhashed,
             payload: Option::Some(user_id_hashed),
-        };
+        }
     }
 
     /// Update a [`UserState`]'s metadata by its `username`
@@ -236,7 +589,7 @@ impl Database {
     ) -> DefaultReturn<Option<String>> {
         // make sure user exists
         let existing = &self.get_user_by_username(name.clone()).await;
-        if !existing.is_ok() {
+        if existing.is_err() {
             return DefaultReturn {
                 success: false,
                 message: String::from("User does not exist!"),
@@ -244,6 +597,8 @@ impl Database {
             };
         }
 
+        let _timer = self.metrics.time_query("edit_user_metadata_by_name");
+
         // update user
         let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
             "UPDATE \"sh_users\" SET \"metadata\" = ? WHERE \"username\" = ?"
@@ -259,45 +614,46 @@ impl Database {
             .execute(c)
             .await;
 
-        if res.is_err() {
+        if let Err(e) = res {
+            log_error("edit_user_metadata_by_name", &e);
+
             return DefaultReturn {
                 success: false,
-                message: String::from(res.err().unwrap().to_string()),
+                message: e.to_string(),
                 payload: Option::None,
             };
         }
 
-        // update cache
-        let existing_in_cache = self.base.cachedb.get(format!("user:{}", name)).await;
-
-        if existing_in_cache.is_some() {
-            let mut user =
-                serde_json::from_str::<UserState<String>>(&existing_in_cache.unwrap()).unwrap();
-            user.metadata = meta.to_string(); // update metadata
+        // update cache (user will be refetched on next read)
+        self.base
+            .cachedb
+            .remove(format!("{}:{}", self.auth.options.prefix, name))
+            .await;
 
-            // update cache
-            self.base
-                .cachedb
-                .update(
-                    format!("user:{}", name),
-                    serde_json::to_string::<UserState<String>>(&user).unwrap(),
-                )
-                .await;
-        }
+        self.events
+            .publish(Event::new("profile_update", name.clone(), Option::None));
 
         // return
-        return DefaultReturn {
+        DefaultReturn {
             success: true,
             message: String::from("User updated!"),
             payload: Option::Some(name),
-        };
+        }
     }
 
     /// Ban a [`UserState`] by its `username`
-    pub async fn ban_user_by_name(&self, name: String) -> DefaultReturn<Option<String>> {
+    ///
+    /// # Arguments:
+    /// * `name` - the username of the user
+    /// * `force` - also ban users with a level elevation other than 0 (staff)
+    pub async fn ban_user_by_name(
+        &self,
+        name: String,
+        force: bool,
+    ) -> DefaultReturn<Option<String>> {
         // make sure user exists
         let existing = &self.get_user_by_username(name.clone()).await;
-        if !existing.is_ok() {
+        if existing.is_err() {
             return DefaultReturn {
                 success: false,
                 message: String::from("User does not exist!"),
@@ -307,7 +663,7 @@ impl Database {
 
         // make sure user level elevation is 0
         let level = &existing.as_ref().ok().unwrap().level;
-        if level.elevation == 0 {
+        if (level.elevation != 0) && !force {
             return DefaultReturn {
                 success: false,
                 message: String::from("User must be of level elevation 0"),
@@ -323,319 +679,2522 @@ impl Database {
         };
 
         let c = &self.base.db.client;
-        let res = sqlquery(query)
-            .bind::<&str>("banned")
-            .bind::<&String>(&name)
-            .execute(c)
-            .await;
+        let res = {
+            let _timer = self.metrics.time_query("ban_user_by_name");
+
+            sqlquery(query)
+                .bind::<&str>("banned")
+                .bind::<&String>(&name)
+                .execute(c)
+                .await
+        };
+
+        if let Err(e) = res {
+            log_error("ban_user_by_name", &e);
 
-        if res.is_err() {
             return DefaultReturn {
                 success: false,
-                message: String::from(res.err().unwrap().to_string()),
+                message: e.to_string(),
                 payload: Option::None,
             };
         }
 
-        // update cache
-        let existing_in_cache = self.base.cachedb.get(format!("user:{}", name)).await;
+        self.events
+            .publish(Event::new("ban", name.clone(), Option::None));
 
-        if existing_in_cache.is_some() {
-            let mut user =
-                serde_json::from_str::<UserState<String>>(&existing_in_cache.unwrap()).unwrap();
-            user.role = String::from("banned"); // update role
+        // notify user
+        let _ = self
+            .create_notification(
+                name.clone(),
+                "ban",
+                String::from("Your account has been banned"),
+                String::new(),
+            )
+            .await;
 
-            // update cache
-            self.base
-                .cachedb
-                .update(
-                    format!("user:{}", name),
-                    serde_json::to_string::<UserState<String>>(&user).unwrap(),
-                )
-                .await;
-        }
+        // update cache (user will be refetched on next read)
+        self.base
+            .cachedb
+            .remove(format!("{}:{}", self.auth.options.prefix, name))
+            .await;
 
         // return
-        return DefaultReturn {
+        DefaultReturn {
             success: true,
             message: String::from("User banned!"),
             payload: Option::Some(name),
-        };
+        }
     }
 
-    // follows
+    /// Unban a [`UserState`] by its `username` (their role is reset to `member`)
+    pub async fn unban_user_by_name(&self, name: String) -> DefaultReturn<Option<String>> {
+        // make sure user is banned (banned users can't be fetched normally)
+        let existing = self
+            .get_users_by_username(std::slice::from_ref(&name))
+            .await;
 
-    // GET
-    /// Get a [`UserFollow`] by the username of the user following
+        match existing.get(&name) {
+            Some(u) if u.role == "banned" => (),
+            Some(_) => {
+                return DefaultReturn {
+                    success: false,
+                    message: String::from("User is not banned!"),
+                    payload: Option::None,
+                }
+            }
+            None => {
+                return DefaultReturn {
+                    success: false,
+                    message: String::from("User does not exist!"),
+                    payload: Option::None,
+                }
+            }
+        }
+
+        let res = self.set_user_role(name, String::from("member")).await;
+
+        if !res.success {
+            return res;
+        }
+
+        // return
+        DefaultReturn {
+            success: true,
+            message: String::from("User unbanned!"),
+            payload: res.payload,
+        }
+    }
+
+    /// Set the role of a [`UserState`] by its `username`
     ///
     /// # Arguments:
-    /// * `user` - username of user following
-    /// * `is_following` - the username of the user that `user` is following
-    pub async fn get_follow_by_user(
-        &self,
-        user: String,
-        is_following: String,
-    ) -> DefaultReturn<Option<Log>> {
+    /// * `name` - the username of the user
+    /// * `role` - the name of the role (must be `member`, `banned` or an existing level)
+    pub async fn set_user_role(&self, name: String, role: String) -> DefaultReturn<Option<String>> {
+        // make sure user exists
+        let existing = self
+            .get_users_by_username(std::slice::from_ref(&name))
+            .await;
+
+        if !existing.contains_key(&name) {
+            return DefaultReturn {
+                success: false,
+                message: String::from("User does not exist!"),
+                payload: Option::None,
+            };
+        }
+
+        // make sure role exists
+        if (role != "member")
+            & (role != "banned")
+            & self
+                .get_level_by_role(role.clone())
+                .await
+                .payload
+                .id
+                .is_empty()
+        {
+            return DefaultReturn {
+                success: false,
+                message: String::from("Role does not exist!"),
+                payload: Option::None,
+            };
+        }
+
+        let _timer = self.metrics.time_query("set_user_role");
+
+        // update user
         let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE ? AND \"logtype\" = 'follow'"
+            "UPDATE \"sh_users\" SET \"role\" = ? WHERE \"username\" = ?"
         } else {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE $1 AND \"logtype\" = 'follow'"
+            "UPDATE \"sh_users\" SET (\"role\") = ($1) WHERE \"username\" = $2"
         };
 
         let c = &self.base.db.client;
         let res = sqlquery(query)
-            .bind::<&String>(&format!(
-                "%\"user\":\"{user}\",\"is_following\":\"{is_following}\"%"
-            ))
-            .fetch_one(c)
+            .bind::<&String>(&role)
+            .bind::<&String>(&name)
+            .execute(c)
             .await;
 
-        if res.is_err() {
+        if let Err(e) = res {
+            log_error("set_user_role", &e);
+
             return DefaultReturn {
                 success: false,
-                message: String::from("Follow does not exist"),
+                message: e.to_string(),
                 payload: Option::None,
             };
         }
 
-        // ...
-        let row = res.unwrap();
-        let row = self.base.textify_row(row).data;
+        // update cache (user will be refetched on next read)
+        self.base
+            .cachedb
+            .remove(format!("{}:{}", self.auth.options.prefix, name))
+            .await;
 
         // return
-        return DefaultReturn {
+        DefaultReturn {
             success: true,
-            message: String::from("Follow exists"),
-            payload: Option::Some(Log {
-                id: row.get("id").unwrap().to_string(),
-                logtype: row.get("logtype").unwrap().to_string(),
-                timestamp: row.get("timestamp").unwrap().parse::<u128>().unwrap(),
-                content: row.get("content").unwrap().to_string(),
-            }),
-        };
+            message: String::from("User updated!"),
+            payload: Option::Some(name),
+        }
     }
 
-    /// Get the [`UserFollow`]s that are following the given `user`
+    /// Replace the ID of a [`UserState`] by its `username` (signing them out
+    /// everywhere). Returns their new unhashed ID as the message
     ///
     /// # Arguments:
-    /// * `user` - username of user to check
-    /// * `offset` - optional value representing the SQL fetch offset
-    pub async fn get_user_followers(
-        &self,
-        user: String,
-        offset: Option<i32>,
-    ) -> DefaultReturn<Option<Vec<Log>>> {
+    /// * `name` - the username of the user
+    pub async fn rotate_user_id(&self, name: String) -> DefaultReturn<Option<String>> {
+        // make sure user exists
+        let existing = self
+            .get_users_by_username(std::slice::from_ref(&name))
+            .await;
+
+        if !existing.contains_key(&name) {
+            return DefaultReturn {
+                success: false,
+                message: String::from("User does not exist!"),
+                payload: Option::None,
+            };
+        }
+
+        let _timer = self.metrics.time_query("rotate_user_id");
+
+        // update user
         let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE ? AND \"logtype\" = 'follow' ORDER BY \"timestamp\" DESC LIMIT 50 OFFSET ?"
+            "UPDATE \"sh_users\" SET \"id_hashed\" = ? WHERE \"username\" = ?"
         } else {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE $1 AND \"logtype\" = 'follow' ORDER BY \"timestamp\" DESC LIMIT 50 OFFSET $2"
+            "UPDATE \"sh_users\" SET (\"id_hashed\") = ($1) WHERE \"username\" = $2"
         };
 
+        let user_id_unhashed: String = dorsal::utility::uuid();
+        let user_id_hashed: String = dorsal::utility::hash(user_id_unhashed.clone());
+
         let c = &self.base.db.client;
         let res = sqlquery(query)
-            .bind::<&String>(&format!("%\"is_following\":\"{user}\"%"))
-            .bind(if offset.is_some() { offset.unwrap() } else { 0 })
-            .fetch_all(c)
+            .bind::<&String>(&user_id_hashed)
+            .bind::<&String>(&name)
+            .execute(c)
             .await;
 
-        if res.is_err() {
+        if let Err(e) = res {
+            log_error("rotate_user_id", &e);
+
             return DefaultReturn {
                 success: false,
-                message: String::from("Failed to fetch followers"),
+                message: e.to_string(),
                 payload: Option::None,
             };
         }
 
-        // ...
-        let rows = res.unwrap();
-        let mut output: Vec<Log> = Vec::new();
+        // update cache (user will be refetched on next read)
+        self.base
+            .cachedb
+            .remove(format!("{}:{}", self.auth.options.prefix, name))
+            .await;
 
-        for row in rows {
-            let row = self.base.textify_row(row).data;
-            output.push(Log {
-                id: row.get("id").unwrap().to_string(),
-                logtype: row.get("logtype").unwrap().to_string(),
-                timestamp: row.get("timestamp").unwrap().parse::<u128>().unwrap(),
-                content: row.get("content").unwrap().to_string(),
+        // return
+        DefaultReturn {
+            success: true,
+            message: user_id_unhashed,
+            payload: Option::Some(user_id_hashed),
+        }
+    }
+
+    /// Delete a [`UserState`] by its `username`
+    ///
+    /// Only used to undo a partially created account (see `api::setup`), nothing
+    /// the user owns is deleted.
+    ///
+    /// # Arguments:
+    /// * `name` - the username of the user
+    pub async fn delete_user(&self, name: String) -> bool {
+        let _timer = self.metrics.time_query("delete_user");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "DELETE FROM \"sh_users\" WHERE \"username\" = ?"
+        } else {
+            "DELETE FROM \"sh_users\" WHERE \"username\" = $1"
+        };
+
+        let c = &self.base.db.client;
+        if let Err(e) = sqlquery(query).bind::<&String>(&name).execute(c).await {
+            log_error("delete_user", &e);
+            return false;
+        }
+
+        // update cache
+        self.base
+            .cachedb
+            .remove(format!("{}:{}", self.auth.options.prefix, name))
+            .await;
+
+        true
+    }
+
+    /// Check if any user has a level with the `ManageUsers` permission
+    pub async fn has_admin(&self) -> bool {
+        let c = &self.base.db.client;
+        let res = {
+            let _timer = self.metrics.time_query("has_admin");
+
+            sqlquery("SELECT DISTINCT \"role\" FROM \"sh_users\"")
+                .fetch_all(c)
+                .await
+        };
+
+        let roles: Vec<String> = match res {
+            Ok(r) => r
+                .into_iter()
+                .filter_map(|row| self.base.textify_row(row).data.remove("role"))
+                .collect(),
+            Err(e) => {
+                log_error("has_admin", &e);
+                return false;
+            }
+        };
+
+        for role in roles {
+            let level = self.get_level_by_role(role).await.payload.level;
+
+            if level.permissions.contains(&String::from("ManageUsers")) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Create the default role levels (see [`setup::default_levels`]) which don't
+    /// exist yet. Returns the names of the created levels
+    pub async fn seed_role_levels(&self) -> Vec<String> {
+        let mut created: Vec<String> = Vec::new();
+
+        for level in setup::default_levels() {
+            if !self
+                .get_level_by_role(level.name.clone())
+                .await
+                .payload
+                .id
+                .is_empty()
+            {
+                continue;
+            }
+
+            // levels are matched with LIKE on their compact JSON
+            let res = {
+                let _timer = self.metrics.time_query("seed_role_levels");
+
+                self.logs
+                    .create_log(
+                        String::from("level"),
+                        serde_json::to_string::<RoleLevel>(&level).unwrap(),
+                    )
+                    .await
+            };
+
+            if res.is_err() {
+                continue;
+            }
+
+            // the default level may have been cached while this level didn't exist
+            self.base
+                .cachedb
+                .remove(format!("{}:{}", self.auth.options.logs_prefix, level.name))
+                .await;
+
+            created.push(level.name);
+        }
+
+        created
+    }
+
+    /// Get every [`UserState`] (including banned users), oldest first
+    pub async fn get_all_users(&self) -> Vec<UserState<UserMetadata>> {
+        let _timer = self.metrics.time_query("get_all_users");
+
+        let c = &self.base.db.client;
+        let rows = match sqlquery("SELECT * FROM \"sh_users\" ORDER BY \"timestamp\" ASC")
+            .fetch_all(c)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log_error("get_all_users", &e);
+                return Vec::new();
+            }
+        };
+
+        // return
+        rows.into_iter()
+            .map(|row| {
+                let row = self.base.textify_row(row).data;
+
+                UserState {
+                    username: row.get("username").cloned().unwrap_or_default(),
+                    id_hashed: row.get("id_hashed").cloned().unwrap_or_default(),
+                    role: row.get("role").cloned().unwrap_or_default(),
+                    timestamp: row
+                        .get("timestamp")
+                        .and_then(|t| t.parse::<u128>().ok())
+                        .unwrap_or(0),
+                    metadata: row
+                        .get("metadata")
+                        .and_then(|m| serde_json::from_str::<UserMetadata>(m).ok())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+
+    // fields
+
+    // GET
+    /// Get a user's custom [`ProfileField`]s by their `username`
+    ///
+    /// # Arguments:
+    /// * `username` - the username of the user
+    pub async fn get_profile_fields(&self, username: String) -> Vec<ProfileField> {
+        let _timer = self.metrics.time_query("get_profile_fields");
+
+        // check in cache
+        let cached = self
+            .base
+            .cachedb
+            .get(format!("sh_fields:{}", username))
+            .await;
+
+        if let Some(c) = cached {
+            return serde_json::from_str::<Vec<ProfileField>>(&c).unwrap_or_default();
+        }
+
+        // ...
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_fields\" WHERE \"username\" = ?"
+        } else {
+            "SELECT * FROM \"sh_fields\" WHERE \"username\" = $1"
+        };
+
+        let c = &self.base.db.client;
+        let res = sqlquery(query)
+            .bind::<&String>(&username)
+            .fetch_one(c)
+            .await;
+
+        let fields = match res {
+            Ok(row) => {
+                let row = self.base.textify_row(row).data;
+                row.get("fields").unwrap().to_string()
+            }
+            Err(_) => String::from("[]"),
+        };
+
+        // store in cache
+        self.base
+            .cachedb
+            .set(format!("sh_fields:{}", username), fields.clone())
+            .await;
+
+        // return
+        serde_json::from_str::<Vec<ProfileField>>(&fields).unwrap_or_default()
+    }
+
+    // SET
+    /// Replace a user's custom [`ProfileField`]s by their `username`
+    ///
+    /// # Arguments:
+    /// * `username` - the username of the user
+    /// * `fields` - the user's new fields
+    pub async fn set_profile_fields(
+        &self,
+        username: String,
+        fields: Vec<ProfileField>,
+    ) -> DefaultReturn<Option<String>> {
+        let _timer = self.metrics.time_query("set_profile_fields");
+
+        let (delete, insert): (&str, &str) =
+            if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+                (
+                    "DELETE FROM \"sh_fields\" WHERE \"username\" = ?",
+                    "INSERT INTO \"sh_fields\" VALUES (?, ?)",
+                )
+            } else {
+                (
+                    "DELETE FROM \"sh_fields\" WHERE \"username\" = $1",
+                    "INSERT INTO \"sh_fields\" VALUES ($1, $2)",
+                )
+            };
+
+        // replace existing fields (in one transaction, so they're never lost)
+        let c = &self.base.db.client;
+        let res = async {
+            let mut tx = c.begin().await?;
+
+            sqlquery(delete)
+                .bind::<&String>(&username)
+                .execute(&mut *tx)
+                .await?;
+
+            if !fields.is_empty() {
+                sqlquery(insert)
+                    .bind::<&String>(&username)
+                    .bind::<&String>(&serde_json::to_string(&fields).unwrap())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await
+        }
+        .await;
+
+        if let Err(e) = res {
+            log_error("set_profile_fields", &e);
+
+            return DefaultReturn {
+                success: false,
+                message: e.to_string(),
+                payload: Option::None,
+            };
+        }
+
+        // update cache
+        self.base
+            .cachedb
+            .remove(format!("sh_fields:{}", username))
+            .await;
+
+        // return
+        DefaultReturn {
+            success: true,
+            message: String::from("Fields updated!"),
+            payload: Option::Some(username),
+        }
+    }
+
+    // settings
+
+    // GET
+    /// Get a user's [`UserSettings`] by their `username`
+    ///
+    /// # Arguments:
+    /// * `username` - the username of the user
+    pub async fn get_user_settings(&self, username: String) -> UserSettings {
+        let _timer = self.metrics.time_query("get_user_settings");
+
+        // check in cache
+        let cached = self
+            .base
+            .cachedb
+            .get(format!("sh_settings:{}", username))
+            .await;
+
+        if let Some(c) = cached {
+            return serde_json::from_str::<UserSettings>(&c).unwrap_or_default();
+        }
+
+        // ...
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_settings\" WHERE \"username\" = ?"
+        } else {
+            "SELECT * FROM \"sh_settings\" WHERE \"username\" = $1"
+        };
+
+        let c = &self.base.db.client;
+        let res = sqlquery(query)
+            .bind::<&String>(&username)
+            .fetch_one(c)
+            .await;
+
+        let settings = match res {
+            Ok(row) => {
+                let row = self.base.textify_row(row).data;
+                row.get("settings").unwrap().to_string()
+            }
+            Err(_) => String::from("{}"),
+        };
+
+        // store in cache
+        self.base
+            .cachedb
+            .set(format!("sh_settings:{}", username), settings.clone())
+            .await;
+
+        // return
+        serde_json::from_str::<UserSettings>(&settings).unwrap_or_default()
+    }
+
+    // SET
+    /// Replace a user's [`UserSettings`] by their `username`
+    ///
+    /// # Arguments:
+    /// * `username` - the username of the user
+    /// * `settings` - the new [`UserSettings`]
+    pub async fn set_user_settings(
+        &self,
+        username: String,
+        settings: UserSettings,
+    ) -> DefaultReturn<Option<String>> {
+        let _timer = self.metrics.time_query("set_user_settings");
+
+        let (delete, insert): (&str, &str) =
+            if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+                (
+                    "DELETE FROM \"sh_settings\" WHERE \"username\" = ?",
+                    "INSERT INTO \"sh_settings\" VALUES (?, ?)",
+                )
+            } else {
+                (
+                    "DELETE FROM \"sh_settings\" WHERE \"username\" = $1",
+                    "INSERT INTO \"sh_settings\" VALUES ($1, $2)",
+                )
+            };
+
+        // replace existing settings (in one transaction, so a failed insert doesn't
+        // leave the account without settings, making it public)
+        let c = &self.base.db.client;
+        let res = async {
+            let mut tx = c.begin().await?;
+
+            sqlquery(delete)
+                .bind::<&String>(&username)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlquery(insert)
+                .bind::<&String>(&username)
+                .bind::<&String>(&serde_json::to_string(&settings).unwrap())
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        }
+        .await;
+
+        if let Err(e) = res {
+            log_error("set_user_settings", &e);
+
+            return DefaultReturn {
+                success: false,
+                message: e.to_string(),
+                payload: Option::None,
+            };
+        }
+
+        // update cache
+        self.base
+            .cachedb
+            .remove(format!("sh_settings:{}", username))
+            .await;
+
+        // return
+        DefaultReturn {
+            success: true,
+            message: String::from("Settings updated!"),
+            payload: Option::Some(username),
+        }
+    }
+
+    // logs
+
+    // SET
+    /// Record a rejected registration or login attempt ([`RejectedAttempt`])
+    ///
+    /// # Arguments:
+    /// * `props` - [`RejectedAttempt`]
+    pub async fn log_rejected_attempt(&self, props: RejectedAttempt) -> LogResult<()> {
+        let _timer = self.metrics.time_query("log_rejected_attempt");
+
+        self.logs
+            .create_log(
+                String::from("rejected"),
+                serde_json::to_string::<RejectedAttempt>(&props).unwrap(),
+            )
+            .await
+    }
+
+    // follows
+
+    // GET
+    /// Get a [`UserFollow`] by the username of the user following
+    ///
+    /// # Arguments:
+    /// * `user` - username of user following
+    /// * `is_following` - the username of the user that `user` is following
+    pub async fn get_follow_by_user(
+        &self,
+        user: String,
+        is_following: String,
+    ) -> DefaultReturn<Option<Log>> {
+        self.get_follow_log("follow", UserFollow { user, is_following })
+            .await
+    }
+
+    /// Get a pending follow request by the username of the user requesting
+    ///
+    /// # Arguments:
+    /// * `user` - username of user requesting to follow
+    /// * `is_following` - the username of the user that `user` requested to follow
+    pub async fn get_follow_request(
+        &self,
+        user: String,
+        is_following: String,
+    ) -> DefaultReturn<Option<Log>> {
+        self.get_follow_log("follow_request", UserFollow { user, is_following })
+            .await
+    }
+
+    /// Get a [`Log`] of the given `logtype` containing the given [`UserFollow`]
+    ///
+    /// # Arguments:
+    /// * `logtype` - the type of the log (`follow` or `follow_request`)
+    /// * `follow` - [`UserFollow`]
+    async fn get_follow_log(
+        &self,
+        logtype: &str,
+        follow: UserFollow,
+    ) -> DefaultReturn<Option<Log>> {
+        let _timer = self.metrics.time_query("get_follow_log");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" = ? AND \"logtype\" = ?"
+        } else {
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" = $1 AND \"logtype\" = $2"
+        };
+
+        // follow logs are stored as the exact serialized UserFollow
+        let c = &self.base.db.client;
+        let res = sqlquery(query)
+            .bind::<String>(serde_json::to_string::<UserFollow>(&follow).unwrap())
+            .bind::<&str>(logtype)
+            .fetch_one(c)
+            .await;
+
+        if res.is_err() {
+            return DefaultReturn {
+                success: false,
+                message: String::from("Follow does not exist"),
+                payload: Option::None,
+            };
+        }
+
+        // ...
+        let row = res.unwrap();
+        let row = self.base.textify_row(row).data;
+
+        // return
+        DefaultReturn {
+            success: true,
+            message: String::from("Follow exists"),
+            payload: Option::Some(Log {
+                id: row.get("id").unwrap().to_string(),
+                logtype: row.get("logtype").unwrap().to_string(),
+                timestamp: row.get("timestamp").unwrap().parse::<u128>().unwrap(),
+                content: row.get("content").unwrap().to_string(),
+            }),
+        }
+    }
+
+    /// Get the [`Relationship`] between `user` and `other`
+    ///
+    /// # Arguments:
+    /// * `user` - username of the user viewing the relationship
+    /// * `other` - username of the other user
+    pub async fn get_relationship(&self, user: String, other: String) -> Relationship {
+        let mut relationship = Relationship {
+            blocking: self.get_block(user.clone(), other.clone()).await.is_some(),
+            ..Default::default()
+        };
+
+        let _timer = self.metrics.time_query("get_relationship");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" IN (?, ?) AND \"logtype\" IN ('follow', 'follow_request')"
+        } else {
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" IN ($1, $2) AND \"logtype\" IN ('follow', 'follow_request')"
+        };
+
+        let following = UserFollow {
+            user: user.clone(),
+            is_following: other.clone(),
+        };
+
+        let followed_by = UserFollow {
+            user: other.clone(),
+            is_following: user.clone(),
+        };
+
+        let c = &self.base.db.client;
+        let rows = match sqlquery(query)
+            .bind::<String>(serde_json::to_string::<UserFollow>(&following).unwrap())
+            .bind::<String>(serde_json::to_string::<UserFollow>(&followed_by).unwrap())
+            .fetch_all(c)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log_error("get_relationship", &e);
+                return relationship;
+            }
+        };
+
+        for row in rows {
+            let row = self.base.textify_row(row).data;
+
+            let follow = match serde_json::from_str::<UserFollow>(row.get("content").unwrap()) {
+                Ok(f) => f,
+                Err(_) => continue,
+            };
+
+            let timestamp = row.get("timestamp").unwrap().parse::<u128>().ok();
+
+            if row.get("logtype").unwrap() == "follow_request" {
+                relationship.requested |= follow == following;
+                continue;
+            }
+
+            if follow == following {
+                relationship.following = true;
+                relationship.followed_at = timestamp;
+            } else {
+                relationship.followed_by = true;
+                relationship.followed_by_at = timestamp;
+            }
+        }
+
+        relationship.mutual = relationship.following & relationship.followed_by;
+
+        // return
+        relationship
+    }
+
+    /// Get the [`UserFollow`]s that are following the given `user`
+    ///
+    /// # Arguments:
+    /// * `user` - username of user to check
+    /// * `page` - [`PageQuery`]
+    pub async fn get_user_followers(
+        &self,
+        user: String,
+        page: &PageQuery,
+    ) -> DefaultReturn<Option<Page<Log>>> {
+        match self
+            .get_logs_page("follow", json_field_like("is_following", &user), page)
+            .await
+        {
+            Some(p) => DefaultReturn {
+                success: true,
+                message: String::from("Followers exists"),
+                payload: Option::Some(p),
+            },
+            None => DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch followers"),
+                payload: Option::None,
+            },
+        }
+    }
+
+    /// Get the [`UserFollow`]s that the given `user` is following
+    ///
+    /// # Arguments:
+    /// * `user` - username of user to check
+    /// * `page` - [`PageQuery`]
+    pub async fn get_user_following(
+        &self,
+        user: String,
+        page: &PageQuery,
+    ) -> DefaultReturn<Option<Page<Log>>> {
+        match self
+            .get_logs_page("follow", json_field_like("user", &user), page)
+            .await
+        {
+            Some(p) => DefaultReturn {
+                success: true,
+                message: String::from("Following exists"),
+                payload: Option::Some(p),
+            },
+            None => DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch following"),
+                payload: Option::None,
+            },
+        }
+    }
+
+    /// Get query placeholders for `count` values (`?, ?` or `$1, $2`)
+    ///
+    /// # Arguments:
+    /// * `count` - the amount of values
+    fn placeholders(&self, count: usize) -> String {
+        self.number_placeholders(&vec!["?"; count].join(", "))
+    }
+
+    /// Get a page of [`Log`]s (newest first), `None` if the query fails
+    ///
+    /// # Arguments:
+    /// * `logtype` - the type of the logs
+    /// * `content_like` - `LIKE` pattern the log content must match (see [`json_field_like`])
+    /// * `page` - [`PageQuery`]
+    pub async fn get_logs_page(
+        &self,
+        logtype: &str,
+        content_like: String,
+        page: &PageQuery,
+    ) -> Option<Page<Log>> {
+        let rows = self
+            .get_page(
+                "sh_logs",
+                "\"content\" LIKE ? ESCAPE '!' AND \"logtype\" = ?",
+                vec![content_like, logtype.to_string()],
+                page,
+            )
+            .await?;
+
+        // return
+        Option::Some(rows.map(|row| Log {
+            id: row.get("id").unwrap().to_string(),
+            logtype: row.get("logtype").unwrap().to_string(),
+            timestamp: row.get("timestamp").unwrap().parse::<u128>().unwrap(),
+            content: row.get("content").unwrap().to_string(),
+        }))
+    }
+
+    /// Get a page of rows from `table` (newest first), `None` if the query fails
+    ///
+    /// Rows are ordered by their `timestamp` and `id` columns.
+    ///
+    /// # Arguments:
+    /// * `table` - the table to select from
+    /// * `filter` - SQL condition rows must match (using `?` for values)
+    /// * `values` - the values used in `filter`
+    /// * `page` - [`PageQuery`]
+    pub async fn get_page(
+        &self,
+        table: &str,
+        filter: &str,
+        values: Vec<String>,
+        page: &PageQuery,
+    ) -> Option<Page<HashMap<String, String>>> {
+        let _timer = self.metrics.time_query("get_page");
+
+        // timestamps are stored as text, but they're all the same length (so they
+        // still sort correctly)
+        let direction = page.direction();
+        let (condition, order) = match direction {
+            Direction::First => ("", "DESC"),
+            Direction::After(_) => (
+                " AND (\"timestamp\" < ? OR (\"timestamp\" = ? AND \"id\" < ?))",
+                "DESC",
+            ),
+            Direction::Before(_) => (
+                " AND (\"timestamp\" > ? OR (\"timestamp\" = ? AND \"id\" > ?))",
+                "ASC",
+            ),
+        };
+
+        let query = self.number_placeholders(&format!(
+            "SELECT * FROM \"{table}\" WHERE ({filter}){condition} ORDER BY \"timestamp\" {order}, \"id\" {order} LIMIT ?"
+        ));
+
+        let mut q = sqlquery(&query);
+
+        for value in &values {
+            q = q.bind::<&String>(value);
+        }
+
+        if let Direction::After(ref cursor) | Direction::Before(ref cursor) = direction {
+            q = q
+                .bind::<String>(cursor.timestamp.to_string())
+                .bind::<String>(cursor.timestamp.to_string())
+                .bind::<&String>(&cursor.id);
+        }
+
+        // fetch one extra row to know if there's another page
+        let c = &self.base.db.client;
+        let rows = match q.bind((page.limit() + 1) as i64).fetch_all(c).await {
+            Ok(r) => r,
+            Err(e) => {
+                log_error("get_page", &e);
+                return Option::None;
+            }
+        };
+
+        let rows: Vec<HashMap<String, String>> = rows
+            .into_iter()
+            .map(|row| self.base.textify_row(row).data)
+            .collect();
+
+        // return
+        Option::Some(Page::from_rows(rows, page, |row| Cursor {
+            timestamp: row
+                .get("timestamp")
+                .and_then(|t| t.parse::<u128>().ok())
+                .unwrap_or(0),
+            id: row.get("id").cloned().unwrap_or_default(),
+        }))
+    }
+
+    /// Replace `?` placeholders with `$1`, `$2`, ... when using postgres
+    ///
+    /// # Arguments:
+    /// * `query` - the query (values must never be written into it directly)
+    fn number_placeholders(&self, query: &str) -> String {
+        if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            return query.to_string();
+        }
+
+        let mut output = String::new();
+        let mut i = 0;
+
+        for c in query.chars() {
+            if c == '?' {
+                i += 1;
+                output.push_str(&format!("${i}"));
+            } else {
+                output.push(c);
+            }
+        }
+
+        output
+    }
+
+    /// Get many users by their `username` (in one query)
+    ///
+    /// # Arguments:
+    /// * `usernames` - the usernames of the users
+    pub async fn get_users_by_username(
+        &self,
+        usernames: &[String],
+    ) -> HashMap<String, UserState<UserMetadata>> {
+        let _timer = self.metrics.time_query("get_users_by_username");
+
+        let mut output: HashMap<String, UserState<UserMetadata>> = HashMap::new();
+
+        if usernames.is_empty() {
+            return output;
+        }
+
+        let query = format!(
+            "SELECT * FROM \"sh_users\" WHERE \"username\" IN ({})",
+            self.placeholders(usernames.len())
+        );
+
+        let mut q = sqlquery(&query);
+
+        for username in usernames {
+            q = q.bind::<&String>(username);
+        }
+
+        let c = &self.base.db.client;
+        let rows = match q.fetch_all(c).await {
+            Ok(r) => r,
+            Err(e) => {
+                log_error("get_users_by_username", &e);
+                return output;
+            }
+        };
+
+        for row in rows {
+            let row = self.base.textify_row(row).data;
+            let username = row.get("username").unwrap().to_string();
+
+            output.insert(
+                username.clone(),
+                UserState {
+                    username,
+                    id_hashed: row.get("id_hashed").unwrap().to_string(),
+                    role: row.get("role").unwrap().to_string(),
+                    timestamp: row.get("timestamp").unwrap().parse::<u128>().unwrap_or(0),
+                    metadata: serde_json::from_str::<UserMetadata>(row.get("metadata").unwrap())
+                        .unwrap_or_default(),
+                },
+            );
+        }
+
+        // return
+        output
+    }
+
+    /// Get which of the given [`UserFollow`]s exist (in one query)
+    ///
+    /// # Arguments:
+    /// * `follows` - the [`UserFollow`]s to check
+    pub async fn get_existing_follows(&self, follows: &[UserFollow]) -> Vec<UserFollow> {
+        let _timer = self.metrics.time_query("get_existing_follows");
+
+        if follows.is_empty() {
+            return Vec::new();
+        }
+
+        // follow logs are stored as the exact serialized UserFollow
+        let contents: Vec<String> = follows
+            .iter()
+            .map(|f| serde_json::to_string::<UserFollow>(f).unwrap())
+            .collect();
+
+        let query = format!(
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" IN ({}) AND \"logtype\" = 'follow'",
+            self.placeholders(contents.len())
+        );
+
+        let mut q = sqlquery(&query);
+
+        for content in &contents {
+            q = q.bind::<&String>(content);
+        }
+
+        let c = &self.base.db.client;
+        let rows = match q.fetch_all(c).await {
+            Ok(r) => r,
+            Err(e) => {
+                log_error("get_existing_follows", &e);
+                return Vec::new();
+            }
+        };
+
+        // return
+        rows.into_iter()
+            .filter_map(|row| {
+                let row = self.base.textify_row(row).data;
+                serde_json::from_str::<UserFollow>(row.get("content").unwrap()).ok()
+            })
+            .collect()
+    }
+
+    /// Turn follow [`Log`]s into [`UserSummary`]s (users are fetched in batches)
+    ///
+    /// # Arguments:
+    /// * `logs` - the follow logs (from [`Database::get_user_followers`] or [`Database::get_user_following`])
+    /// * `followers` - if `logs` are followers (summarize `user`) or following (summarize `is_following`)
+    /// * `viewer` - the username of the user viewing the list (for `follows_you` and `you_follow`)
+    pub async fn summarize_follows(
+        &self,
+        logs: Vec<Log>,
+        followers: bool,
+        viewer: Option<String>,
+    ) -> Vec<UserSummary> {
+        let follows: Vec<(String, u128)> = logs
+            .iter()
+            .filter_map(|l| {
+                let follow = serde_json::from_str::<UserFollow>(&l.content).ok()?;
+                Option::Some((
+                    if followers {
+                        follow.user
+                    } else {
+                        follow.is_following
+                    },
+                    l.timestamp,
+                ))
+            })
+            .collect();
+
+        let usernames: Vec<String> = follows.iter().map(|f| f.0.clone()).collect();
+        let users = self.get_users_by_username(&usernames).await;
+
+        // check relationships with the viewer
+        let mut you_follow: HashSet<String> = HashSet::new();
+        let mut follows_you: HashSet<String> = HashSet::new();
+
+        if let Some(viewer) = viewer {
+            let mut pairs: Vec<UserFollow> = Vec::new();
+
+            for username in &usernames {
+                pairs.push(UserFollow {
+                    user: viewer.clone(),
+                    is_following: username.clone(),
+                });
+
+                pairs.push(UserFollow {
+                    user: username.clone(),
+                    is_following: viewer.clone(),
+                });
+            }
+
+            for follow in self.get_existing_follows(&pairs).await {
+                if follow.user == viewer {
+                    you_follow.insert(follow.is_following);
+                } else {
+                    follows_you.insert(follow.user);
+                }
+            }
+        }
+
+        // ...
+        let mut output: Vec<UserSummary> = Vec::new();
+
+        for (username, followed_at) in follows {
+            // skip users which no longer exist
+            let user = match users.get(&username) {
+                Some(u) => u,
+                None => continue,
+            };
+
+            output.push(UserSummary {
+                nickname: user.metadata.nickname.clone().unwrap_or(username.clone()),
+                avatar_url: format!("/api/v1/auth/users/{username}/avatar"),
+                role: user.role.clone(),
+                followed_at,
+                follows_you: follows_you.contains(&username),
+                you_follow: you_follow.contains(&username),
+                username,
             });
         }
 
-        // return
-        return DefaultReturn {
-            success: true,
-            message: String::from("Followers exists"),
-            payload: Option::Some(output),
+        // return
+        output
+    }
+
+    /// Get the amount of followers a user has
+    ///
+    /// # Arguments:
+    /// * `user` - username of user to check
+    pub async fn get_user_follow_count(&self, user: String) -> DefaultReturn<usize> {
+        let _timer = self.metrics.time_query("get_user_follow_count");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE ? ESCAPE '!' AND \"logtype\" = 'follow'"
+        } else {
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE $1 ESCAPE '!' AND \"logtype\" = 'follow'"
+        };
+
+        let c = &self.base.db.client;
+        let res = sqlquery(query)
+            .bind::<&String>(&json_field_like("is_following", &user))
+            .fetch_all(c)
+            .await;
+
+        if let Err(e) = res {
+            log_error("get_user_follow_count", &e);
+
+            return DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch followers"),
+                payload: 0,
+            };
+        }
+
+        // ...
+        let rows = res.unwrap();
+
+        // return
+        DefaultReturn {
+            success: true,
+            message: String::from("Follow exists"),
+            payload: rows.len(),
+        }
+    }
+
+    /// Get the amount of users a user is following
+    ///
+    /// # Arguments:
+    /// * `user` - username of user to check
+    pub async fn get_user_following_count(&self, user: String) -> DefaultReturn<usize> {
+        let _timer = self.metrics.time_query("get_user_following_count");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE ? ESCAPE '!' AND \"logtype\" = 'follow'"
+        } else {
+            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE $1 ESCAPE '!' AND \"logtype\" = 'follow'"
+        };
+
+        let c = &self.base.db.client;
+        let res = sqlquery(query)
+            .bind::<&String>(&json_field_like("user", &user))
+            .fetch_all(c)
+            .await;
+
+        if let Err(e) = res {
+            log_error("get_user_following_count", &e);
+
+            return DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch following"),
+                payload: 0,
+            };
+        }
+
+        // ...
+        let rows = res.unwrap();
+
+        // return
+        DefaultReturn {
+            success: true,
+            message: String::from("Follow exists"),
+            payload: rows.len(),
+        }
+    }
+
+    // SET
+    /// Follow a user (does nothing if `props.user` is already following them)
+    ///
+    /// Returns `true` if a new follow was created.
+    ///
+    /// # Arguments:
+    /// * `props` - [`UserFollow`]
+    pub async fn follow_user(&self, props: &UserFollow) -> LogResult<bool> {
+        self.check_follow(props).await?;
+
+        if !self.create_follow(props).await? {
+            return Ok(false);
+        }
+
+        // notify followed user
+        let _ = self
+            .create_notification(
+                props.is_following.clone(),
+                "follow",
+                format!("{} followed you", props.user),
+                format!("/{}", props.user),
+            )
+            .await;
+
+        Ok(true)
+    }
+
+    /// Create a follow log (does nothing if it already exists)
+    ///
+    /// # Arguments:
+    /// * `props` - [`UserFollow`]
+    async fn create_follow(&self, props: &UserFollow) -> LogResult<bool> {
+        // check if follow exists
+        let existing: DefaultReturn<Option<Log>> = self
+            .get_follow_by_user(props.user.to_owned(), props.is_following.to_owned())
+            .await;
+
+        if existing.success {
+            return Ok(false);
+        }
+
+        let _timer = self.metrics.time_query("create_follow");
+
+        self.logs
+            .create_log(
+                String::from("follow"),
+                serde_json::to_string::<UserFollow>(props).unwrap(),
+            )
+            .await?;
+
+        self.events.publish(Event::new(
+            "follow",
+            props.is_following.clone(),
+            Option::Some(props.user.clone()),
+        ));
+
+        Ok(true)
+    }
+
+    /// Make sure `props.user` is allowed to follow `props.is_following`
+    ///
+    /// # Arguments:
+    /// * `props` - [`UserFollow`]
+    async fn check_follow(&self, props: &UserFollow) -> LogResult<()> {
+        // users cannot be the same
+        if props.user == props.is_following {
+            return Err(LogError::Other);
+        }
+
+        // make sure both users exist
+        let existing = self.get_user_by_username(props.user.to_owned()).await;
+
+        if existing.is_err() {
+            return Err(LogError::NotFound);
+        }
+
+        // make sure both users exist
+        let existing = self
+            .get_user_by_username(props.is_following.to_owned())
+            .await;
+
+        if existing.is_err() {
+            return Err(LogError::NotFound);
+        }
+
+        // users cannot follow users they've blocked (or been blocked by)
+        if self
+            .is_blocked_either(props.user.to_owned(), props.is_following.to_owned())
+            .await
+        {
+            return Err(LogError::Other);
+        }
+
+        Ok(())
+    }
+
+    /// Unfollow a user (does nothing if `props.user` isn't following them)
+    ///
+    /// Returns `true` if a follow was removed.
+    ///
+    /// # Arguments:
+    /// * `props` - [`UserFollow`]
+    pub async fn unfollow_user(&self, props: &UserFollow) -> LogResult<bool> {
+        // check if follow exists
+        let existing: DefaultReturn<Option<Log>> = self
+            .get_follow_by_user(props.user.to_owned(), props.is_following.to_owned())
+            .await;
+
+        if !existing.success {
+            return Ok(false);
+        }
+
+        let _timer = self.metrics.time_query("unfollow_user");
+
+        self.logs.delete_log(existing.payload.unwrap().id).await?;
+
+        self.events.publish(Event::new(
+            "unfollow",
+            props.is_following.clone(),
+            Option::Some(props.user.clone()),
+        ));
+
+        Ok(true)
+    }
+
+    // follow requests
+
+    // GET
+    /// Get the pending follow requests sent to the given `user`
+    ///
+    /// # Arguments:
+    /// * `user` - username of user to check
+    /// * `page` - [`PageQuery`]
+    pub async fn get_follow_requests(
+        &self,
+        user: String,
+        page: &PageQuery,
+    ) -> DefaultReturn<Option<Page<Log>>> {
+        match self
+            .get_logs_page(
+                "follow_request",
+                json_field_like("is_following", &user),
+                page,
+            )
+            .await
+        {
+            Some(p) => DefaultReturn {
+                success: true,
+                message: String::from("Follow requests exist"),
+                payload: Option::Some(p),
+            },
+            None => DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch follow requests"),
+                payload: Option::None,
+            },
+        }
+    }
+
+    /// Get the pending follow requests sent by the given `user`
+    ///
+    /// # Arguments:
+    /// * `user` - username of user to check
+    /// * `page` - [`PageQuery`]
+    pub async fn get_sent_follow_requests(
+        &self,
+        user: String,
+        page: &PageQuery,
+    ) -> DefaultReturn<Option<Page<Log>>> {
+        match self
+            .get_logs_page("follow_request", json_field_like("user", &user), page)
+            .await
+        {
+            Some(p) => DefaultReturn {
+                success: true,
+                message: String::from("Follow requests exist"),
+                payload: Option::Some(p),
+            },
+            None => DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch follow requests"),
+                payload: Option::None,
+            },
+        }
+    }
+
+    /// Check if a user's followers and following lists can be seen by a viewer
+    ///
+    /// Lists of private accounts are only shown to the user themselves and their followers.
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user the lists belong to
+    /// * `viewer` - username of the user viewing the lists
+    pub async fn can_view_follows(&self, username: String, viewer: Option<String>) -> bool {
+        if !self.get_user_settings(username.clone()).await.private {
+            return true;
+        }
+
+        match viewer {
+            Some(viewer) if viewer == username => true,
+            Some(viewer) => self.get_follow_by_user(viewer, username).await.success,
+            None => false,
+        }
+    }
+
+    // SET
+    /// Request to follow a user (does nothing if a request already exists, or
+    /// `props.user` is already following them)
+    ///
+    /// Returns `true` if a new request was created.
+    ///
+    /// # Arguments:
+    /// * `props` - [`UserFollow`]
+    pub async fn request_follow(&self, props: &UserFollow) -> LogResult<bool> {
+        self.check_follow(props).await?;
+
+        // check if follow (or request) exists
+        if self
+            .get_follow_by_user(props.user.to_owned(), props.is_following.to_owned())
+            .await
+            .success
+            | self
+                .get_follow_request(props.user.to_owned(), props.is_following.to_owned())
+                .await
+                .success
+        {
+            return Ok(false);
+        }
+
+        {
+            let _timer = self.metrics.time_query("request_follow");
+
+            self.logs
+                .create_log(
+                    String::from("follow_request"),
+                    serde_json::to_string::<UserFollow>(props).unwrap(),
+                )
+                .await?;
+        }
+
+        // notify requested user
+        let _ = self
+            .create_notification(
+                props.is_following.clone(),
+                "follow_request",
+                format!("{} requested to follow you", props.user),
+                format!("/{}/settings", props.is_following),
+            )
+            .await;
+
+        Ok(true)
+    }
+
+    /// Remove a pending follow request (does nothing if it doesn't exist)
+    ///
+    /// Returns `true` if a request was removed.
+    ///
+    /// # Arguments:
+    /// * `props` - [`UserFollow`]
+    pub async fn remove_follow_request(&self, props: &UserFollow) -> LogResult<bool> {
+        // check if request exists
+        let existing: DefaultReturn<Option<Log>> = self
+            .get_follow_request(props.user.to_owned(), props.is_following.to_owned())
+            .await;
+
+        if !existing.success {
+            return Ok(false);
+        }
+
+        let _timer = self.metrics.time_query("remove_follow_request");
+
+        // return
+        self.logs
+            .delete_log(existing.payload.unwrap().id)
+            .await
+            .map(|_| true)
+    }
+
+    /// Approve a pending follow request, creating the follow
+    ///
+    /// The follow is created and the request removed in one transaction, so a
+    /// failure leaves the request pending.
+    ///
+    /// # Arguments:
+    /// * `props` - [`UserFollow`]
+    pub async fn approve_follow_request(&self, props: &UserFollow) -> LogResult<bool> {
+        // check if request exists
+        let request: DefaultReturn<Option<Log>> = self
+            .get_follow_request(props.user.to_owned(), props.is_following.to_owned())
+            .await;
+
+        if !request.success {
+            return Err(LogError::NotFound);
+        }
+
+        let request = request.payload.unwrap();
+
+        self.check_follow(props).await?;
+
+        // check if follow exists
+        let following = self
+            .get_follow_by_user(props.user.to_owned(), props.is_following.to_owned())
+            .await
+            .success;
+
+        // create follow and remove request
+        let (insert, delete): (&str, &str) =
+            if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+                (
+                    "INSERT INTO \"sh_logs\" VALUES (?, ?, ?, ?)",
+                    "DELETE FROM \"sh_logs\" WHERE \"id\" = ?",
+                )
+            } else {
+                (
+                    "INSERT INTO \"sh_logs\" VALUES ($1, $2, $3, $4)",
+                    "DELETE FROM \"sh_logs\" WHERE \"id\" = $1",
+                )
+            };
+
+        let c = &self.base.db.client;
+        let res = async {
+            let _timer = self.metrics.time_query("approve_follow_request");
+
+            let mut tx = c.begin().await?;
+
+            if !following {
+                sqlquery(insert)
+                    .bind::<String>(dorsal::utility::random_id())
+                    .bind::<&str>("follow")
+                    .bind::<String>(dorsal::utility::unix_epoch_timestamp().to_string())
+                    .bind::<String>(serde_json::to_string::<UserFollow>(props).unwrap())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            sqlquery(delete)
+                .bind::<&String>(&request.id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        }
+        .await;
+
+        if let Err(e) = res {
+            log_error("approve_follow_request", &e);
+            return Err(LogError::Other);
+        }
+
+        // update cache
+        self.base
+            .cachedb
+            .remove(format!("{}:{}", self.logs.options.prefix, request.id))
+            .await;
+
+        if following {
+            return Ok(false);
+        }
+
+        self.events.publish(Event::new(
+            "follow",
+            props.is_following.clone(),
+            Option::Some(props.user.clone()),
+        ));
+
+        // notify requesting user
+        let _ = self
+            .create_notification(
+                props.user.clone(),
+                "follow_approved",
+                format!("{} approved your follow request", props.is_following),
+                format!("/{}", props.is_following),
+            )
+            .await;
+
+        Ok(true)
+    }
+
+    // blocks
+
+    // GET
+    /// Get a [`UserBlock`] by the username of the user blocking
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user blocking
+    /// * `blocked` - username of the user that `username` blocked
+    pub async fn get_block(&self, username: String, blocked: String) -> Option<UserBlock> {
+        let _timer = self.metrics.time_query("get_block");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_blocks\" WHERE \"username\" = ? AND \"blocked\" = ?"
+        } else {
+            "SELECT * FROM \"sh_blocks\" WHERE \"username\" = $1 AND \"blocked\" = $2"
+        };
+
+        let c = &self.base.db.client;
+        let row = sqlquery(query)
+            .bind::<&String>(&username)
+            .bind::<&String>(&blocked)
+            .fetch_one(c)
+            .await
+            .ok()?;
+
+        // return
+        Option::Some(deserialize_block(self.base.textify_row(row).data))
+    }
+
+    /// Check if either user is blocking the other
+    ///
+    /// # Arguments:
+    /// * `a` - username of the first user
+    /// * `b` - username of the second user
+    pub async fn is_blocked_either(&self, a: String, b: String) -> bool {
+        let _timer = self.metrics.time_query("is_blocked_either");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_blocks\" WHERE (\"username\" = ? AND \"blocked\" = ?) OR (\"username\" = ? AND \"blocked\" = ?)"
+        } else {
+            "SELECT * FROM \"sh_blocks\" WHERE (\"username\" = $1 AND \"blocked\" = $2) OR (\"username\" = $3 AND \"blocked\" = $4)"
+        };
+
+        let c = &self.base.db.client;
+        sqlquery(query)
+            .bind::<&String>(&a)
+            .bind::<&String>(&b)
+            .bind::<&String>(&b)
+            .bind::<&String>(&a)
+            .fetch_one(c)
+            .await
+            .is_ok()
+    }
+
+    /// Check if a user's profile (and their lists) should be hidden from a viewer
+    ///
+    /// Profiles are hidden from users they have blocked.
+    ///
+    /// # Arguments:
+    /// * `username` - username of the profile
+    /// * `viewer` - username of the user viewing the profile
+    pub async fn is_hidden_from(&self, username: String, viewer: Option<String>) -> bool {
+        match viewer {
+            Some(viewer) => self.get_block(username, viewer).await.is_some(),
+            None => false,
+        }
+    }
+
+    /// Get the [`UserBlock`]s created by the given `username`
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user blocking
+    /// * `page` - [`PageQuery`]
+    pub async fn get_user_blocks(
+        &self,
+        username: String,
+        page: &PageQuery,
+    ) -> DefaultReturn<Option<Page<UserBlock>>> {
+        match self
+            .get_page("sh_blocks", "\"username\" = ?", vec![username], page)
+            .await
+        {
+            Some(p) => DefaultReturn {
+                success: true,
+                message: String::from("Blocks exist"),
+                payload: Option::Some(p.map(deserialize_block)),
+            },
+            None => DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch blocks"),
+                payload: Option::None,
+            },
+        }
+    }
+
+    // SET
+    /// Block a user (does nothing if they're already blocked)
+    ///
+    /// Follows between the two users are removed (in both directions).
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user blocking
+    /// * `blocked` - username of the user to block
+    pub async fn block_user(&self, username: String, blocked: String) -> DefaultReturn<bool> {
+        // users cannot be the same
+        if username == blocked {
+            return DefaultReturn {
+                success: false,
+                message: String::from("You cannot block yourself."),
+                payload: false,
+            };
+        }
+
+        // make sure user exists
+        if self.get_user_by_username(blocked.clone()).await.is_err() {
+            return DefaultReturn {
+                success: false,
+                message: String::from("User does not exist!"),
+                payload: false,
+            };
+        }
+
+        // check if block exists
+        if self
+            .get_block(username.clone(), blocked.clone())
+            .await
+            .is_some()
+        {
+            return DefaultReturn {
+                success: true,
+                message: String::from("User already blocked."),
+                payload: false,
+            };
+        }
+
+        // create block (a concurrent block of the same user is ignored)
+        let query: &str = if self.base.db._type == "sqlite" {
+            "INSERT OR IGNORE INTO \"sh_blocks\" VALUES (?, ?, ?, ?)"
+        } else if self.base.db._type == "mysql" {
+            "INSERT IGNORE INTO \"sh_blocks\" VALUES (?, ?, ?, ?)"
+        } else {
+            "INSERT INTO \"sh_blocks\" VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING"
+        };
+
+        let c = &self.base.db.client;
+        let res = {
+            let _timer = self.metrics.time_query("block_user");
+
+            sqlquery(query)
+                .bind::<String>(dorsal::utility::random_id())
+                .bind::<&String>(&username)
+                .bind::<&String>(&blocked)
+                .bind::<String>(dorsal::utility::unix_epoch_timestamp().to_string())
+                .execute(c)
+                .await
+        };
+
+        match res {
+            Ok(r) if r.rows_affected() == 0 => {
+                return DefaultReturn {
+                    success: true,
+                    message: String::from("User already blocked."),
+                    payload: false,
+                }
+            }
+            Ok(_) => (),
+            Err(e) => {
+                log_error("block_user", &e);
+
+                return DefaultReturn {
+                    success: false,
+                    message: e.to_string(),
+                    payload: false,
+                };
+            }
+        }
+
+        // remove follows and follow requests (in both directions)
+        for follow in [
+            UserFollow {
+                user: username.clone(),
+                is_following: blocked.clone(),
+            },
+            UserFollow {
+                user: blocked,
+                is_following: username,
+            },
+        ] {
+            let _ = self.unfollow_user(&follow).await;
+            let _ = self.remove_follow_request(&follow).await;
+        }
+
+        // return
+        DefaultReturn {
+            success: true,
+            message: String::from("User blocked."),
+            payload: true,
+        }
+    }
+
+    /// Unblock a user (does nothing if they aren't blocked)
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user blocking
+    /// * `blocked` - username of the user to unblock
+    pub async fn unblock_user(&self, username: String, blocked: String) -> DefaultReturn<bool> {
+        let _timer = self.metrics.time_query("unblock_user");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "DELETE FROM \"sh_blocks\" WHERE \"username\" = ? AND \"blocked\" = ?"
+        } else {
+            "DELETE FROM \"sh_blocks\" WHERE \"username\" = $1 AND \"blocked\" = $2"
+        };
+
+        let c = &self.base.db.client;
+        let res = sqlquery(query)
+            .bind::<&String>(&username)
+            .bind::<&String>(&blocked)
+            .execute(c)
+            .await;
+
+        match res {
+            Ok(r) if r.rows_affected() > 0 => DefaultReturn {
+                success: true,
+                message: String::from("User unblocked."),
+                payload: true,
+            },
+            Ok(_) => DefaultReturn {
+                success: true,
+                message: String::from("User is not blocked."),
+                payload: false,
+            },
+            Err(e) => DefaultReturn {
+                success: false,
+                message: e.to_string(),
+                payload: false,
+            },
+        }
+    }
+
+    // notifications
+
+    // GET
+    /// Get a [`Notification`] by its `id`
+    ///
+    /// # Arguments:
+    /// * `id` - the ID of the notification
+    pub async fn get_notification(&self, id: String) -> Option<Notification> {
+        let _timer = self.metrics.time_query("get_notification");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT * FROM \"sh_notifications\" WHERE \"id\" = ?"
+        } else {
+            "SELECT * FROM \"sh_notifications\" WHERE \"id\" = $1"
+        };
+
+        let c = &self.base.db.client;
+        let row = sqlquery(query)
+            .bind::<&String>(&id)
+            .fetch_one(c)
+            .await
+            .ok()?;
+
+        // return
+        Option::Some(deserialize_notification(self.base.textify_row(row).data))
+    }
+
+    /// Get the [`Notification`]s sent to the given `username` (newest first)
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user
+    /// * `page` - [`PageQuery`]
+    pub async fn get_user_notifications(
+        &self,
+        username: String,
+        page: &PageQuery,
+    ) -> DefaultReturn<Option<Page<Notification>>> {
+        match self
+            .get_page("sh_notifications", "\"username\" = ?", vec![username], page)
+            .await
+        {
+            Some(p) => DefaultReturn {
+                success: true,
+                message: String::from("Notifications exist"),
+                payload: Option::Some(p.map(deserialize_notification)),
+            },
+            None => DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch notifications"),
+                payload: Option::None,
+            },
+        }
+    }
+
+    /// Get the amount of unread [`Notification`]s sent to the given `username`
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user
+    pub async fn get_unread_notification_count(&self, username: String) -> usize {
+        let _timer = self.metrics.time_query("get_unread_notification_count");
+
+        // check in cache
+        let cached = self
+            .base
+            .cachedb
+            .get(format!("sh_notification_count:{}", username))
+            .await;
+
+        if let Some(c) = cached {
+            return c.parse::<usize>().unwrap_or(0);
+        }
+
+        // ...
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "SELECT \"id\" FROM \"sh_notifications\" WHERE \"username\" = ? AND \"is_read\" = 'false'"
+        } else {
+            "SELECT \"id\" FROM \"sh_notifications\" WHERE \"username\" = $1 AND \"is_read\" = 'false'"
+        };
+
+        let c = &self.base.db.client;
+        let count = match sqlquery(query)
+            .bind::<&String>(&username)
+            .fetch_all(c)
+            .await
+        {
+            Ok(rows) => rows.len(),
+            Err(e) => {
+                log_error("get_unread_notification_count", &e);
+                return 0;
+            }
         };
+
+        // store in cache
+        self.base
+            .cachedb
+            .set(
+                format!("sh_notification_count:{}", username),
+                count.to_string(),
+            )
+            .await;
+
+        // return
+        count
     }
 
-    /// Get the [`UserFollow`]s that the given `user` is following
+    // SET
+    /// Send a [`Notification`] to a user
     ///
     /// # Arguments:
-    /// * `user` - username of user to check
-    /// * `offset` - optional value representing the SQL fetch offset
-    pub async fn get_user_following(
+    /// * `username` - username of the user to notify
+    /// * `kind` - the kind of notification (see [`Notification`])
+    /// * `title` - the title of the notification
+    /// * `link` - where the notification leads (can be empty)
+    pub async fn create_notification(
         &self,
-        user: String,
-        offset: Option<i32>,
-    ) -> DefaultReturn<Option<Vec<Log>>> {
+        username: String,
+        kind: &str,
+        title: String,
+        link: String,
+    ) -> DefaultReturn<Option<Notification>> {
+        let _timer = self.metrics.time_query("create_notification");
+
+        let notification = Notification {
+            id: dorsal::utility::random_id(),
+            username,
+            kind: kind.to_string(),
+            title,
+            link,
+            read: false,
+            timestamp: dorsal::utility::unix_epoch_timestamp(),
+        };
+
         let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE ? AND \"logtype\" = 'follow' ORDER BY \"timestamp\" DESC LIMIT 50 OFFSET ?"
+            "INSERT INTO \"sh_notifications\" VALUES (?, ?, ?, ?, ?, ?, ?)"
         } else {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE $1 AND \"logtype\" = 'follow' ORDER BY \"timestamp\" DESC LIMIT 50 OFFSET $2"
+            "INSERT INTO \"sh_notifications\" VALUES ($1, $2, $3, $4, $5, $6, $7)"
         };
 
         let c = &self.base.db.client;
         let res = sqlquery(query)
-            .bind::<&String>(&format!("%\"user\":\"{user}\"%"))
-            .bind(if offset.is_some() { offset.unwrap() } else { 0 })
-            .fetch_all(c)
+            .bind::<&String>(&notification.id)
+            .bind::<&String>(&notification.username)
+            .bind::<&String>(&notification.kind)
+            .bind::<&String>(&notification.title)
+            .bind::<&String>(&notification.link)
+            .bind::<&str>("false")
+            .bind::<String>(notification.timestamp.to_string())
+            .execute(c)
             .await;
 
-        if res.is_err() {
+        if let Err(e) = res {
+            log_error("create_notification", &e);
+
             return DefaultReturn {
                 success: false,
-                message: String::from("Failed to fetch following"),
+                message: e.to_string(),
                 payload: Option::None,
             };
         }
 
-        // ...
-        let rows = res.unwrap();
-        let mut output: Vec<Log> = Vec::new();
+        // update cache
+        self.base
+            .cachedb
+            .remove(format!("sh_notification_count:{}", notification.username))
+            .await;
 
-        for row in rows {
-            let row = self.base.textify_row(row).data;
-            output.push(Log {
-                id: row.get("id").unwrap().to_string(),
-                logtype: row.get("logtype").unwrap().to_string(),
-                timestamp: row.get("timestamp").unwrap().parse::<u128>().unwrap(),
-                content: row.get("content").unwrap().to_string(),
-            });
+        // return
+        DefaultReturn {
+            success: true,
+            message: String::from("Notification created!"),
+            payload: Option::Some(notification),
+        }
+    }
+
+    /// Notify a user that their profile was edited by someone else (staff)
+    ///
+    /// Does nothing if `editor` is the user themselves.
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user that was edited
+    /// * `editor` - username of the user that made the edit
+    pub async fn notify_profile_edit(&self, username: String, editor: String) {
+        if username == editor {
+            return;
+        }
+
+        let _ = self
+            .create_notification(
+                username.clone(),
+                "profile_edit",
+                format!("Your profile was edited by {editor}"),
+                format!("/{username}"),
+            )
+            .await;
+    }
+
+    /// Mark notifications sent to `username` as read
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user
+    /// * `id` - the ID of a single notification (`None` to mark all of them)
+    pub async fn mark_notifications_read(
+        &self,
+        username: String,
+        id: Option<String>,
+    ) -> DefaultReturn<bool> {
+        self.update_notifications(
+            username,
+            id,
+            "UPDATE \"sh_notifications\" SET \"is_read\" = 'true' WHERE \"username\" = ?",
+            "Marked as read.",
+        )
+        .await
+    }
+
+    /// Delete notifications sent to `username`
+    ///
+    /// # Arguments:
+    /// * `username` - username of the user
+    /// * `id` - the ID of a single notification (`None` to clear all of them)
+    pub async fn clear_notifications(
+        &self,
+        username: String,
+        id: Option<String>,
+    ) -> DefaultReturn<bool> {
+        self.update_notifications(
+            username,
+            id,
+            "DELETE FROM \"sh_notifications\" WHERE \"username\" = ?",
+            "Notifications cleared.",
+        )
+        .await
+    }
+
+    /// Run `query` (using `?` for the username) on the notifications sent to
+    /// `username`, or a single one of them if an `id` is given
+    async fn update_notifications(
+        &self,
+        username: String,
+        id: Option<String>,
+        query: &str,
+        message: &str,
+    ) -> DefaultReturn<bool> {
+        let _timer = self.metrics.time_query("update_notifications");
+
+        let query = match id {
+            Some(_) => format!("{query} AND \"id\" = ?"),
+            None => query.to_string(),
+        };
+
+        let query = self.number_placeholders(&query);
+
+        let c = &self.base.db.client;
+        let mut q = sqlquery(&query).bind::<&String>(&username);
+
+        if let Some(ref id) = id {
+            q = q.bind::<&String>(id);
         }
 
+        let res = q.execute(c).await;
+
+        // update cache
+        self.base
+            .cachedb
+            .remove(format!("sh_notification_count:{}", username))
+            .await;
+
         // return
-        return DefaultReturn {
-            success: true,
-            message: String::from("Following exists"),
-            payload: Option::Some(output),
+        match res {
+            Ok(r) => DefaultReturn {
+                success: true,
+                message: String::from(message),
+                payload: r.rows_affected() > 0,
+            },
+            Err(e) => DefaultReturn {
+                success: false,
+                message: e.to_string(),
+                payload: false,
+            },
+        }
+    }
+
+    // webhooks
+
+    // GET
+    /// Get every registered [`Webhook`]
+    pub async fn get_webhooks(&self) -> Vec<Webhook> {
+        let _timer = self.metrics.time_query("get_webhooks");
+
+        let c = &self.base.db.client;
+        let rows = match sqlquery("SELECT * FROM \"sh_webhooks\" ORDER BY \"timestamp\" DESC")
+            .fetch_all(c)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log_error("get_webhooks", &e);
+                return Vec::new();
+            }
         };
+
+        // return
+        rows.into_iter()
+            .map(|row| deserialize_webhook(self.base.textify_row(row).data))
+            .collect()
     }
 
-    /// Get the amount of followers a user has
+    /// Get a [`Webhook`] by its `id`
     ///
     /// # Arguments:
-    /// * `user` - username of user to check
-    pub async fn get_user_follow_count(&self, user: String) -> DefaultReturn<usize> {
+    /// * `id` - the ID of the webhook
+    pub async fn get_webhook(&self, id: String) -> Option<Webhook> {
+        let _timer = self.metrics.time_query("get_webhook");
+
         let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE ? AND \"logtype\" = 'follow'"
+            "SELECT * FROM \"sh_webhooks\" WHERE \"id\" = ?"
         } else {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE $1 AND \"logtype\" = 'follow'"
+            "SELECT * FROM \"sh_webhooks\" WHERE \"id\" = $1"
         };
 
         let c = &self.base.db.client;
-        let res = sqlquery(query)
-            .bind::<&String>(&format!("%\"is_following\":\"{user}\"%"))
-            .fetch_all(c)
-            .await;
+        let row = sqlquery(query)
+            .bind::<&String>(&id)
+            .fetch_one(c)
+            .await
+            .ok()?;
 
-        if res.is_err() {
+        // return
+        Option::Some(deserialize_webhook(self.base.textify_row(row).data))
+    }
+
+    /// Get the [`WebhookDelivery`] attempts for a [`Webhook`] (newest first)
+    ///
+    /// # Arguments:
+    /// * `id` - the ID of the webhook
+    /// * `page` - [`PageQuery`]
+    pub async fn get_webhook_deliveries(
+        &self,
+        id: String,
+        page: &PageQuery,
+    ) -> DefaultReturn<Option<Page<Log>>> {
+        match self
+            .get_logs_page("webhook_delivery", json_field_like("webhook", &id), page)
+            .await
+        {
+            Some(p) => DefaultReturn {
+                success: true,
+                message: String::from("Deliveries exist"),
+                payload: Option::Some(p),
+            },
+            None => DefaultReturn {
+                success: false,
+                message: String::from("Failed to fetch deliveries"),
+                payload: Option::None,
+            },
+        }
+    }
+
+    // SET
+    /// Register a new [`Webhook`]
+    ///
+    /// # Arguments:
+    /// * `url` - where events are delivered
+    /// * `secret` - the secret deliveries are signed with
+    /// * `events` - the events delivered to the webhook
+    /// * `created_by` - username of the user creating the webhook
+    /// * `allow_private` - allow URLs on loopback and private addresses
+    pub async fn create_webhook(
+        &self,
+        url: String,
+        secret: String,
+        events: Vec<String>,
+        created_by: String,
+        allow_private: bool,
+    ) -> DefaultReturn<Option<Webhook>> {
+        let _timer = self.metrics.time_query("create_webhook");
+
+        // check values
+        if !url.starts_with("http://") && !url.starts_with("https://") {
             return DefaultReturn {
                 success: false,
-                message: String::from("Failed to fetch followers"),
-                payload: 0,
+                message: String::from("URL must start with http:// or https://"),
+                payload: Option::None,
             };
         }
 
-        // ...
-        let rows = res.unwrap();
+        // never deliver to ourselves or the internal network (unless allowed)
+        if let Err(e) = webhooks::resolve(&url, allow_private).await {
+            return DefaultReturn {
+                success: false,
+                message: match e {
+                    NetError::Unresolvable => String::from("URL host could not be resolved"),
+                    NetError::ForbiddenAddress => {
+                        String::from("URL must point to a public address")
+                    }
+                    _ => String::from("URL is invalid"),
+                },
+                payload: Option::None,
+            };
+        }
 
-        // return
-        return DefaultReturn {
-            success: true,
-            message: String::from("Follow exists"),
-            payload: rows.len(),
+        if events.is_empty()
+            | events
+                .iter()
+                .any(|e| (e != "*") && !webhooks::EVENTS.contains(&e.as_str()))
+        {
+            return DefaultReturn {
+                success: false,
+                message: format!(
+                    "Events must be \"*\" or any of: {}",
+                    webhooks::EVENTS.join(", ")
+                ),
+                payload: Option::None,
+            };
+        }
+
+        let webhook = Webhook {
+            id: dorsal::utility::random_id(),
+            url,
+            secret,
+            events,
+            created_by,
+            timestamp: dorsal::utility::unix_epoch_timestamp(),
         };
-    }
 
-    /// Get the amount of users a user is following
-    ///
-    /// # Arguments:
-    /// * `user` - username of user to check
-    pub async fn get_user_following_count(&self, user: String) -> DefaultReturn<usize> {
+        // ...
         let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE ? AND \"logtype\" = 'follow'"
+            "INSERT INTO \"sh_webhooks\" VALUES (?, ?, ?, ?, ?, ?)"
         } else {
-            "SELECT * FROM \"sh_logs\" WHERE \"content\" LIKE $1 AND \"logtype\" = 'follow'"
+            "INSERT INTO \"sh_webhooks\" VALUES ($1, $2, $3, $4, $5, $6)"
         };
 
         let c = &self.base.db.client;
         let res = sqlquery(query)
-            .bind::<&String>(&format!("%\"user\":\"{user}\"%"))
-            .fetch_all(c)
+            .bind::<&String>(&webhook.id)
+            .bind::<&String>(&webhook.url)
+            .bind::<&String>(&webhook.secret)
+            .bind::<String>(webhook.events.join(","))
+            .bind::<&String>(&webhook.created_by)
+            .bind::<String>(webhook.timestamp.to_string())
+            .execute(c)
             .await;
 
-        if res.is_err() {
+        if let Err(e) = res {
+            log_error("create_webhook", &e);
+
             return DefaultReturn {
                 success: false,
-                message: String::from("Failed to fetch following"),
-                payload: 0,
+                message: e.to_string(),
+                payload: Option::None,
             };
         }
 
-        // ...
-        let rows = res.unwrap();
-
         // return
-        return DefaultReturn {
+        DefaultReturn {
             success: true,
-            message: String::from("Follow exists"),
-            payload: rows.len(),
+            message: String::from("Webhook created!"),
+            payload: Option::Some(webhook),
+        }
+    }
+
+    /// Delete a [`Webhook`] by its `id`
+    ///
+    /// # Arguments:
+    /// * `id` - the ID of the webhook
+    pub async fn delete_webhook(&self, id: String) -> DefaultReturn<bool> {
+        let _timer = self.metrics.time_query("delete_webhook");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "DELETE FROM \"sh_webhooks\" WHERE \"id\" = ?"
+        } else {
+            "DELETE FROM \"sh_webhooks\" WHERE \"id\" = $1"
+        };
+
+        let c = &self.base.db.client;
+        match sqlquery(query).bind::<&String>(&id).execute(c).await {
+            Ok(r) if r.rows_affected() > 0 => DefaultReturn {
+                success: true,
+                message: String::from("Webhook deleted!"),
+                payload: true,
+            },
+            Ok(_) => DefaultReturn {
+                success: false,
+                message: String::from("Webhook does not exist!"),
+                payload: false,
+            },
+            Err(e) => DefaultReturn {
+                success: false,
+                message: e.to_string(),
+                payload: false,
+            },
+        }
+    }
+
+    /// Record a [`WebhookDelivery`] attempt
+    ///
+    /// # Arguments:
+    /// * `props` - [`WebhookDelivery`]
+    pub async fn log_webhook_delivery(&self, props: &WebhookDelivery) -> LogResult<()> {
+        let _timer = self.metrics.time_query("log_webhook_delivery");
+
+        self.logs
+            .create_log(
+                String::from("webhook_delivery"),
+                serde_json::to_string::<WebhookDelivery>(props).unwrap(),
+            )
+            .await
+    }
+
+    // signing keys
+
+    // GET
+    /// Get every published [`SigningKey`] (newest first)
+    pub async fn get_signing_keys(&self) -> Vec<SigningKey> {
+        let _timer = self.metrics.time_query("get_signing_keys");
+
+        let c = &self.base.db.client;
+        let rows = match sqlquery("SELECT * FROM \"sh_signing_keys\"")
+            .fetch_all(c)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                log_error("get_signing_keys", &e);
+                return Vec::new();
+            }
         };
+
+        let mut keys: Vec<SigningKey> = rows
+            .into_iter()
+            .map(|row| deserialize_signing_key(self.base.textify_row(row).data))
+            .collect();
+
+        keys.sort_by_key(|k| std::cmp::Reverse(k.timestamp));
+        keys
+    }
+
+    /// Get the [`SigningKey`] new tokens should be signed with (the newest key
+    /// which has been published for at least [`jwt::PUBLISH_DELAY`])
+    pub async fn get_current_signing_key(&self) -> Option<SigningKey> {
+        let published = dorsal::utility::unix_epoch_timestamp()
+            .saturating_sub(jwt::PUBLISH_DELAY as u128 * 1000);
+
+        self.get_signing_keys()
+            .await
+            .into_iter()
+            .find(|k| k.timestamp <= published)
     }
 
     // SET
-    /// Toggle the following status of `user` on `is_following` ([`UserFollow`])
+    /// Publish the next [`SigningKey`] ahead of time and delete keys which can no
+    /// longer have valid tokens
+    ///
+    /// The next key is created [`jwt::PUBLISH_DELAY`] before the current key is
+    /// `config.rotation` old, so it takes over right on time.
     ///
     /// # Arguments:
-    /// * `props` - [`UserFollow`]
-    pub async fn toggle_user_follow(&self, props: &mut UserFollow) -> LogResult<()> {
-        // users cannot be the same
-        if props.user == props.is_following {
-            return Err(LogError::Other);
-        }
+    /// * `config` - [`JwtConfig`]
+    pub async fn maintain_signing_keys(&self, config: &JwtConfig) {
+        let now = dorsal::utility::unix_epoch_timestamp();
+        let delay = jwt::PUBLISH_DELAY as u128 * 1000;
+        let rotation = config.rotation as u128 * 1000;
 
-        // make sure both users exist
-        let existing = self.get_user_by_username(props.user.to_owned()).await;
+        let keys = self.get_signing_keys().await;
 
-        if !existing.is_ok() {
-            return Err(LogError::NotFound);
+        // publish the next key
+        let newest = keys.first().map(|k| now.saturating_sub(k.timestamp));
+
+        if newest.is_none_or(|age| age + delay >= rotation) {
+            self.create_signing_key().await;
         }
 
-        // make sure both users exist
-        let existing = self
-            .get_user_by_username(props.is_following.to_owned())
-            .await;
+        // delete keys retired for longer than a token can live
+        let published = now.saturating_sub(delay);
+        let mut active: Option<u128> = Option::None; // when the key after this one took over
 
-        if !existing.is_ok() {
-            return Err(LogError::NotFound);
+        for key in keys {
+            if key.timestamp > published {
+                continue; // not used yet
+            }
+
+            if let Some(replaced) = active {
+                if now.saturating_sub(replaced + delay) > config.ttl as u128 * 1000 {
+                    // every token signed with this key has expired
+                    self.delete_signing_key(key.id.clone()).await;
+                }
+            }
+
+            active = Option::Some(key.timestamp);
         }
+    }
 
-        // check if follow exists
-        let existing: DefaultReturn<Option<Log>> = self
-            .get_follow_by_user(props.user.to_owned(), props.is_following.to_owned())
+    /// Create a new [`SigningKey`] (which is used for new tokens once it has been
+    /// published for [`jwt::PUBLISH_DELAY`])
+    pub async fn create_signing_key(&self) -> DefaultReturn<Option<SigningKey>> {
+        let _timer = self.metrics.time_query("create_signing_key");
+
+        let key = SigningKey::generate();
+
+        // ...
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "INSERT INTO \"sh_signing_keys\" VALUES (?, ?, ?, ?)"
+        } else {
+            "INSERT INTO \"sh_signing_keys\" VALUES ($1, $2, $3, $4)"
+        };
+
+        let c = &self.base.db.client;
+        let res = sqlquery(query)
+            .bind::<&String>(&key.id)
+            .bind::<&String>(&key.private_key)
+            .bind::<&String>(&key.public_key)
+            .bind::<String>(key.timestamp.to_string())
+            .execute(c)
             .await;
 
-        if existing.success {
-            // delete log and return
-            return self.logs.delete_log(existing.payload.unwrap().id).await;
+        if let Err(e) = res {
+            log_error("create_signing_key", &e);
+
+            return DefaultReturn {
+                success: false,
+                message: e.to_string(),
+                payload: Option::None,
+            };
         }
 
         // return
-        self.logs
-            .create_log(
-                String::from("follow"),
-                serde_json::to_string::<UserFollow>(&props).unwrap(),
-            )
+        DefaultReturn {
+            success: true,
+            message: String::from("Signing key created!"),
+            payload: Option::Some(key),
+        }
+    }
+
+    /// Delete a [`SigningKey`] by its `id`
+    ///
+    /// # Arguments:
+    /// * `id` - the ID of the key
+    pub async fn delete_signing_key(&self, id: String) -> bool {
+        let _timer = self.metrics.time_query("delete_signing_key");
+
+        let query: &str = if (self.base.db._type == "sqlite") | (self.base.db._type == "mysql") {
+            "DELETE FROM \"sh_signing_keys\" WHERE \"id\" = ?"
+        } else {
+            "DELETE FROM \"sh_signing_keys\" WHERE \"id\" = $1"
+        };
+
+        let c = &self.base.db.client;
+        sqlquery(query)
+            .bind::<&String>(&id)
+            .execute(c)
             .await
+            .is_ok()
     }
 }
@@ -0,0 +1,216 @@
+//! # Events
+//!
+//! Realtime events (registrations, follows, bans, profile updates) published by [`Database`](crate::db::Database)
+//! mutation methods and streamed to clients over Server-Sent Events (see `api::events`).
+//!
+//! Events are delivered to subscribers in the same process by default. When
+//! `events.store` is set to `redis`, events are also published to the `sh_events`
+//! redis channel, and events published by other instances are delivered to local
+//! subscribers (so multiple instances can share one stream).
+use std::sync::mpsc;
+
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::config::EventsConfig;
+
+/// The redis channel events are fanned out through
+pub const REDIS_CHANNEL: &str = "sh_events";
+
+/// The amount of events a slow subscriber can fall behind before missing events
+const CAPACITY: usize = 256;
+
+/// A single realtime event
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Event {
+    pub id: String,
+    pub kind: String, // "register", "follow", "unfollow", "ban" or "profile_update"
+    pub username: String, // the user the event is about (ex: the followed user)
+    pub actor: Option<String>, // the user that caused the event (ex: the follower)
+    pub timestamp: u128,
+    #[serde(skip)]
+    pub remote: bool, // if the event was published by another instance
+}
+
+impl Event {
+    /// Create a new [`Event`]
+    ///
+    /// # Arguments:
+    /// * `kind` - the kind of event
+    /// * `username` - the user the event is about
+    /// * `actor` - the user that caused the event
+    pub fn new(kind: &str, username: String, actor: Option<String>) -> Event {
+        Event {
+            id: dorsal::utility::random_id(),
+            kind: kind.to_string(),
+            username,
+            actor,
+            timestamp: dorsal::utility::unix_epoch_timestamp(),
+            remote: false,
+        }
+    }
+
+    /// Check if this event involves the given `username`
+    pub fn involves(&self, username: &str) -> bool {
+        (self.username == username) | (self.actor.as_deref() == Some(username))
+    }
+
+    /// Format this event as a Server-Sent Events message
+    pub fn to_sse(&self) -> String {
+        format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            self.id,
+            self.kind,
+            serde_json::to_string(self).unwrap()
+        )
+    }
+}
+
+/// An [`Event`] sent through redis (tagged with the instance that sent it)
+#[derive(Serialize, Deserialize)]
+struct RemoteEvent {
+    origin: String,
+    event: Event,
+}
+
+/// Delivers [`Event`]s to subscribers
+///
+/// Cloning an [`EventBus`] shares the underlying channel.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+    instance: String,
+    /// Payloads waiting to be published to redis (see [`publish_remote`])
+    remote: Option<mpsc::Sender<String>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus {
+            sender: broadcast::channel(CAPACITY).0,
+            instance: dorsal::utility::random_id(),
+            remote: Option::None,
+        }
+    }
+}
+
+impl EventBus {
+    /// Create the [`EventBus`] described by an [`EventsConfig`]
+    ///
+    /// # Arguments:
+    /// * `config` - [`EventsConfig`]
+    /// * `redis` - the redis client used if `config.store` is `redis`
+    pub fn from_config(config: &EventsConfig, redis: redis::Client) -> EventBus {
+        let mut bus = EventBus::default();
+
+        if config.store == "redis" {
+            bus.remote = Option::Some(publish_remote(redis.clone()));
+            bus.listen_remote(redis);
+        }
+
+        bus
+    }
+
+    /// Publish an [`Event`] to every subscriber
+    ///
+    /// Never waits on redis, remote delivery happens in the background.
+    pub fn publish(&self, event: Event) {
+        if let Some(ref remote) = self.remote {
+            let _ = remote.send(
+                serde_json::to_string(&RemoteEvent {
+                    origin: self.instance.clone(),
+                    event: event.clone(),
+                })
+                .unwrap(),
+            );
+        }
+
+        // fails if there are no subscribers, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribe to all future [`Event`]s
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Deliver events published by other instances to local subscribers
+    ///
+    /// # Arguments:
+    /// * `client` - the redis client to subscribe with
+    fn listen_remote(&self, client: redis::Client) {
+        let sender = self.sender.clone();
+        let instance = self.instance.clone();
+
+        std::thread::spawn(move || loop {
+            if let Err(e) = forward_remote(&client, &sender, &instance) {
+                log::warn!(target: "shuttle::events", "lost redis subscription ({e}), retrying");
+            }
+
+            std::thread::sleep(std::time::Duration::from_secs(5));
+        });
+    }
+}
+
+/// Publish payloads to [`REDIS_CHANNEL`] from a background thread, returning
+/// the channel payloads are handed to it through
+///
+/// The connection is kept open between events, and reopened after it fails (an
+/// event which can't be published is dropped, local subscribers still get it).
+///
+/// # Arguments:
+/// * `client` - the redis client to publish with
+fn publish_remote(client: redis::Client) -> mpsc::Sender<String> {
+    let (sender, receiver) = mpsc::channel::<String>();
+
+    std::thread::spawn(move || {
+        let mut connection: Option<redis::Connection> = Option::None;
+
+        // runs until every sender (every clone of the bus) is dropped
+        for payload in receiver {
+            if connection.is_none() {
+                connection = client.get_connection().ok();
+            }
+
+            let c = match connection.as_mut() {
+                Some(c) => c,
+                None => {
+                    log::warn!(target: "shuttle::events", "failed to connect to redis, event not published");
+                    continue;
+                }
+            };
+
+            if let Err(e) = c.publish::<_, _, usize>(REDIS_CHANNEL, payload) {
+                log::warn!(target: "shuttle::events", "failed to publish event to redis ({e})");
+                connection = Option::None;
+            }
+        }
+    });
+
+    sender
+}
+
+/// Forward events from [`REDIS_CHANNEL`] to `sender` until the connection fails
+fn forward_remote(
+    client: &redis::Client,
+    sender: &broadcast::Sender<Event>,
+    instance: &str,
+) -> redis::RedisResult<()> {
+    let mut c = client.get_connection()?;
+    let mut pubsub = c.as_pubsub();
+    pubsub.subscribe(REDIS_CHANNEL)?;
+
+    loop {
+        let payload: String = pubsub.get_message()?.get_payload()?;
+
+        match serde_json::from_str::<RemoteEvent>(&payload) {
+            // our own events were already delivered locally
+            Ok(mut remote) if remote.origin != instance => {
+                remote.event.remote = true;
+                let _ = sender.send(remote.event);
+            }
+            _ => continue,
+        }
+    }
+}
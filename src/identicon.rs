@@ -0,0 +1,52 @@
+//! # Identicons
+//!
+//! Deterministic default avatars, generated from the hash of a username.
+//!
+//! Identicons are a mirrored 5x5 grid (so they're always symmetrical), drawn
+//! in a color picked from the same hash.
+use sha2::{Digest, Sha256};
+
+/// The smallest size an identicon can be rendered at
+pub const MIN_SIZE: u32 = 16;
+/// The largest size an identicon can be rendered at
+pub const MAX_SIZE: u32 = 512;
+/// The size used when no size is given
+pub const DEFAULT_SIZE: u32 = 128;
+
+/// Generate an identicon SVG for `username`
+///
+/// # Arguments:
+/// * `username` - the username the identicon is for
+/// * `size` - the width and height of the image (clamped to [`MIN_SIZE`]..=[`MAX_SIZE`])
+pub fn generate(username: &str, size: u32) -> String {
+    let hash = Sha256::digest(username.as_bytes());
+    let size = size.clamp(MIN_SIZE, MAX_SIZE);
+
+    // color
+    let hue = u16::from_be_bytes([hash[0], hash[1]]) % 360;
+    let saturation = 45 + (hash[2] % 20);
+    let lightness = 45 + (hash[3] % 15);
+
+    // cells (only the left 3 columns are picked, the rest are mirrored)
+    let mut path = String::new();
+
+    for row in 0..5 {
+        for column in 0..3 {
+            let bit = row * 3 + column;
+
+            if (hash[4 + bit / 8] >> (bit % 8)) & 1 == 0 {
+                continue;
+            }
+
+            path.push_str(&format!("M{column} {row}h1v1h-1z"));
+
+            if column != 2 {
+                path.push_str(&format!("M{} {row}h1v1h-1z", 4 - column));
+            }
+        }
+    }
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" viewBox=\"-0.5 -0.5 6 6\" shape-rendering=\"crispEdges\"><rect x=\"-0.5\" y=\"-0.5\" width=\"6\" height=\"6\" fill=\"#f0f0f0\"/><path fill=\"hsl({hue}, {saturation}%, {lightness}%)\" d=\"{path}\"/></svg>"
+    )
+}
@@ -0,0 +1,170 @@
+//! # JWT
+//!
+//! Short-lived signed tokens (EdDSA/Ed25519) for downstream services, so they can
+//! verify users offline using the keys published at `/.well-known/jwks.json`.
+//!
+//! Configured in the `[jwt]` section of the config (see [`JwtConfig`]).
+//!
+//! Signing keys are stored in the database so every instance signs with the same
+//! keys. New keys are published for [`PUBLISH_DELAY`] (the JWKS `max-age`) before
+//! anything is signed with them, so verifiers with a cached JWKS know them by the
+//! time they see them. Retired keys stay published until every token they signed
+//! has expired.
+//!
+//! Keys are created and retired by [`maintain`], never while serving requests.
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+
+use crate::db::Database;
+
+/// How long a new key is published before it's used (seconds), also the `max-age`
+/// of `/.well-known/jwks.json`
+pub const PUBLISH_DELAY: u64 = 300;
+
+/// How often [`maintain`] checks the signing keys
+pub const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Create and retire signing keys forever (see [`Database::maintain_signing_keys`])
+///
+/// # Arguments:
+/// * `db` - [`Database`]
+/// * `config` - [`JwtConfig`]
+pub async fn maintain(db: Database, config: JwtConfig) {
+    loop {
+        db.maintain_signing_keys(&config).await;
+        tokio::time::sleep(MAINTENANCE_INTERVAL).await;
+    }
+}
+
+/// JWT settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// The `iss` claim
+    pub issuer: String,
+    /// Audiences tokens can be issued for (issuing is disabled if empty)
+    pub audiences: Vec<String>,
+    /// Maximum (and default) token lifetime (seconds)
+    pub ttl: u64,
+    /// How long a signing key is used before a new one is created (seconds)
+    pub rotation: u64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        JwtConfig {
+            issuer: String::from("shuttle"),
+            audiences: Vec::new(),
+            ttl: 300,
+            rotation: 60 * 60 * 24 * 7,
+        }
+    }
+}
+
+/// An Ed25519 signing key
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct SigningKey {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub private_key: String, // base64url PKCS#8 document
+    pub public_key: String, // base64url raw public key
+    pub timestamp: u128,
+}
+
+impl SigningKey {
+    /// Generate a new [`SigningKey`]
+    pub fn generate() -> SigningKey {
+        let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .expect("failed to generate signing key");
+
+        let pair = Ed25519KeyPair::from_pkcs8(document.as_ref()).unwrap();
+
+        SigningKey {
+            id: dorsal::utility::random_id(),
+            private_key: URL_SAFE_NO_PAD.encode(document.as_ref()),
+            public_key: URL_SAFE_NO_PAD.encode(pair.public_key().as_ref()),
+            timestamp: dorsal::utility::unix_epoch_timestamp(),
+        }
+    }
+
+    /// Get the [`Jwk`] for this key
+    pub fn jwk(&self) -> Jwk {
+        Jwk {
+            kty: String::from("OKP"),
+            crv: String::from("Ed25519"),
+            alg: String::from("EdDSA"),
+            _use: String::from("sig"),
+            kid: self.id.clone(),
+            x: self.public_key.clone(),
+        }
+    }
+
+    /// Sign a set of [`Claims`], returning the encoded token (`None` if the key is invalid)
+    pub fn sign(&self, claims: &Claims) -> Option<String> {
+        let document = URL_SAFE_NO_PAD.decode(&self.private_key).ok()?;
+        let pair = Ed25519KeyPair::from_pkcs8(&document).ok()?;
+
+        let header = Header {
+            alg: String::from("EdDSA"),
+            typ: String::from("JWT"),
+            kid: self.id.clone(),
+        };
+
+        let message = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_string(&header).unwrap()),
+            URL_SAFE_NO_PAD.encode(serde_json::to_string(claims).unwrap())
+        );
+
+        let signature = pair.sign(message.as_bytes());
+        Option::Some(format!(
+            "{message}.{}",
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        ))
+    }
+}
+
+/// A JWT header
+#[derive(Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    typ: String,
+    kid: String,
+}
+
+/// The claims of an issued token
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Claims {
+    pub iss: String,
+    pub sub: String, // the username
+    pub aud: String,
+    pub iat: u64,
+    pub exp: u64,
+    pub jti: String,
+    pub role: String,
+    pub permissions: Vec<String>,
+    pub elevation: i32,
+}
+
+/// A single public key in a [`Jwks`] (RFC 7517, RFC 8037)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub alg: String,
+    #[serde(rename = "use")]
+    pub _use: String,
+    pub kid: String,
+    pub x: String,
+}
+
+/// A JSON Web Key Set
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
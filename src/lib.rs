@@ -0,0 +1,286 @@
+#![doc = include_str!("../README.md")]
+#![doc(html_favicon_url = "https://swmff.com/static/favicon.svg")]
+#![doc(html_logo_url = "https://avatars.githubusercontent.com/u/174050063")]
+#![doc(issue_tracker_base_url = "https://github.com/swmff/shuttle/issues/")]
+
+use std::sync::Arc;
+
+use actix_files as fs;
+use actix_web::web;
+
+pub mod avatar;
+pub mod cache;
+pub mod captcha;
+pub mod config;
+pub mod db;
+pub mod events;
+pub mod identicon;
+pub mod jwt;
+pub mod logging;
+pub mod media;
+pub mod metadata;
+pub mod metrics;
+pub mod net;
+pub mod pagination;
+pub mod ratelimit;
+pub mod services;
+pub mod setup;
+pub mod webhooks;
+
+pub mod api;
+pub mod pages;
+
+pub mod markup;
+
+pub use crate::db::{AppData, Database};
+
+use crate::captcha::CaptchaVerifier;
+use crate::config::Config;
+use crate::media::MediaStore;
+use crate::ratelimit::RateLimiter;
+use crate::services::ServiceClients;
+use crate::setup::SetupToken;
+
+/// Builds the [`AppData`] for every worker
+///
+/// Everything but the HTTP client (which can't be sent between threads) is
+/// created once and shared between workers.
+///
+/// ```ignore
+/// let builder = AppDataBuilder::new(db, config).await;
+///
+/// HttpServer::new(move || {
+///     App::new()
+///         .app_data(web::Data::new(builder.build()))
+///         .configure(|cfg| shuttle::configure(cfg, &ShuttleOptions::default()))
+/// })
+/// ```
+#[derive(Clone)]
+pub struct AppDataBuilder {
+    db: Database,
+    limiter: RateLimiter,
+    captcha: Arc<dyn CaptchaVerifier>,
+    media: Arc<dyn MediaStore>,
+    services: ServiceClients,
+    config: Arc<Config>,
+    setup: SetupToken,
+}
+
+impl AppDataBuilder {
+    /// Initialize the database, create everything shared between workers and
+    /// start the background tasks ([`webhooks::dispatch`], [`jwt::maintain`])
+    ///
+    /// Must be called from within the actix runtime.
+    ///
+    /// # Arguments:
+    /// * `db` - [`Database`]
+    /// * `config` - [`Config`]
+    pub async fn new(db: Database, config: Arc<Config>) -> AppDataBuilder {
+        db.init().await;
+
+        // first-run bootstrap
+        let setup = if db.has_admin().await {
+            SetupToken::default()
+        } else {
+            let setup = SetupToken::generate();
+
+            log::warn!(
+                "No admin account exists yet. Create one at http://localhost:{}/flow/setup using this setup token: {}",
+                config.port,
+                setup.get().unwrap()
+            );
+
+            setup
+        };
+
+        // deliver webhooks
+        actix_web::rt::spawn(webhooks::dispatch(
+            db.clone(),
+            http_client(),
+            webhooks::RetryPolicy::from_config(&config.webhooks),
+        ));
+
+        // publish and retire jwt signing keys
+        if !config.jwt.audiences.is_empty() {
+            actix_web::rt::spawn(jwt::maintain(db.clone(), config.jwt.clone()));
+        }
+
+        // return
+        AppDataBuilder {
+            // create abuse protection (shared between all workers)
+            limiter: RateLimiter::from_config(&config.ratelimit, db.base.cachedb.client.clone()),
+            captcha: captcha::from_difficulty(config.registration.pow_difficulty),
+            // load service clients
+            services: ServiceClients::new(config.services.clients.clone()),
+            // create media storage
+            media: Arc::new(media::LocalStore::new(&config.media.dir)),
+            db,
+            config,
+            setup,
+        }
+    }
+
+    /// Build the [`AppData`] for the current worker
+    pub fn build(&self) -> AppData {
+        AppData {
+            db: self.db.clone(),
+            http_client: http_client(),
+            limiter: self.limiter.clone(),
+            captcha: self.captcha.clone(),
+            media: self.media.clone(),
+            services: self.services.clone(),
+            config: self.config.clone(),
+            setup: self.setup.clone(),
+        }
+    }
+}
+
+/// Options for [`configure`]
+#[derive(Clone)]
+pub struct ShuttleOptions {
+    /// Every route is mounted under this prefix (ex: `/auth`, empty for the root)
+    ///
+    /// Pages link to each other (and to `/static`) by absolute paths, so a
+    /// prefix can only be used with `pages` disabled.
+    pub prefix: String,
+    /// Mount the HTML pages (not just the API)
+    pub pages: bool,
+    /// Serve static files from this directory (at `{prefix}/static`)
+    pub static_dir: Option<String>,
+    /// Serve generated docs from `./target/doc` (at `{prefix}/api/docs`)
+    pub docs: bool,
+}
+
+impl Default for ShuttleOptions {
+    fn default() -> Self {
+        ShuttleOptions {
+            prefix: String::new(),
+            pages: true,
+            static_dir: Option::Some(String::from("./static")),
+            docs: false,
+        }
+    }
+}
+
+/// Mount Shuttle into an actix app
+///
+/// [`AppData`] must be provided (as `web::Data<AppData>`) by the app, see
+/// [`AppDataBuilder`].
+///
+/// # Panics:
+/// If `opts.prefix` isn't empty while `opts.pages` is enabled.
+///
+/// # Arguments:
+/// * `cfg` - the app's [`web::ServiceConfig`]
+/// * `opts` - [`ShuttleOptions`]
+pub fn configure(cfg: &mut web::ServiceConfig, opts: &ShuttleOptions) {
+    assert!(
+        opts.prefix.is_empty() | !opts.pages,
+        "pages can't be mounted under a prefix, disable them or remove the prefix"
+    );
+
+    let mut scope = web::scope(&opts.prefix)
+        // middleware
+        .wrap(actix_web::middleware::from_fn(ratelimit::middleware))
+        .wrap(actix_web::middleware::from_fn(metrics::middleware))
+        .wrap(actix_web::middleware::from_fn(logging::middleware));
+
+    // static dir
+    if let Some(ref static_dir) = opts.static_dir {
+        scope = scope.service(fs::Files::new("/static", static_dir).show_files_listing());
+    }
+
+    // docs
+    if opts.docs {
+        scope = scope.service(fs::Files::new("/api/docs", "./target/doc").show_files_listing());
+    }
+
+    scope = scope
+        // GET health
+        .service(crate::api::health::healthz_request)
+        .service(crate::api::health::readyz_request)
+        .service(crate::api::health::metrics_request)
+        // GET api
+        .service(crate::api::auth::whoami)
+        .service(crate::api::auth::challenge_request)
+        .service(crate::api::forward::forward_request)
+        // POST api
+        // POST auth
+        .service(crate::api::auth::callback_request)
+        .service(crate::api::auth::register)
+        .service(crate::api::auth::login)
+        .service(crate::api::auth::login_secondary_token)
+        .service(crate::api::auth::edit_about_request)
+        .service(crate::api::auth::refresh_secondary_token_request)
+        .service(crate::api::auth::update_request)
+        .service(crate::api::auth::follow_request)
+        .service(crate::api::auth::block_request)
+        .service(crate::api::auth::approve_follow_request)
+        .service(crate::api::auth::ban_request)
+        .service(crate::api::auth::upload_avatar_request)
+        .service(crate::api::auth::upload_banner_request)
+        .service(crate::api::auth::verify_field_request)
+        .service(crate::api::introspect::introspect_request)
+        .service(crate::api::jwt::issue_request)
+        .service(crate::api::jwt::rotate_request)
+        .service(crate::api::setup::setup_request)
+        // DELETE auth
+        .service(crate::api::auth::delete_avatar_request)
+        .service(crate::api::auth::delete_banner_request)
+        .service(crate::api::auth::unfollow_request)
+        .service(crate::api::auth::unblock_request)
+        .service(crate::api::auth::reject_follow_request)
+        // GET users
+        .service(crate::api::auth::profile_request)
+        .service(crate::api::auth::relationship_request)
+        .service(crate::api::auth::blocks_request)
+        .service(crate::api::auth::follow_requests_request)
+        .service(crate::api::auth::sent_follow_requests_request)
+        .service(crate::api::auth::avatar_request)
+        .service(crate::api::auth::identicon_request)
+        .service(crate::api::auth::banner_request)
+        .service(crate::api::auth::followers_request)
+        .service(crate::api::auth::following_request)
+        .service(crate::api::auth::level_request)
+        .service(crate::api::events::events_request)
+        .service(crate::api::webhooks::list_request)
+        .service(crate::api::webhooks::create_request)
+        .service(crate::api::webhooks::delete_request)
+        .service(crate::api::webhooks::deliveries_request)
+        .service(crate::api::webhooks::test_request)
+        .service(crate::api::notifications::notifications_request)
+        .service(crate::api::notifications::count_request)
+        .service(crate::api::notifications::read_all_request)
+        .service(crate::api::notifications::read_request)
+        .service(crate::api::notifications::clear_all_request)
+        .service(crate::api::notifications::clear_request)
+        .service(crate::api::jwt::jwks_request)
+        // GET root
+        .service(crate::api::auth::logout);
+
+    if opts.pages {
+        scope = scope
+            // GET dashboard
+            .service(crate::pages::auth::register_request)
+            .service(crate::pages::auth::login_request)
+            .service(crate::pages::auth::login_secondary_token_request)
+            .service(crate::pages::setup::setup_request)
+            // GET root
+            .service(crate::pages::home::home_request)
+            .service(crate::pages::notifications::notifications_request)
+            // GET users
+            .service(crate::pages::auth::followers_request)
+            .service(crate::pages::auth::following_request)
+            .service(crate::pages::auth::user_settings_request)
+            .service(crate::pages::auth::profile_request);
+    }
+
+    cfg.service(scope);
+}
+
+/// Create the HTTP client used for outgoing requests
+///
+/// Redirects are followed manually where needed (see [`avatar::fetch`]).
+pub fn http_client() -> awc::Client {
+    awc::Client::builder().disable_redirects().finish()
+}
@@ -0,0 +1,194 @@
+//! # Logging
+//!
+//! Structured logging through the [`log`] facade, written by `env_logger` as
+//! either text or JSON (one object per line, see [`LoggingConfig`]).
+//!
+//! Every request gets an ID (taken from the `X-Request-Id` header, or generated),
+//! which is returned in the response and attached to everything logged while the
+//! request is handled. Values are attached as key-values:
+//!
+//! ```ignore
+//! log::info!(target: "shuttle::auth", username = name.as_str(); "login succeeded");
+//! ```
+//!
+//! Never log tokens, use [`fingerprint`] to tell them apart instead.
+use std::io::Write;
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use log::kv::{self, VisitSource};
+
+use crate::config::LoggingConfig;
+
+/// The header request IDs are read from and returned in
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    /// The ID of the request being handled
+    static REQUEST_ID: String;
+}
+
+/// Get the ID of the request being handled (`None` outside of a request)
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Check if a request ID given by a client is safe to reuse
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && (id.len() <= 128)
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() | ['-', '_', '.', ':'].contains(&c))
+}
+
+/// A short, non-reversible identifier for a token (safe to log)
+///
+/// # Arguments:
+/// * `token` - the unhashed token
+pub fn fingerprint(token: &str) -> String {
+    // hashed twice so the fingerprint isn't a prefix of the stored (hashed) ID
+    dorsal::utility::hash(dorsal::utility::hash(token.to_string()))[..8].to_string()
+}
+
+/// Collects the key-values of a record
+#[derive(Default)]
+struct Fields(Vec<(String, serde_json::Value)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(v) = value.to_u64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_i64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_f64() {
+            serde_json::Value::from(v)
+        } else if let Some(v) = value.to_bool() {
+            serde_json::Value::from(v)
+        } else {
+            serde_json::Value::from(value.to_string())
+        };
+
+        self.0.push((key.to_string(), value));
+        Ok(())
+    }
+}
+
+/// Install the global logger
+///
+/// # Arguments:
+/// * `config` - [`LoggingConfig`] (should be validated already)
+pub fn init(config: &LoggingConfig) {
+    let json = config.format == "json";
+
+    env_logger::Builder::new()
+        // sqlx logs every statement at info (our own queries are logged under shuttle::db)
+        .filter_module("sqlx", log::LevelFilter::Warn)
+        .parse_filters(&config.level)
+        .format(move |buf, record| {
+            let mut fields = Fields::default();
+            let _ = record.key_values().visit(&mut fields);
+
+            if let Some(id) = request_id() {
+                fields
+                    .0
+                    .insert(0, (String::from("request_id"), serde_json::Value::from(id)));
+            }
+
+            let timestamp = buf.timestamp_millis();
+
+            // json (written by hand so the common keys always come first)
+            if json {
+                write!(
+                    buf,
+                    "{{\"timestamp\":\"{timestamp}\",\"level\":\"{}\",\"target\":{},\"message\":{}",
+                    record.level(),
+                    serde_json::Value::from(record.target()),
+                    serde_json::Value::from(record.args().to_string()),
+                )?;
+
+                for (key, value) in fields.0 {
+                    write!(buf, ",{}:{value}", serde_json::Value::from(key))?;
+                }
+
+                return writeln!(buf, "}}");
+            }
+
+            // text
+            write!(
+                buf,
+                "[{timestamp} {:<5} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            )?;
+
+            for (key, value) in fields.0 {
+                match value {
+                    serde_json::Value::String(v) => write!(buf, " {key}={v:?}")?,
+                    v => write!(buf, " {key}={v}")?,
+                }
+            }
+
+            writeln!(buf)
+        })
+        .init();
+}
+
+/// Milliseconds since `start` (rounded to microseconds)
+pub fn duration_ms(start: Instant) -> f64 {
+    (start.elapsed().as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// Assign every request an ID (see [`request_id`]) and log it once it's handled
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_valid_request_id(v))
+        .map(|v| v.to_string())
+        .unwrap_or_else(dorsal::utility::uuid);
+
+    let method = req.method().to_string();
+    let path = req.path().to_string(); // never the query string, it can hold secrets
+    let ip = crate::ratelimit::get_ip(req.request());
+    let start = Instant::now();
+
+    REQUEST_ID
+        .scope(id.clone(), async move {
+            let res = next.call(req).await;
+
+            let status = match res {
+                Ok(ref r) => r.status(),
+                Err(ref e) => e.as_response_error().status_code(),
+            };
+
+            log::info!(
+                target: "shuttle::http",
+                method = method.as_str(),
+                path = path.as_str(),
+                status = status.as_u16(),
+                duration_ms = duration_ms(start),
+                ip = ip.as_str();
+                "{method} {path} {}",
+                status.as_u16()
+            );
+
+            let mut res = res?;
+
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+
+            Ok(res)
+        })
+        .await
+}
@@ -1,139 +1,96 @@
-#![doc = include_str!("../README.md")]
-#![doc(html_favicon_url = "https://swmff.com/static/favicon.svg")]
-#![doc(html_logo_url = "https://avatars.githubusercontent.com/u/174050063")]
-#![doc(issue_tracker_base_url = "https://github.com/swmff/shuttle/issues/")]
-
-use actix_files as fs;
 use actix_web::{web, App, HttpServer};
-use dotenv;
-
-pub mod config;
-pub mod db;
+use std::sync::Arc;
 
-pub mod api;
-pub mod pages;
+use shuttle::db::Database;
+use shuttle::{config, logging, AppDataBuilder};
 
-pub mod markup;
-
-use crate::db::{AppData, Database};
-use dorsal::DatabaseOpts;
+mod cli;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
-    env_logger::init();
 
     // ...
     let args: Vec<String> = config::collect_arguments();
 
-    let port_search: Option<String> = config::get_named_argument(&args, "port");
-    let mut port: u16 = 8080;
+    let command = match cli::parse(&args) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    if command == cli::Command::Help {
+        print!("{}", cli::HELP);
+        return Ok(());
+    }
 
-    if port_search.is_some() {
-        port = port_search.unwrap().parse::<u16>().unwrap();
+    let config = match config::Config::load(&args) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    if config::has_flag(&args, "print-config") {
+        print!("{}", config.to_redacted_toml());
     }
 
-    let static_dir_flag: Option<String> = config::get_named_argument(&args, "static-dir");
+    if let Err(e) = config.validate() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
 
-    // create database
-    let db_type: Option<String> = config::get_named_argument(&args, "db-type");
-    let db_host: Option<String> = config::get_var("DB_HOST");
-    let db_user: Option<String> = config::get_var("DB_USER");
-    let db_pass: Option<String> = config::get_var("DB_PASS");
-    let db_name: Option<String> = config::get_var("DB_NAME");
-
-    let db_is_other: bool = db_type
-        .clone()
-        .is_some_and(|x| (x == String::from("postgres")) | (x == String::from("mysql")));
-
-    if db_is_other && (db_user.is_none() | db_pass.is_none() | db_name.is_none()) {
-        panic!("Missing required database config settings!");
+    if config::has_flag(&args, "print-config") {
+        return Ok(());
     }
 
-    let db: Database = Database::new(DatabaseOpts {
-        _type: db_type,
-        host: db_host,
-        user: if db_is_other {
-            db_user.unwrap()
-        } else {
-            String::new()
-        },
-        pass: if db_is_other {
-            db_pass.unwrap()
-        } else {
-            String::new()
-        },
-        name: if db_is_other {
-            db_name.unwrap()
-        } else {
-            String::new()
-        },
-    })
-    .await;
+    logging::init(&config.logging);
+
+    let port = config.port;
+    let config = Arc::new(config);
+
+    // create database
+    let db: Database = Database::new(config.database_opts(), &config.events).await;
+
+    // run admin commands
+    if !command.is_serve() {
+        if !cli::run(command, db).await {
+            std::process::exit(1);
+        }
+
+        return Ok(());
+    }
 
-    db.init().await;
+    // create app data (and start background tasks)
+    let builder = AppDataBuilder::new(db, config.clone()).await;
 
     // start server
-    println!("Starting server at: http://localhost:{port}");
+    log::info!("Starting server at: http://localhost:{port}");
 
     // serve routes
     HttpServer::new(move || {
-        let client = awc::Client::default();
-        let data = web::Data::new(AppData {
-            db: db.clone(),
-            http_client: client,
-        });
+        let data = web::Data::new(builder.build());
 
         let cors = actix_cors::Cors::default().send_wildcard();
 
         App::new()
             .app_data(web::Data::clone(&data))
-            // middleware
-            .wrap(actix_web::middleware::Logger::default())
+            // middleware (requests are logged by shuttle::logging::middleware)
             .wrap(cors)
-            // static dir
-            .service(
-                fs::Files::new(
-                    "/static",
-                    if static_dir_flag.is_some() {
-                        static_dir_flag.as_ref().unwrap()
-                    } else {
-                        "./static"
+            // routes
+            .configure(|cfg| {
+                shuttle::configure(
+                    cfg,
+                    &shuttle::ShuttleOptions {
+                        static_dir: Option::Some(config.static_dir.clone()),
+                        docs: true,
+                        ..Default::default()
                     },
                 )
-                .show_files_listing(),
-            )
-            // docs
-            .service(fs::Files::new("/api/docs", "./target/doc").show_files_listing())
-            // GET api
-            .service(crate::api::auth::whoami)
-            // POST api
-            // POST auth
-            .service(crate::api::auth::callback_request)
-            .service(crate::api::auth::register)
-            .service(crate::api::auth::login)
-            .service(crate::api::auth::login_secondary_token)
-            .service(crate::api::auth::edit_about_request)
-            .service(crate::api::auth::refresh_secondary_token_request)
-            .service(crate::api::auth::update_request)
-            .service(crate::api::auth::follow_request)
-            .service(crate::api::auth::ban_request)
-            // GET users
-            .service(crate::api::auth::avatar_request)
-            .service(crate::api::auth::followers_request)
-            .service(crate::api::auth::following_request)
-            .service(crate::api::auth::level_request)
-            // GET dashboard
-            .service(crate::pages::auth::register_request)
-            .service(crate::pages::auth::login_request)
-            .service(crate::pages::auth::login_secondary_token_request)
-            // GET root
-            .service(crate::api::auth::logout)
-            .service(crate::pages::home::home_request)
-            // GET users
-            .service(crate::pages::auth::followers_request)
-            .service(crate::pages::auth::following_request)
-            .service(crate::pages::auth::user_settings_request)
+            })
     })
     .bind(("0.0.0.0", port))?
     .run()
@@ -0,0 +1,215 @@
+//! # Media
+//!
+//! Uploaded user images (avatars and banners).
+//!
+//! Uploads are decoded and re-encoded (which strips any metadata), resized to a
+//! fixed size for their [`Slot`], and then kept in a [`MediaStore`].
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use actix_web::web;
+use futures_util::future::BoxFuture;
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Limits};
+
+use crate::avatar::{self, Image};
+
+/// Where an uploaded image is shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Slot {
+    /// The user's avatar (256x256, PNG)
+    Avatar,
+    /// The user's profile banner (1500x500, JPEG)
+    Banner,
+}
+
+impl Slot {
+    /// Get the name of this slot (used in storage keys)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Slot::Avatar => "avatar",
+            Slot::Banner => "banner",
+        }
+    }
+
+    /// Get the `(width, height)` images in this slot are resized to
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Slot::Avatar => (256, 256),
+            Slot::Banner => (1500, 500),
+        }
+    }
+
+    /// Get the storage key for a user's image in this slot
+    ///
+    /// # Arguments:
+    /// * `username` - the user's username
+    pub fn key(&self, username: &str) -> String {
+        // usernames can contain "." so they aren't used directly as file names
+        format!(
+            "{}/{}",
+            self.name(),
+            dorsal::utility::hash(username.to_string())
+        )
+    }
+}
+
+/// Upload errors
+#[derive(Debug)]
+pub enum MediaError {
+    /// The upload is larger than `media.max_size`
+    TooLarge,
+    /// The upload is not a supported image (or is corrupt)
+    NotAnImage,
+    /// The image could not be stored
+    Storage,
+}
+
+impl std::fmt::Display for MediaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use MediaError::*;
+        f.write_str(match self {
+            TooLarge => "Image is too large.",
+            NotAnImage => "File is not a supported image (PNG, JPEG, GIF, or WebP).",
+            Storage => "Failed to store image.",
+        })
+    }
+}
+
+pub type Result<T> = std::result::Result<T, MediaError>;
+
+/// Storage for uploaded images
+///
+/// Implement this to keep uploads somewhere other than the local disk. Methods
+/// are called from request handlers, so they must not block.
+pub trait MediaStore: Send + Sync {
+    /// Get an image by its key
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<Vec<u8>>>;
+
+    /// Store an image under `key` (replacing any existing image)
+    fn put<'a>(&'a self, key: &'a str, bytes: Vec<u8>) -> BoxFuture<'a, Result<()>>;
+
+    /// Delete the image stored under `key`
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Store images in a local directory (files are accessed on the blocking thread
+/// pool)
+pub struct LocalStore {
+    pub dir: PathBuf,
+}
+
+impl MediaStore for LocalStore {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<Vec<u8>>> {
+        let path = self.dir.join(key);
+
+        Box::pin(async move {
+            web::block(move || std::fs::read(path).ok())
+                .await
+                .ok()
+                .flatten()
+        })
+    }
+
+    fn put<'a>(&'a self, key: &'a str, bytes: Vec<u8>) -> BoxFuture<'a, Result<()>> {
+        let path = self.dir.join(key);
+
+        Box::pin(async move {
+            let res = web::block(move || {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+
+                std::fs::write(path, bytes)
+            })
+            .await;
+
+            match res {
+                Ok(Ok(_)) => Ok(()),
+                _ => Err(MediaError::Storage),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<()>> {
+        let path = self.dir.join(key);
+
+        Box::pin(async move {
+            match web::block(move || std::fs::remove_file(path)).await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(MediaError::Storage),
+            }
+        })
+    }
+}
+
+impl LocalStore {
+    /// Create a new [`LocalStore`]
+    ///
+    /// # Arguments:
+    /// * `dir` - the directory images are stored in
+    pub fn new(dir: &str) -> LocalStore {
+        LocalStore {
+            dir: PathBuf::from(dir),
+        }
+    }
+}
+
+/// Decode an uploaded image and re-encode it for `slot`
+///
+/// # Arguments:
+/// * `bytes` - the uploaded file
+/// * `slot` - the [`Slot`] the image is for
+pub fn process_upload(bytes: &[u8], slot: Slot) -> Result<Image> {
+    let format = match avatar::detect_image_type(bytes) {
+        Some("image/png") => ImageFormat::Png,
+        Some("image/jpeg") => ImageFormat::Jpeg,
+        Some("image/gif") => ImageFormat::Gif,
+        Some("image/webp") => ImageFormat::WebP,
+        _ => return Err(MediaError::NotAnImage),
+    };
+
+    // don't let tiny files decode into huge images
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(8192);
+    limits.max_image_height = Some(8192);
+    limits.max_alloc = Some(256 * 1024 * 1024);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+
+    let decoded = match reader.decode() {
+        Ok(i) => i,
+        Err(_) => return Err(MediaError::NotAnImage),
+    };
+
+    // resize
+    let (width, height) = slot.size();
+    let resized = decoded.resize_to_fill(width, height, FilterType::Lanczos3);
+
+    // encode
+    let mut out: Vec<u8> = Vec::new();
+    let (encoded, content_type) = match slot {
+        Slot::Avatar => (
+            resized.write_to(&mut Cursor::new(&mut out), ImageFormat::Png),
+            "image/png",
+        ),
+        Slot::Banner => (
+            // jpeg has no alpha channel
+            resized
+                .into_rgb8()
+                .write_to(&mut Cursor::new(&mut out), ImageFormat::Jpeg),
+            "image/jpeg",
+        ),
+    };
+
+    if encoded.is_err() {
+        return Err(MediaError::NotAnImage);
+    }
+
+    Ok(Image {
+        content_type,
+        bytes: out,
+    })
+}
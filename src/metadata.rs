@@ -0,0 +1,434 @@
+//! # Metadata
+//!
+//! Field-level policy for user metadata updates.
+//!
+//! Clients can only change user-editable fields, and only the fields they send
+//! are changed. Server-managed fields (like `secondary_token`) are never taken
+//! from a client.
+//!
+//! Users can also add custom profile fields ([`ProfileField`]), limited by the
+//! [`FieldSchema`] in the `[profile_fields]` section of the config.
+use std::collections::BTreeMap;
+
+use actix_web::http::Uri;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::db::UserMetadata;
+
+/// Fields which are managed by the server and can never be set by clients
+pub const SERVER_MANAGED_FIELDS: [&str; 1] = ["secondary_token"];
+
+/// The longest a nickname can be (in characters)
+pub const MAX_NICKNAME_LEN: usize = 32;
+/// The longest an about section can be (in bytes)
+pub const MAX_ABOUT_LEN: usize = 200_000;
+/// The longest a URL can be (in bytes)
+pub const MAX_URL_LEN: usize = 2048;
+/// The longest a custom field key can be (in characters)
+pub const MAX_FIELD_KEY_LEN: usize = 32;
+
+/// Validation errors, by field name
+pub type FieldErrors = BTreeMap<String, String>;
+
+/// A custom field shown on a user's profile
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProfileField {
+    /// Identifier for the field (`a-z`, `0-9`, `_` and `-`)
+    pub key: String,
+    /// Display label for the field
+    pub label: String,
+    /// The field's value (links start with `http://` or `https://`)
+    pub value: String,
+    /// If the linked page links back to the user's profile (see [`has_rel_me_link`])
+    #[serde(default)]
+    pub verified: bool,
+}
+
+impl ProfileField {
+    /// Check if this field's value is a link
+    pub fn is_link(&self) -> bool {
+        is_link(&self.value)
+    }
+}
+
+fn is_link(value: &str) -> bool {
+    value.starts_with("http://") | value.starts_with("https://")
+}
+
+/// Limits for custom profile fields
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldSchema {
+    /// The most custom fields a user can have
+    pub max_fields: usize,
+    /// Keys users are allowed to use (any key is allowed if this is empty)
+    pub allowed_keys: Vec<String>,
+    /// The longest a field label can be (in characters)
+    pub max_label_len: usize,
+    /// The longest a field value can be (in characters)
+    pub max_value_len: usize,
+}
+
+impl Default for FieldSchema {
+    fn default() -> Self {
+        FieldSchema {
+            max_fields: 8,
+            allowed_keys: Vec::new(),
+            max_label_len: 32,
+            max_value_len: 256,
+        }
+    }
+}
+
+/// The result of [`apply_update`]
+pub struct ProfileUpdate {
+    /// The user's new metadata
+    pub metadata: UserMetadata,
+    /// The user's new custom fields (`None` if they weren't changed)
+    pub fields: Option<Vec<ProfileField>>,
+    /// If the account should be private (`None` if it wasn't changed)
+    pub private: Option<bool>,
+}
+
+/// Apply a partial update to a user's metadata and custom fields
+///
+/// Fields missing from `update` are left untouched, and `null` clears optional fields.
+/// Custom fields are sent as an object under `fields`, where each key is set to a
+/// value (or `{ "label": ..., "value": ... }`), or `null` to remove it.
+///
+/// # Arguments:
+/// * `metadata` - the user's current [`UserMetadata`]
+/// * `fields` - the user's current [`ProfileField`]s
+/// * `schema` - the [`FieldSchema`] custom fields must follow
+/// * `update` - the fields sent by the client
+pub fn apply_update(
+    metadata: &UserMetadata,
+    fields: &[ProfileField],
+    schema: &FieldSchema,
+    update: &Map<String, Value>,
+) -> Result<ProfileUpdate, FieldErrors> {
+    let mut out = ProfileUpdate {
+        metadata: metadata.clone(),
+        fields: Option::None,
+        private: Option::None,
+    };
+
+    let mut errors = FieldErrors::new();
+
+    for (field, value) in update {
+        let res = match field.as_str() {
+            "nickname" => validate_nickname(value).map(|v| out.metadata.nickname = v),
+            "about" => validate_about(value).map(|v| out.metadata.about = v),
+            "avatar_url" => validate_url(value).map(|v| out.metadata.avatar_url = v),
+            "private" => match value {
+                Value::Bool(b) => {
+                    out.private = Option::Some(*b);
+                    Ok(())
+                }
+                _ => Err(String::from("Expected a boolean.")),
+            },
+            "fields" => {
+                out.fields = Option::Some(apply_fields(fields, schema, value, &mut errors));
+                Ok(())
+            }
+            f if SERVER_MANAGED_FIELDS.contains(&f) => {
+                Err(String::from("This field is managed by the server."))
+            }
+            _ => Err(String::from("Unknown field.")),
+        };
+
+        if let Err(e) = res {
+            errors.insert(field.to_owned(), e);
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(out)
+}
+
+/// Apply an update to custom fields, adding errors to `errors` (as `fields.KEY`)
+fn apply_fields(
+    current: &[ProfileField],
+    schema: &FieldSchema,
+    update: &Value,
+    errors: &mut FieldErrors,
+) -> Vec<ProfileField> {
+    let mut out = current.to_vec();
+
+    let update = match update {
+        Value::Object(u) => u,
+        _ => {
+            errors.insert(String::from("fields"), String::from("Expected an object."));
+            return out;
+        }
+    };
+
+    for (key, value) in update {
+        let error_key = format!("fields.{key}");
+        let existing = out.iter().position(|f| &f.key == key);
+
+        // remove (always allowed, even if the schema no longer allows this key)
+        if value.is_null() {
+            if let Some(i) = existing {
+                out.remove(i);
+            }
+
+            continue;
+        }
+
+        if let Err(e) = validate_field_key(key, schema) {
+            errors.insert(error_key, e);
+            continue;
+        }
+
+        // set
+        let field = match validate_field(value, schema) {
+            Ok(f) => f,
+            Err(e) => {
+                errors.insert(error_key, e);
+                continue;
+            }
+        };
+
+        match existing {
+            Some(i) => {
+                let label = field.label.unwrap_or(out[i].label.clone());
+
+                // changing the value means it has to be verified again
+                if out[i].value != field.value {
+                    out[i].verified = false;
+                }
+
+                out[i].label = label;
+                out[i].value = field.value;
+            }
+            None => out.push(ProfileField {
+                key: key.to_owned(),
+                label: field.label.unwrap_or(key.to_owned()),
+                value: field.value,
+                verified: false,
+            }),
+        }
+    }
+
+    if out.len() > schema.max_fields {
+        errors.insert(
+            String::from("fields"),
+            format!("Cannot have more than {} fields.", schema.max_fields),
+        );
+    }
+
+    out
+}
+
+/// A validated custom field update
+struct FieldInput {
+    label: Option<String>,
+    value: String,
+}
+
+fn validate_field_key(key: &str, schema: &FieldSchema) -> Result<(), String> {
+    if key.is_empty() | (key.chars().count() > MAX_FIELD_KEY_LEN) {
+        return Err(format!(
+            "Key must be between 1 and {MAX_FIELD_KEY_LEN} characters long."
+        ));
+    }
+
+    if !key
+        .chars()
+        .all(|c| c.is_ascii_lowercase() | c.is_ascii_digit() | (c == '_') | (c == '-'))
+    {
+        return Err(String::from(
+            "Key can only contain lowercase letters, numbers, \"_\", and \"-\".",
+        ));
+    }
+
+    if !schema.allowed_keys.is_empty() & !schema.allowed_keys.iter().any(|k| k == key) {
+        return Err(String::from("This field is not allowed."));
+    }
+
+    Ok(())
+}
+
+fn validate_field(value: &Value, schema: &FieldSchema) -> Result<FieldInput, String> {
+    let (label, value) = match value {
+        Value::String(v) => (Option::None, v),
+        Value::Object(o) => {
+            let label = match o.get("label") {
+                None | Some(Value::Null) => Option::None,
+                Some(Value::String(l)) => Option::Some(l.trim().to_string()),
+                Some(_) => return Err(String::from("Label must be a string.")),
+            };
+
+            match o.get("value") {
+                Some(Value::String(v)) => (label, v),
+                _ => return Err(String::from("Value must be a string.")),
+            }
+        }
+        _ => return Err(String::from("Expected a string or an object.")),
+    };
+
+    // label
+    if let Some(ref label) = label {
+        if label.is_empty() | (label.chars().count() > schema.max_label_len) {
+            return Err(format!(
+                "Label must be between 1 and {} characters long.",
+                schema.max_label_len
+            ));
+        }
+
+        if label.chars().any(char::is_control) {
+            return Err(String::from("Label cannot contain control characters."));
+        }
+    }
+
+    // value
+    let value = value.trim().to_string();
+
+    if value.is_empty() | (value.chars().count() > schema.max_value_len) {
+        return Err(format!(
+            "Value must be between 1 and {} characters long.",
+            schema.max_value_len
+        ));
+    }
+
+    if value.chars().any(char::is_control) {
+        return Err(String::from("Value cannot contain control characters."));
+    }
+
+    if is_link(&value) {
+        validate_url(&Value::String(value.clone()))?;
+    }
+
+    Ok(FieldInput { label, value })
+}
+
+/// Get an optional string, treating empty strings as `None`
+fn optional_string(value: &Value) -> Result<Option<String>, String> {
+    match value {
+        Value::Null => Ok(Option::None),
+        Value::String(s) if s.trim().is_empty() => Ok(Option::None),
+        Value::String(s) => Ok(Option::Some(s.trim().to_string())),
+        _ => Err(String::from("Expected a string.")),
+    }
+}
+
+fn validate_nickname(value: &Value) -> Result<Option<String>, String> {
+    let nickname = match optional_string(value)? {
+        Some(n) => n,
+        None => return Ok(Option::None), // falls back to the username
+    };
+
+    if nickname.chars().count() > MAX_NICKNAME_LEN {
+        return Err(format!(
+            "Must be at most {MAX_NICKNAME_LEN} characters long."
+        ));
+    }
+
+    if nickname.chars().any(char::is_control) {
+        return Err(String::from("Cannot contain control characters."));
+    }
+
+    Ok(Option::Some(nickname))
+}
+
+fn validate_about(value: &Value) -> Result<String, String> {
+    let about = match value {
+        Value::String(s) => s.to_owned(),
+        _ => return Err(String::from("Expected a string.")),
+    };
+
+    if about.len() > MAX_ABOUT_LEN {
+        return Err(format!("Must be at most {MAX_ABOUT_LEN} bytes long."));
+    }
+
+    Ok(about)
+}
+
+fn validate_url(value: &Value) -> Result<Option<String>, String> {
+    let url = match optional_string(value)? {
+        Some(u) => u,
+        None => return Ok(Option::None),
+    };
+
+    if url.len() > MAX_URL_LEN {
+        return Err(format!("Must be at most {MAX_URL_LEN} bytes long."));
+    }
+
+    let uri = match url.parse::<Uri>() {
+        Ok(u) => u,
+        Err(_) => return Err(String::from("Must be a valid URL.")),
+    };
+
+    match (uri.scheme_str(), uri.host()) {
+        (Some("http") | Some("https"), Some(_)) => Ok(Option::Some(url)),
+        _ => Err(String::from("Must be an http or https URL.")),
+    }
+}
+
+/// Check if `html` contains a `rel="me"` link (`<a>` or `<link>`) to `profile_url`
+///
+/// # Arguments:
+/// * `html` - the page the link should be on
+/// * `profile_url` - the full URL of the user's profile
+pub fn has_rel_me_link(html: &str, profile_url: &str) -> bool {
+    let target = profile_url.trim_end_matches('/').to_lowercase();
+
+    for tag in html.split('<').skip(1) {
+        let tag = match tag.split_once('>') {
+            Some((t, _)) => t.to_lowercase(),
+            None => continue,
+        };
+
+        match tag.split_whitespace().next() {
+            Some("a") | Some("link") => (),
+            _ => continue,
+        }
+
+        let is_me = get_attribute(&tag, "rel")
+            .map(|rel| rel.split_whitespace().any(|r| r == "me"))
+            .unwrap_or(false);
+
+        let href = get_attribute(&tag, "href").map(|h| h.trim().trim_end_matches('/'));
+
+        if is_me & (href == Option::Some(target.as_str())) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Get the value of an attribute from the inside of an HTML tag
+fn get_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut start = 0;
+
+    while let Some(i) = tag[start..].find(name) {
+        let i = start + i;
+        start = i + name.len();
+
+        // make sure this is the whole attribute name
+        if !tag[..i].ends_with(char::is_whitespace) {
+            continue;
+        }
+
+        let rest = tag[start..].trim_start();
+
+        let rest = match rest.strip_prefix('=') {
+            Some(r) => r.trim_start(),
+            None => continue,
+        };
+
+        return match rest.chars().next() {
+            Some(q @ ('"' | '\'')) => rest[1..].split(q).next(),
+            Some(_) => rest.split(char::is_whitespace).next(),
+            None => Option::None,
+        };
+    }
+
+    Option::None
+}
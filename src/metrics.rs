@@ -0,0 +1,284 @@
+//! # Metrics
+//!
+//! Counters and histograms exported in the Prometheus text format (see `api::health`).
+//!
+//! Requests are counted by [`middleware`] per route pattern (ex: `/api/v1/auth/users/{name:.*}`),
+//! so the amount of series doesn't grow with the amount of users. Everything else
+//! is recorded through [`Metrics`], which is held by [`Database`](crate::db::Database).
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web;
+
+use crate::db::AppData;
+
+/// Upper bounds (in seconds) of every histogram bucket
+pub const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// `(name, help)` of every metric, in the order they're rendered
+const DESCRIPTIONS: [(&str, &str); 7] = [
+    (
+        "shuttle_http_requests_total",
+        "HTTP requests by method, route and status",
+    ),
+    (
+        "shuttle_http_request_duration_seconds",
+        "HTTP request latency by method and route",
+    ),
+    (
+        "shuttle_registrations_total",
+        "Registration attempts by result",
+    ),
+    ("shuttle_logins_total", "Login attempts by result"),
+    (
+        "shuttle_follow_toggles_total",
+        "Follow toggles by action (follow, unfollow, request, cancel)",
+    ),
+    (
+        "shuttle_avatar_fetches_total",
+        "Remote avatar fetches by outcome",
+    ),
+    (
+        "shuttle_db_query_duration_seconds",
+        "Database query latency by query",
+    ),
+];
+
+/// A single histogram series
+#[derive(Clone, Default)]
+struct Histogram {
+    /// Observations in each bucket of [`BUCKETS`] (not cumulative)
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Registry {
+    /// metric name -> rendered labels -> value
+    counters: BTreeMap<&'static str, BTreeMap<String, u64>>,
+    /// metric name -> rendered labels -> histogram
+    histograms: BTreeMap<&'static str, BTreeMap<String, Histogram>>,
+}
+
+/// Metrics registry
+///
+/// Cloning a [`Metrics`] shares the underlying registry, so every worker reports
+/// into (and exports) the same series.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+    value
+        .replace("\\", "\\\\")
+        .replace("\"", "\\\"")
+        .replace("\n", "\\n")
+}
+
+/// Render labels as `key="value",...` (without braces)
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", escape(v)))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Join rendered labels with an extra label
+fn with_label(labels: &str, extra: &str) -> String {
+    if labels.is_empty() {
+        return format!("{{{extra}}}");
+    }
+
+    format!("{{{labels},{extra}}}")
+}
+
+impl Metrics {
+    /// Increment a counter
+    ///
+    /// # Arguments:
+    /// * `name` - the name of the counter
+    /// * `labels` - `(key, value)` pairs
+    pub fn inc(&self, name: &'static str, labels: &[(&str, &str)]) {
+        let mut registry = self.registry.lock().unwrap();
+
+        *registry
+            .counters
+            .entry(name)
+            .or_default()
+            .entry(render_labels(labels))
+            .or_default() += 1;
+    }
+
+    /// Record an observation in a histogram
+    ///
+    /// # Arguments:
+    /// * `name` - the name of the histogram
+    /// * `labels` - `(key, value)` pairs
+    /// * `seconds` - the observed duration
+    pub fn observe(&self, name: &'static str, labels: &[(&str, &str)], seconds: f64) {
+        let mut registry = self.registry.lock().unwrap();
+
+        let histogram = registry
+            .histograms
+            .entry(name)
+            .or_default()
+            .entry(render_labels(labels))
+            .or_default();
+
+        if let Some(i) = BUCKETS.iter().position(|b| seconds <= *b) {
+            histogram.buckets[i] += 1;
+        }
+
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+
+    /// Time a database query until the returned [`QueryTimer`] is dropped
+    ///
+    /// # Arguments:
+    /// * `query` - the name of the query (ex: the [`Database`](crate::db::Database) method)
+    pub fn time_query(&self, query: &'static str) -> QueryTimer {
+        QueryTimer {
+            metrics: self.clone(),
+            query,
+            start: Instant::now(),
+        }
+    }
+
+    /// Render every series in the Prometheus text format
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut output = String::new();
+
+        for (name, help) in DESCRIPTIONS {
+            // counters
+            if let Some(series) = registry.counters.get(name) {
+                output.push_str(&format!("# HELP {name} {help}\n# TYPE {name} counter\n"));
+
+                for (labels, value) in series {
+                    if labels.is_empty() {
+                        output.push_str(&format!("{name} {value}\n"));
+                    } else {
+                        output.push_str(&format!("{name}{{{labels}}} {value}\n"));
+                    }
+                }
+
+                continue;
+            }
+
+            // histograms
+            if let Some(series) = registry.histograms.get(name) {
+                output.push_str(&format!("# HELP {name} {help}\n# TYPE {name} histogram\n"));
+
+                for (labels, histogram) in series {
+                    let mut cumulative: u64 = 0;
+
+                    for (bound, count) in BUCKETS.iter().zip(histogram.buckets.iter()) {
+                        cumulative += count;
+                        output.push_str(&format!(
+                            "{name}_bucket{} {cumulative}\n",
+                            with_label(labels, &format!("le=\"{bound}\""))
+                        ));
+                    }
+
+                    output.push_str(&format!(
+                        "{name}_bucket{} {}\n",
+                        with_label(labels, "le=\"+Inf\""),
+                        histogram.count
+                    ));
+
+                    let labels = if labels.is_empty() {
+                        String::new()
+                    } else {
+                        format!("{{{labels}}}")
+                    };
+
+                    output.push_str(&format!("{name}_sum{labels} {}\n", histogram.sum));
+                    output.push_str(&format!("{name}_count{labels} {}\n", histogram.count));
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Records `shuttle_db_query_duration_seconds` (and logs the query under
+/// `shuttle::db` at debug level) when dropped (see [`Metrics::time_query`])
+pub struct QueryTimer {
+    metrics: Metrics,
+    query: &'static str,
+    start: Instant,
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let seconds = self.start.elapsed().as_secs_f64();
+
+        log::debug!(
+            target: "shuttle::db",
+            query = self.query,
+            duration_ms = crate::logging::duration_ms(self.start);
+            "{}",
+            self.query
+        );
+
+        self.metrics.observe(
+            "shuttle_db_query_duration_seconds",
+            &[("query", self.query)],
+            seconds,
+        );
+    }
+}
+
+/// Count requests and record their latency
+///
+/// Requests which don't match a route are grouped under `unmatched`.
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let data = match req.app_data::<web::Data<AppData>>() {
+        Some(d) => d.clone(),
+        None => return next.call(req).await,
+    };
+
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let res = next.call(req).await;
+
+    // ...
+    let (route, status) = match res {
+        Ok(ref r) => (r.request().match_pattern(), r.status()),
+        Err(ref e) => (Option::None, e.as_response_error().status_code()),
+    };
+
+    let route = route.unwrap_or(String::from("unmatched"));
+
+    data.db.metrics.inc(
+        "shuttle_http_requests_total",
+        &[
+            ("method", &method),
+            ("route", &route),
+            ("status", status.as_str()),
+        ],
+    );
+
+    data.db.metrics.observe(
+        "shuttle_http_request_duration_seconds",
+        &[("method", &method), ("route", &route)],
+        start.elapsed().as_secs_f64(),
+    );
+
+    res
+}
@@ -0,0 +1,227 @@
+//! # Net
+//!
+//! Resolving the hosts of outgoing requests made on behalf of users (avatars,
+//! webhooks), so they can be kept away from private addresses.
+//!
+//! The resolved address should be pinned for the request (`awc`'s
+//! `ClientRequest::address`), otherwise DNS could answer differently the
+//! second time.
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+
+use actix_web::http::Uri;
+use actix_web::web;
+
+/// Net errors
+#[derive(Debug, PartialEq)]
+pub enum NetError {
+    /// The URL is not a valid `http`/`https` URL
+    InvalidUrl,
+    /// The URL's host could not be resolved
+    Unresolvable,
+    /// The URL resolves to an address we won't connect to
+    ForbiddenAddress,
+}
+
+impl std::fmt::Display for NetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use NetError::*;
+        f.write_str(match self {
+            InvalidUrl => "URL is invalid.",
+            Unresolvable => "URL host could not be resolved.",
+            ForbiddenAddress => "URL points to a forbidden address.",
+        })
+    }
+}
+
+pub type Result<T> = std::result::Result<T, NetError>;
+
+/// Check if an address is publicly routable (and so safe to connect to)
+///
+/// # Arguments:
+/// * `ip` - the address to check
+pub fn is_public_address(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            // addresses which embed an ipv4 address
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_v4(&v4);
+            }
+
+            let segments = ip.segments();
+
+            if (segments[0] == 0x64) & (segments[1] == 0xff9b) {
+                // nat64
+                let octets = ip.octets();
+                return is_public_v4(&Ipv4Addr::new(
+                    octets[12], octets[13], octets[14], octets[15],
+                ));
+            }
+
+            !(ip.is_loopback()
+                | ip.is_unspecified()
+                | ip.is_multicast()
+                | (segments[0] == 0) // ipv4-compatible and other reserved addresses
+                | ((segments[0] & 0xfe00) == 0xfc00) // unique local
+                | ((segments[0] & 0xffc0) == 0xfe80) // link-local
+                | ((segments[0] & 0xffc0) == 0xfec0) // site-local
+                | ((segments[0] == 0x2001) & (segments[1] == 0x0db8)) // documentation
+                | (segments[0] == 0x2002)) // 6to4
+        }
+    }
+}
+
+fn is_public_v4(ip: &Ipv4Addr) -> bool {
+    let octets = ip.octets();
+
+    !(ip.is_private()
+        | ip.is_loopback()
+        | ip.is_link_local()
+        | ip.is_broadcast()
+        | ip.is_documentation()
+        | ip.is_unspecified()
+        | ip.is_multicast()
+        | (octets[0] == 0) // "this" network
+        | ((octets[0] == 100) & ((octets[1] & 0xc0) == 64)) // shared address space
+        | ((octets[0] == 192) & (octets[1] == 0) & (octets[2] == 0)) // protocol assignments
+        | ((octets[0] == 198) & ((octets[1] & 0xfe) == 18)) // benchmarking
+        | (octets[0] >= 240)) // reserved
+}
+
+/// Resolve the host of `uri` to every address it points to
+///
+/// # Arguments:
+/// * `uri` - the URL being requested
+async fn lookup(uri: &Uri) -> Result<Vec<SocketAddr>> {
+    match uri.scheme_str() {
+        Some("http") | Some("https") => (),
+        _ => return Err(NetError::InvalidUrl),
+    };
+
+    let host = match uri.host() {
+        Some(h) => h.trim_start_matches("[").trim_end_matches("]").to_string(),
+        None => return Err(NetError::InvalidUrl),
+    };
+
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+
+    match web::block(move || (host.as_str(), port).to_socket_addrs()).await {
+        Ok(Ok(addrs)) => {
+            let addrs: Vec<SocketAddr> = addrs.collect();
+
+            if addrs.is_empty() {
+                return Err(NetError::Unresolvable);
+            }
+
+            Ok(addrs)
+        }
+        _ => Err(NetError::Unresolvable),
+    }
+}
+
+/// Resolve the host of `uri`, returning the first address (no matter where it is)
+///
+/// Only for requests to hosts the server's operators trust, use [`resolve_public`]
+/// for anything else.
+///
+/// # Arguments:
+/// * `uri` - the URL being requested
+pub async fn resolve(uri: &Uri) -> Result<SocketAddr> {
+    Ok(lookup(uri).await?[0])
+}
+
+/// Resolve the host of `uri`, returning the first address if every resolved
+/// address is public
+///
+/// # Arguments:
+/// * `uri` - the URL being requested
+pub async fn resolve_public(uri: &Uri) -> Result<SocketAddr> {
+    let addrs = lookup(uri).await?;
+
+    // every address must be public, otherwise a host could resolve to
+    // both a public and a private address and we might pick the wrong one
+    if addrs.iter().any(|a| !is_public_address(&a.ip())) {
+        return Err(NetError::ForbiddenAddress);
+    }
+
+    Ok(addrs[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public(ip: &str) -> bool {
+        is_public_address(&ip.parse().unwrap())
+    }
+
+    #[test]
+    fn public_addresses_are_allowed() {
+        for ip in [
+            "1.1.1.1",
+            "93.184.216.34",
+            "2606:4700:4700::1111",
+            "::ffff:1.1.1.1",
+            "64:ff9b::101:101",
+        ] {
+            assert!(public(ip), "{ip}");
+        }
+    }
+
+    #[test]
+    fn private_addresses_are_refused() {
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fc00::1",
+            "fe80::1",
+        ] {
+            assert!(!public(ip), "{ip}");
+        }
+    }
+
+    #[test]
+    fn wrapped_private_addresses_are_refused() {
+        for ip in [
+            // v4-mapped
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "::ffff:169.254.169.254",
+            // nat64
+            "64:ff9b::7f00:1",
+            "64:ff9b::a00:1",
+            "64:ff9b::a9fe:a9fe",
+            // v4-compatible
+            "::127.0.0.1",
+            // 6to4
+            "2002:7f00:1::",
+        ] {
+            assert!(!public(ip), "{ip}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn resolve_public_refuses_loopback() {
+        let uri: Uri = "http://127.0.0.1:8080/".parse().unwrap();
+
+        assert_eq!(resolve_public(&uri).await, Err(NetError::ForbiddenAddress));
+        assert_eq!(resolve(&uri).await, Ok("127.0.0.1:8080".parse().unwrap()));
+
+        let uri: Uri = "ftp://1.1.1.1/".parse().unwrap();
+        assert_eq!(resolve_public(&uri).await, Err(NetError::InvalidUrl));
+    }
+}
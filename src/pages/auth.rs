@@ -1,6 +1,8 @@
 use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
 
-use crate::db::{self, AppData, Log, UserMetadata, UserState};
+use crate::db::{self, AppData, UserMetadata, UserState, UserSummary};
+use crate::metadata::ProfileField;
+use crate::pagination::{Page, PageQuery};
 
 use super::base;
 use askama::Template;
@@ -8,12 +10,15 @@ use askama::Template;
 #[derive(Default, PartialEq, serde::Deserialize)]
 pub struct CallbackQueryProps {
     pub callback: String, // redirect here after finish
+    #[serde(default)]
+    pub next: String, // passed on to the callback (see `api::auth::callback_request`)
 }
 
 #[derive(Template)]
 #[template(path = "auth/login.html")]
 struct LoginTemplate {
     callback: String,
+    next: String,
     // required fields (super::base)
     auth_state: bool,
     site_name: String,
@@ -24,6 +29,7 @@ struct LoginTemplate {
 #[template(path = "auth/register.html")]
 struct RegisterTemplate {
     callback: String,
+    next: String,
     invite_code_required: bool,
     // required fields (super::base)
     auth_state: bool,
@@ -35,6 +41,7 @@ struct RegisterTemplate {
 #[template(path = "auth/login_secondary_token.html")]
 struct LoginSecondaryTokenTemplate {
     callback: String,
+    next: String,
     // required fields (super::base)
     auth_state: bool,
     site_name: String,
@@ -47,12 +54,37 @@ pub struct QueryProps {
     pub offset: Option<i32>,
 }
 
+#[derive(Template)]
+#[template(path = "auth/user_profile.html")]
+struct ProfileTemplate {
+    user: UserState<UserMetadata>,
+    meta: UserMetadata,
+    user_nick: String,
+    about: String,
+    fields: Vec<ProfileField>,
+    followers_count: usize,
+    following_count: usize,
+    is_following: bool,
+    follows_you: bool,
+    is_blocking: bool,
+    is_requested: bool,
+    is_private: bool,
+    can_edit: bool,
+    edit_mode: bool,
+    deducktive: String,
+    // required fields (super::base)
+    auth_state: bool,
+    site_name: String,
+    body_embed: String,
+}
+
 #[derive(Template)]
 #[template(path = "auth/followers.html")]
 struct FollowersTemplate {
-    followers: Vec<Log>,
+    followers: Vec<UserSummary>,
     user: UserState<UserMetadata>,
-    offset: i32,
+    next: String, // cursor for the next page (empty if this is the last page)
+    prev: String, // cursor for the previous page (empty if this is the first page)
     // required fields (super::base)
     auth_state: bool,
     site_name: String,
@@ -62,25 +94,22 @@ struct FollowersTemplate {
 #[derive(Template)]
 #[template(path = "auth/following.html")]
 struct FollowingTemplate {
-    following: Vec<Log>,
+    following: Vec<UserSummary>,
     user: UserState<UserMetadata>,
-    offset: i32,
+    next: String, // cursor for the next page (empty if this is the last page)
+    prev: String, // cursor for the previous page (empty if this is the first page)
     // required fields (super::base)
     auth_state: bool,
     site_name: String,
     body_embed: String,
 }
 
-#[derive(Default, PartialEq, serde::Deserialize)]
-pub struct FollowersQueryProps {
-    pub offset: Option<i32>,
-}
-
 #[derive(Template)]
 #[template(path = "auth/user_settings.html")]
 struct SettingsTemplate {
     profile: UserState<UserMetadata>,
     metadata: String,
+    is_private: bool,
     // required fields (super::base)
     auth_state: bool,
     site_name: String,
@@ -89,21 +118,21 @@ struct SettingsTemplate {
 
 #[get("/flow/auth/register")]
 /// Available at "/flow/auth/register"
-/// Still renders even if `REGISTRATION_DISABLED` is present
+/// Still renders even if registration is disabled
 pub async fn register_request(
     req: HttpRequest,
     info: web::Query<CallbackQueryProps>,
+    data: web::Data<AppData>,
 ) -> impl Responder {
-    let invite_codes = crate::config::get_var("INVITE_CODES");
-
     // ...
-    let base = base::get_base_values(req.cookie("__Secure-Token").is_some());
-    return HttpResponse::Ok()
+    let base = base::get_base_values(&data.config, req.cookie("__Secure-Token").is_some());
+    HttpResponse::Ok()
         .append_header(("Content-Type", "text/html"))
         .body(
             RegisterTemplate {
                 callback: info.callback.clone(),
-                invite_code_required: invite_codes.is_some(),
+                next: info.next.clone(),
+                invite_code_required: !data.config.registration.invite_codes.is_empty(),
                 // required fields
                 auth_state: base.auth_state,
                 site_name: base.site_name,
@@ -111,7 +140,7 @@ pub async fn register_request(
             }
             .render()
             .unwrap(),
-        );
+        )
 }
 
 #[get("/flow/auth/login")]
@@ -119,14 +148,16 @@ pub async fn register_request(
 pub async fn login_request(
     req: HttpRequest,
     info: web::Query<CallbackQueryProps>,
+    data: web::Data<AppData>,
 ) -> impl Responder {
     // ...
-    let base = base::get_base_values(req.cookie("__Secure-Token").is_some());
-    return HttpResponse::Ok()
+    let base = base::get_base_values(&data.config, req.cookie("__Secure-Token").is_some());
+    HttpResponse::Ok()
         .append_header(("Content-Type", "text/html"))
         .body(
             LoginTemplate {
                 callback: info.callback.clone(),
+                next: info.next.clone(),
                 // required fields
                 auth_state: base.auth_state,
                 site_name: base.site_name,
@@ -134,7 +165,7 @@ pub async fn login_request(
             }
             .render()
             .unwrap(),
-        );
+        )
 }
 
 #[get("/flow/auth/login-st")]
@@ -142,14 +173,16 @@ pub async fn login_request(
 pub async fn login_secondary_token_request(
     req: HttpRequest,
     info: web::Query<CallbackQueryProps>,
+    data: web::Data<AppData>,
 ) -> impl Responder {
     // ...
-    let base = base::get_base_values(req.cookie("__Secure-Token").is_some());
-    return HttpResponse::Ok()
+    let base = base::get_base_values(&data.config, req.cookie("__Secure-Token").is_some());
+    HttpResponse::Ok()
         .append_header(("Content-Type", "text/html"))
         .body(
             LoginSecondaryTokenTemplate {
                 callback: info.callback.clone(),
+                next: info.next.clone(),
                 // required fields
                 auth_state: base.auth_state,
                 site_name: base.site_name,
@@ -157,7 +190,95 @@ pub async fn login_secondary_token_request(
             }
             .render()
             .unwrap(),
-        );
+        )
+}
+
+#[get("/{username}")]
+/// Available at "/{username}"
+pub async fn profile_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<QueryProps>,
+) -> impl Responder {
+    // get user
+    let username: String = req.match_info().get("username").unwrap().to_string();
+
+    let user = data.db.get_user_by_username(username.clone()).await;
+
+    if user.is_err() {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("404: Not Found");
+    }
+
+    let unwrap = user.ok().unwrap();
+
+    // verify auth status
+    let (set_cookie, _, token_user) = base::check_auth_status(req.clone(), data.clone()).await;
+
+    // users that have been blocked by the profile can't view it
+    let viewer = match token_user {
+        Some(Ok(ref u)) => Option::Some(u.user.username.clone()),
+        _ => Option::None,
+    };
+
+    if data.db.is_hidden_from(username.clone(), viewer).await {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("404: Not Found");
+    }
+
+    let (can_edit, relationship) = match token_user {
+        Some(Ok(ref token_user)) => (
+            (token_user.user.username == unwrap.user.username)
+                | (token_user
+                    .level
+                    .permissions
+                    .contains(&String::from("ManageUsers"))),
+            data.db
+                .get_relationship(token_user.user.username.clone(), username.clone())
+                .await,
+        ),
+        _ => (false, db::Relationship::default()),
+    };
+
+    // ...
+    let meta = unwrap.user.metadata.clone();
+    let base = base::get_base_values(&data.config, token_user.is_some());
+
+    let props = ProfileTemplate {
+        user: unwrap.user.clone(),
+        user_nick: meta.nickname.clone().unwrap_or(username.clone()),
+        about: crate::markup::render(&meta.about),
+        meta,
+        fields: data.db.get_profile_fields(username.clone()).await,
+        followers_count: data
+            .db
+            .get_user_follow_count(username.clone())
+            .await
+            .payload,
+        following_count: data
+            .db
+            .get_user_following_count(username.clone())
+            .await
+            .payload,
+        is_following: relationship.following,
+        follows_you: relationship.followed_by,
+        is_blocking: relationship.blocking,
+        is_requested: relationship.requested,
+        is_private: data.db.get_user_settings(username).await.private,
+        can_edit,
+        edit_mode: can_edit & info.edit.unwrap_or(false),
+        deducktive: base.deducktive,
+        auth_state: base.auth_state,
+        site_name: base.site_name,
+        body_embed: base.body_embed,
+    };
+
+    HttpResponse::Ok()
+        .append_header(("Set-Cookie", set_cookie))
+        .append_header(("Content-Type", "text/html"))
+        .body(props.render().unwrap())
 }
 
 #[get("/{username:.*}/followers")]
@@ -165,15 +286,19 @@ pub async fn login_secondary_token_request(
 pub async fn followers_request(
     req: HttpRequest,
     data: web::Data<AppData>,
-    info: web::Query<FollowersQueryProps>,
+    info: web::Query<PageQuery>,
 ) -> impl Responder {
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
+
     // get user
     let username: String = req.match_info().get("username").unwrap().to_string();
     let username_c = username.clone();
 
     let user = data.db.get_user_by_username(username).await;
 
-    if user.is_ok() == false {
+    if user.is_err() {
         return HttpResponse::NotFound()
             .append_header(("Content-Type", "text/plain"))
             .body("404: Not Found");
@@ -182,32 +307,58 @@ pub async fn followers_request(
     let unwrap = user.ok().unwrap();
 
     // verify auth status
-    let (set_cookie, _, _) = base::check_auth_status(req.clone(), data.clone()).await;
+    let (set_cookie, _, token_user) = base::check_auth_status(req.clone(), data.clone()).await;
+    let (viewer, is_staff) = match token_user {
+        Some(Ok(u)) => (
+            Option::Some(u.user.username),
+            u.level.permissions.contains(&String::from("ManageUsers")),
+        ),
+        _ => (Option::None, false),
+    };
 
-    // ...
-    let followers_res: db::DefaultReturn<Option<Vec<db::Log>>> = data
+    if data
         .db
-        .get_user_followers(username_c.clone(), info.offset)
-        .await;
+        .is_hidden_from(username_c.clone(), viewer.clone())
+        .await
+    {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("404: Not Found");
+    }
 
-    let base = base::get_base_values(req.cookie("__Secure-Token").is_some());
+    if !is_staff
+        && !data
+            .db
+            .can_view_follows(username_c.clone(), viewer.clone())
+            .await
+    {
+        return HttpResponse::Forbidden()
+            .append_header(("Content-Type", "text/plain"))
+            .body("This account is private.");
+    }
+
+    // ...
+    let followers_res: db::DefaultReturn<Option<Page<db::Log>>> =
+        data.db.get_user_followers(username_c.clone(), &info).await;
+
+    let page = followers_res.payload.unwrap_or_default();
+    let followers = data.db.summarize_follows(page.items, true, viewer).await;
+
+    let base = base::get_base_values(&data.config, req.cookie("__Secure-Token").is_some());
     let props = FollowersTemplate {
         user: unwrap.clone().user,
-        followers: followers_res.payload.unwrap(),
-        offset: if info.offset.is_some() {
-            info.offset.unwrap()
-        } else {
-            0
-        },
+        followers,
+        next: page.next.unwrap_or_default(),
+        prev: page.prev.unwrap_or_default(),
         auth_state: base.auth_state,
         site_name: base.site_name,
         body_embed: base.body_embed,
     };
 
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Set-Cookie", set_cookie))
         .append_header(("Content-Type", "text/html"))
-        .body(props.render().unwrap());
+        .body(props.render().unwrap())
 }
 
 #[get("/{username:.*}/following")]
@@ -215,15 +366,19 @@ pub async fn followers_request(
 pub async fn following_request(
     req: HttpRequest,
     data: web::Data<AppData>,
-    info: web::Query<FollowersQueryProps>,
+    info: web::Query<PageQuery>,
 ) -> impl Responder {
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
+
     // get user
     let username: String = req.match_info().get("username").unwrap().to_string();
     let username_c = username.clone();
 
     let user = data.db.get_user_by_username(username).await;
 
-    if user.is_ok() == false {
+    if user.is_err() {
         return HttpResponse::NotFound()
             .append_header(("Content-Type", "text/plain"))
             .body("404: Not Found");
@@ -232,32 +387,58 @@ pub async fn following_request(
     let unwrap = user.ok().unwrap();
 
     // verify auth status
-    let (set_cookie, _, _) = base::check_auth_status(req.clone(), data.clone()).await;
+    let (set_cookie, _, token_user) = base::check_auth_status(req.clone(), data.clone()).await;
+    let (viewer, is_staff) = match token_user {
+        Some(Ok(u)) => (
+            Option::Some(u.user.username),
+            u.level.permissions.contains(&String::from("ManageUsers")),
+        ),
+        _ => (Option::None, false),
+    };
 
-    // ...
-    let following_res: db::DefaultReturn<Option<Vec<db::Log>>> = data
+    if data
         .db
-        .get_user_following(username_c.clone(), info.offset)
-        .await;
+        .is_hidden_from(username_c.clone(), viewer.clone())
+        .await
+    {
+        return HttpResponse::NotFound()
+            .append_header(("Content-Type", "text/plain"))
+            .body("404: Not Found");
+    }
+
+    if !is_staff
+        && !data
+            .db
+            .can_view_follows(username_c.clone(), viewer.clone())
+            .await
+    {
+        return HttpResponse::Forbidden()
+            .append_header(("Content-Type", "text/plain"))
+            .body("This account is private.");
+    }
+
+    // ...
+    let following_res: db::DefaultReturn<Option<Page<db::Log>>> =
+        data.db.get_user_following(username_c.clone(), &info).await;
+
+    let page = following_res.payload.unwrap_or_default();
+    let following = data.db.summarize_follows(page.items, false, viewer).await;
 
-    let base = base::get_base_values(req.cookie("__Secure-Token").is_some());
+    let base = base::get_base_values(&data.config, req.cookie("__Secure-Token").is_some());
     let props = FollowingTemplate {
         user: unwrap.clone().user,
-        following: following_res.payload.unwrap(),
-        offset: if info.offset.is_some() {
-            info.offset.unwrap()
-        } else {
-            0
-        },
+        following,
+        next: page.next.unwrap_or_default(),
+        prev: page.prev.unwrap_or_default(),
         auth_state: base.auth_state,
         site_name: base.site_name,
         body_embed: base.body_embed,
     };
 
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Set-Cookie", set_cookie))
         .append_header(("Content-Type", "text/html"))
-        .body(props.render().unwrap());
+        .body(props.render().unwrap())
 }
 
 #[get("/{name:.*}/settings")]
@@ -268,7 +449,7 @@ pub async fn user_settings_request(
 ) -> impl Responder {
     // get user
     let name: String = req.match_info().get("name").unwrap().to_string();
-    let profile = match data.db.get_user_by_username(name).await {
+    let profile = match data.db.get_user_by_username(name.clone()).await {
         Ok(p) => p,
         Err(e) => {
             return HttpResponse::NotFound().body(e.to_string());
@@ -291,26 +472,39 @@ pub async fn user_settings_request(
             .permissions
             .contains(&String::from("ManageUsers")));
 
-    if can_view == false {
+    if !can_view {
         return HttpResponse::NotFound()
             .append_header(("Content-Type", "text/plain"))
             .body("You do not have permission to manage this user's contents.");
     }
 
     // ...
-    let base = base::get_base_values(token_cookie.is_some());
+    let base = base::get_base_values(&data.config, token_cookie.is_some());
+
+    // the secondary token is only shown as a refresh button, never send its hash
+    let mut metadata = profile.user.metadata.clone();
+    metadata.secondary_token = Option::None;
+
+    // custom fields are edited as "fields.KEY"
+    let mut metadata = serde_json::to_value(&metadata).unwrap();
+
+    for field in data.db.get_profile_fields(name.clone()).await {
+        metadata[format!("fields.{}", field.key)] = serde_json::Value::String(field.value);
+    }
+
     let props = SettingsTemplate {
         profile: profile.clone().user,
-        metadata: serde_json::to_string(&profile.user.metadata)
+        metadata: serde_json::to_string(&metadata)
             .unwrap()
             .replace("/", "\\/"),
+        is_private: data.db.get_user_settings(name).await.private,
         auth_state: base.auth_state,
         site_name: base.site_name,
         body_embed: base.body_embed,
     };
 
-    return HttpResponse::Ok()
+    HttpResponse::Ok()
         .append_header(("Set-Cookie", set_cookie))
         .append_header(("Content-Type", "text/html"))
-        .body(props.render().unwrap());
+        .body(props.render().unwrap())
 }
@@ -1,5 +1,6 @@
 use actix_web::{web::Data, HttpRequest};
 
+use crate::config::Config;
 use crate::db::AppData;
 use dorsal::db::special::auth_db::{FullUser, UserMetadata, Result};
 
@@ -10,18 +11,34 @@ pub struct BaseTemplate {
     pub body_embed: String,
 }
 
-pub fn get_base_values(token_cookie: bool) -> BaseTemplate {
-    let body_embed = match std::env::var("BODY_EMBED") {
-        Ok(b) => b,
-        Err(_) => String::new(),
-    };
-
+pub fn get_base_values(config: &Config, token_cookie: bool) -> BaseTemplate {
     // return
     BaseTemplate {
         auth_state: token_cookie,
-        deducktive: std::env::var("DEDUCKTIVE_ROOT").unwrap_or(String::new()),
-        site_name: std::env::var("SITE_NAME").unwrap_or("Shuttle".to_string()),
-        body_embed,
+        deducktive: config.deducktive_root.clone(),
+        site_name: config.site_name.clone(),
+        body_embed: config.body_embed.clone(),
+    }
+}
+
+/// Build the `Set-Cookie` value for a session token (`None` clears the cookie)
+///
+/// The cookie is host-only, unless `forward_auth.cookie_domain` is set.
+pub fn token_cookie(config: &Config, token: Option<&str>) -> String {
+    let scope = if config.forward_auth.cookie_domain.is_empty() {
+        String::from("HostOnly=true")
+    } else {
+        format!("Domain={}", config.forward_auth.cookie_domain)
+    };
+
+    match token {
+        Some(token) => format!(
+            "__Secure-Token={token}; SameSite=Lax; Secure; Path=/; {scope}; HttpOnly=true; Max-Age={}",
+            60 * 60 * 24 * 365
+        ),
+        None => format!(
+            "__Secure-Token=refresh; SameSite=Strict; Secure; Path=/; {scope}; HttpOnly=true; Max-Age=0"
+        ),
     }
 }
 
@@ -35,27 +52,32 @@ pub async fn check_auth_status(
 ) {
     // verify auth status
     let token_cookie = req.cookie("__Secure-Token");
-    let mut set_cookie: &str = "";
+    let mut set_cookie = String::new();
 
-    let mut token_user: Option<Result<FullUser<UserMetadata>>> = if token_cookie.is_some() {
-        Option::Some(
+    let mut token_user: Option<Result<FullUser<UserMetadata>>> = match token_cookie {
+        Some(ref c) => Option::Some(
             data.db
                 .auth
-                .get_user_by_unhashed(token_cookie.as_ref().unwrap().value().to_string()) // if the user is returned, that means the ID is valid
+                .get_user_by_unhashed(c.value().to_string()) // if the user is returned, that means the ID is valid
                 .await,
-        )
-    } else {
-        Option::None
+        ),
+        None => Option::None,
     };
 
     if token_user.is_some() {
         // make sure user exists, refresh token if not
-        if token_user.as_ref().unwrap().is_ok() == false {
-            set_cookie = "__Secure-Token=refresh; SameSite=Strict; Secure; Path=/; HostOnly=true; HttpOnly=true; Max-Age=0";
+        if token_user.as_ref().unwrap().is_err() {
+            log::debug!(
+                target: "shuttle::auth",
+                token = crate::logging::fingerprint(token_cookie.as_ref().unwrap().value()).as_str();
+                "invalid session token, clearing cookie"
+            );
+
+            set_cookie = self::token_cookie(&data.config, Option::None);
             token_user = Option::None;
         }
     }
 
     // return
-    (set_cookie.to_string(), token_cookie, token_user)
+    (set_cookie, token_cookie, token_user)
 }
@@ -18,29 +18,28 @@ struct HomeTemplate {
 pub async fn home_request(req: HttpRequest, data: web::Data<db::AppData>) -> impl Responder {
     // verify auth status
     let token_cookie = req.cookie("__Secure-Token");
-    let mut set_cookie: &str = "";
+    let mut set_cookie = String::new();
 
-    let mut token_user = if token_cookie.is_some() {
-        Option::Some(
+    let mut token_user = match token_cookie {
+        Some(ref c) => Option::Some(
             data.db
-                .get_user_by_unhashed(token_cookie.as_ref().unwrap().value().to_string()) // if the user is returned, that means the ID is valid
+                .get_user_by_unhashed(c.value().to_string()) // if the user is returned, that means the ID is valid
                 .await,
-        )
-    } else {
-        Option::None
+        ),
+        None => Option::None,
     };
 
     if token_user.is_some() {
         // make sure user exists, refresh token if not
-        if token_user.as_ref().unwrap().is_ok() == false {
-            set_cookie = "__Secure-Token=refresh; SameSite=Strict; Secure; Path=/; HostOnly=true; HttpOnly=true; Max-Age=0";
+        if token_user.as_ref().unwrap().is_err() {
+            set_cookie = base::token_cookie(&data.config, Option::None);
             token_user = Option::None;
         }
     }
 
     // ...
-    let base = base::get_base_values(token_user.is_some());
-    return HttpResponse::Ok()
+    let base = base::get_base_values(&data.config, token_user.is_some());
+    HttpResponse::Ok()
         .append_header(("Set-Cookie", set_cookie))
         .append_header(("Content-Type", "text/html"))
         .body(
@@ -52,5 +51,5 @@ pub async fn home_request(req: HttpRequest, data: web::Data<db::AppData>) -> imp
             }
             .render()
             .unwrap(),
-        );
+        )
 }
@@ -1,3 +1,5 @@
 pub mod auth;
 pub mod base;
 pub mod home;
+pub mod notifications;
+pub mod setup;
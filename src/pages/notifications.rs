@@ -0,0 +1,63 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+
+use crate::db::{AppData, Notification};
+use crate::pagination::PageQuery;
+
+use super::base;
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "notifications.html")]
+struct NotificationsTemplate {
+    notifications: Vec<Notification>,
+    next: String, // cursor for the next page (empty if this is the last page)
+    prev: String, // cursor for the previous page (empty if this is the first page)
+    // required fields (super::base)
+    auth_state: bool,
+    site_name: String,
+    body_embed: String,
+}
+
+#[get("/notifications")]
+/// Available at "/notifications"
+pub async fn notifications_request(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    info: web::Query<PageQuery>,
+) -> impl Responder {
+    let info = info
+        .into_inner()
+        .capped(data.config.pagination.max_page_size);
+
+    // verify auth status
+    let (set_cookie, _, token_user) = base::check_auth_status(req.clone(), data.clone()).await;
+
+    if token_user.is_none() {
+        return HttpResponse::NotAcceptable().body("An account is required to do this");
+    }
+
+    let user = token_user.unwrap().ok().unwrap();
+
+    // ...
+    let page = data
+        .db
+        .get_user_notifications(user.user.username, &info)
+        .await
+        .payload
+        .unwrap_or_default();
+
+    let base = base::get_base_values(&data.config, true);
+    let props = NotificationsTemplate {
+        notifications: page.items,
+        next: page.next.unwrap_or_default(),
+        prev: page.prev.unwrap_or_default(),
+        auth_state: base.auth_state,
+        site_name: base.site_name,
+        body_embed: base.body_embed,
+    };
+
+    HttpResponse::Ok()
+        .append_header(("Set-Cookie", set_cookie))
+        .append_header(("Content-Type", "text/html"))
+        .body(props.render().unwrap())
+}
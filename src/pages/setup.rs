@@ -0,0 +1,36 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+
+use crate::db::AppData;
+
+use super::base;
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "setup.html")]
+struct SetupTemplate {
+    pending: bool,
+    // required fields (super::base)
+    auth_state: bool,
+    site_name: String,
+    body_embed: String,
+}
+
+#[get("/flow/setup")]
+/// Available at "/flow/setup"
+pub async fn setup_request(req: HttpRequest, data: web::Data<AppData>) -> impl Responder {
+    // ...
+    let base = base::get_base_values(&data.config, req.cookie("__Secure-Token").is_some());
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "text/html"))
+        .body(
+            SetupTemplate {
+                pending: data.setup.is_pending(),
+                // required fields
+                auth_state: base.auth_state,
+                site_name: base.site_name,
+                body_embed: base.body_embed,
+            }
+            .render()
+            .unwrap(),
+        )
+}
@@ -0,0 +1,315 @@
+//! # Pagination
+//!
+//! Cursor-based pagination for list endpoints.
+//!
+//! Lists are ordered newest first by `(timestamp, id)`, and a cursor points at
+//! a single row in that order. Pages are fetched with `after` (older rows) or
+//! `before` (newer rows), so rows being added or removed never cause other rows
+//! to be skipped or repeated.
+use actix_web::{HttpRequest, HttpResponse};
+use hex_fmt::HexFmt;
+use serde::{Deserialize, Serialize};
+
+use crate::db::DefaultReturn;
+
+/// The page size used when no limit is given
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// A position in a list (the `timestamp` and `id` of a row)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    pub timestamp: u128,
+    pub id: String,
+}
+
+impl Cursor {
+    /// Encode this cursor as an opaque string
+    pub fn encode(&self) -> String {
+        format!("{}", HexFmt(format!("{}:{}", self.timestamp, self.id)))
+    }
+
+    /// Decode a cursor created by [`Cursor::encode`]
+    ///
+    /// # Arguments:
+    /// * `input` - the encoded cursor
+    pub fn decode(input: &str) -> Option<Cursor> {
+        // from_str_radix would also accept signs ("+f")
+        if !input.len().is_multiple_of(2) | !input.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Option::None;
+        }
+
+        let bytes = (0..input.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&input[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+
+        let decoded = String::from_utf8(bytes).ok()?;
+        let (timestamp, id) = decoded.split_once(":")?;
+
+        Option::Some(Cursor {
+            timestamp: timestamp.parse::<u128>().ok()?,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// Pagination query parameters (`?after=...&limit=...`)
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct PageQuery {
+    /// Get rows older than this cursor
+    pub after: Option<String>,
+    /// Get rows newer than this cursor
+    pub before: Option<String>,
+    /// The amount of rows to get (see [`PageQuery::capped`])
+    pub limit: Option<usize>,
+}
+
+/// The direction a page is fetched in
+#[derive(Clone, Debug, PartialEq)]
+pub enum Direction {
+    /// The first page (newest rows)
+    First,
+    /// Rows older than the cursor
+    After(Cursor),
+    /// Rows newer than the cursor
+    Before(Cursor),
+}
+
+impl PageQuery {
+    /// Get the page size for this query
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1)
+    }
+
+    /// Cap the page size of this query (list handlers cap every query they
+    /// receive at `pagination.max_page_size`)
+    ///
+    /// # Arguments:
+    /// * `max` - the largest page size allowed
+    pub fn capped(mut self, max: usize) -> PageQuery {
+        self.limit = Option::Some(self.limit().min(max.max(1)));
+        self
+    }
+
+    /// Get the direction of this query (invalid cursors start from the first page)
+    pub fn direction(&self) -> Direction {
+        if let Some(cursor) = self.after.as_ref().and_then(|c| Cursor::decode(c)) {
+            return Direction::After(cursor);
+        }
+
+        if let Some(cursor) = self.before.as_ref().and_then(|c| Cursor::decode(c)) {
+            return Direction::Before(cursor);
+        }
+
+        Direction::First
+    }
+}
+
+/// A single page of a list
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the next (older) page
+    pub next: Option<String>,
+    /// Cursor for the previous (newer) page
+    pub prev: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from rows fetched for `query`
+    ///
+    /// Rows must be fetched in the order of the query's direction (newest first,
+    /// or oldest first when fetching `before` a cursor), and one more row than
+    /// [`PageQuery::limit`] should be fetched to know if there are more rows.
+    ///
+    /// # Arguments:
+    /// * `rows` - the fetched rows
+    /// * `query` - the [`PageQuery`] the rows were fetched for
+    /// * `cursor` - get the [`Cursor`] of a row
+    pub fn from_rows(
+        mut rows: Vec<T>,
+        query: &PageQuery,
+        cursor: impl Fn(&T) -> Cursor,
+    ) -> Page<T> {
+        let limit = query.limit();
+        let direction = query.direction();
+
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+
+        if let Direction::Before(_) = direction {
+            // fetched oldest first
+            rows.reverse();
+        }
+
+        let first = rows.first().map(|r| cursor(r).encode());
+        let last = rows.last().map(|r| cursor(r).encode());
+
+        let (next, prev) = match direction {
+            Direction::First => (if has_more { last } else { Option::None }, Option::None),
+            Direction::After(_) => (if has_more { last } else { Option::None }, first),
+            Direction::Before(_) => (last, if has_more { first } else { Option::None }),
+        };
+
+        Page {
+            items: rows,
+            next,
+            prev,
+        }
+    }
+
+    /// Convert the items in this page
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next: self.next,
+            prev: self.prev,
+        }
+    }
+
+    /// Get the value of a `Link` header for this page
+    ///
+    /// # Arguments:
+    /// * `path` - the path of the list endpoint
+    /// * `query` - the [`PageQuery`] this page was fetched for
+    pub fn link_header(&self, path: &str, query: &PageQuery) -> Option<String> {
+        let limit = query.limit();
+        let mut links: Vec<String> = Vec::new();
+
+        if let Some(ref next) = self.next {
+            links.push(format!("<{path}?after={next}&limit={limit}>; rel=\"next\""));
+        }
+
+        if let Some(ref prev) = self.prev {
+            links.push(format!(
+                "<{path}?before={prev}&limit={limit}>; rel=\"prev\""
+            ));
+        }
+
+        if links.is_empty() {
+            return Option::None;
+        }
+
+        Option::Some(links.join(", "))
+    }
+}
+
+/// Create a JSON response for a page (with a `Link` header)
+///
+/// # Arguments:
+/// * `req` - the request for the list
+/// * `query` - the [`PageQuery`] the page was fetched for
+/// * `res` - the page
+pub fn page_response<T: Serialize>(
+    req: &HttpRequest,
+    query: &PageQuery,
+    res: DefaultReturn<Option<Page<T>>>,
+) -> HttpResponse {
+    let mut builder = HttpResponse::Ok();
+    builder.append_header(("Content-Type", "application/json"));
+
+    if let Some(link) = res
+        .payload
+        .as_ref()
+        .and_then(|p| p.link_header(req.path(), query))
+    {
+        builder.append_header(("Link", link));
+    }
+
+    builder.body(serde_json::to_string::<DefaultReturn<Option<Page<T>>>>(&res).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(timestamp: &u128) -> Cursor {
+        Cursor {
+            timestamp: *timestamp,
+            id: format!("row{timestamp}"),
+        }
+    }
+
+    fn encoded(timestamp: u128) -> Option<String> {
+        Option::Some(cursor(&timestamp).encode())
+    }
+
+    fn query(after: Option<u128>, before: Option<u128>) -> PageQuery {
+        PageQuery {
+            after: after.and_then(encoded),
+            before: before.and_then(encoded),
+            limit: Option::Some(2),
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let c = Cursor {
+            timestamp: 1700000000000,
+            id: String::from("a:b"),
+        };
+
+        assert_eq!(Cursor::decode(&c.encode()), Option::Some(c));
+    }
+
+    #[test]
+    fn cursor_rejects_malformed() {
+        for input in [
+            "",
+            "313a6",            // odd length
+            "zz3a61",           // not hex
+            "313a+1",           // signs
+            "3161",             // no separator
+            "613a61",           // timestamp isn't a number
+            "ff3a61",           // not utf-8
+            "\u{e9}\u{e9}3a61", // not ascii
+        ] {
+            assert_eq!(Cursor::decode(input), Option::None, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn first_page() {
+        let page = Page::from_rows(vec![5, 4, 3], &query(None, None), cursor);
+        assert_eq!(page.items, vec![5, 4]);
+        assert_eq!(page.next, encoded(4));
+        assert_eq!(page.prev, Option::None);
+
+        let page = Page::from_rows(vec![5, 4], &query(None, None), cursor);
+        assert_eq!(page.next, Option::None);
+        assert_eq!(page.prev, Option::None);
+    }
+
+    #[test]
+    fn page_after() {
+        let page = Page::from_rows(vec![4, 3, 2], &query(Some(5), None), cursor);
+        assert_eq!(page.items, vec![4, 3]);
+        assert_eq!(page.next, encoded(3));
+        assert_eq!(page.prev, encoded(4));
+
+        // last page
+        let page = Page::from_rows(vec![1], &query(Some(2), None), cursor);
+        assert_eq!(page.next, Option::None);
+        assert_eq!(page.prev, encoded(1));
+
+        // past the end
+        let page = Page::from_rows(Vec::new(), &query(Some(1), None), cursor);
+        assert_eq!(page.next, Option::None);
+        assert_eq!(page.prev, Option::None);
+    }
+
+    #[test]
+    fn page_before() {
+        // fetched oldest first
+        let page = Page::from_rows(vec![3, 4, 5], &query(None, Some(2)), cursor);
+        assert_eq!(page.items, vec![4, 3]);
+        assert_eq!(page.next, encoded(3));
+        assert_eq!(page.prev, encoded(4));
+
+        // newest page
+        let page = Page::from_rows(vec![4, 5], &query(None, Some(3)), cursor);
+        assert_eq!(page.items, vec![5, 4]);
+        assert_eq!(page.next, encoded(4));
+        assert_eq!(page.prev, Option::None);
+    }
+}
@@ -0,0 +1,486 @@
+//! # Rate limiting
+//!
+//! Fixed-window rate limiting, keyed by any string (usually `ACTION:IP`).
+//!
+//! Windows are stored in memory by default, or in redis when `ratelimit.store`
+//! is set to `redis` (so multiple instances can share limits).
+//!
+//! Every `/api/` route is limited by [`middleware`] using named budgets, which can
+//! be changed in `[ratelimit.budgets]` (see [`RateLimitConfig`]).
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::future::BoxFuture;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::AsyncRedis;
+use crate::config::RateLimitConfig;
+use crate::db::{AppData, DefaultReturn};
+
+/// A single fixed window: `(window start, hits in window, window length)`
+type Window = (u128, u32, u128);
+
+/// The amount of hits allowed within a window
+///
+/// Written as `HITS/SECONDS` (ex: `5/3600`) in the config.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Budget {
+    /// Hits allowed in a single window
+    pub limit: u32,
+    /// Length of a window (in milliseconds)
+    pub window: u128,
+}
+
+impl Budget {
+    /// Create a new [`Budget`]
+    ///
+    /// # Arguments:
+    /// * `limit` - hits allowed in a single window
+    /// * `seconds` - length of a window (in seconds)
+    pub const fn new(limit: u32, seconds: u128) -> Budget {
+        Budget {
+            limit,
+            window: seconds * 1000,
+        }
+    }
+
+    /// Parse a [`Budget`] in the format `HITS/SECONDS` (ex: `5/3600`)
+    pub fn parse(input: &str) -> Option<Budget> {
+        let (limit, seconds) = input.split_once("/")?;
+
+        match (limit.trim().parse::<u32>(), seconds.trim().parse::<u128>()) {
+            (Ok(limit), Ok(seconds)) => Option::Some(Budget::new(limit, seconds)),
+            _ => Option::None,
+        }
+    }
+}
+
+impl TryFrom<String> for Budget {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Budget::parse(&value).ok_or(format!("invalid budget {value:?}, expected HITS/SECONDS"))
+    }
+}
+
+impl From<Budget> for String {
+    fn from(value: Budget) -> Self {
+        format!("{}/{}", value.limit, value.window / 1000)
+    }
+}
+
+/// The state of a key after a hit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitState {
+    /// If the hit was within the budget
+    pub allowed: bool,
+    /// Hits allowed in a single window
+    pub limit: u32,
+    /// Hits left in the current window
+    pub remaining: u32,
+    /// When the current window resets (in milliseconds since the epoch)
+    pub reset: u128,
+}
+
+/// Storage for rate limit windows
+pub trait RateLimitStore: Send + Sync {
+    /// Record a hit on `key`, returning the amount of hits in the current window
+    /// and when that window resets (in milliseconds since the epoch)
+    ///
+    /// # Arguments:
+    /// * `key` - the identifier being limited
+    /// * `window` - the length of a window (in milliseconds)
+    fn hit<'a>(&'a self, key: &'a str, window: u128) -> BoxFuture<'a, (u32, u128)>;
+}
+
+/// How many hits [`MemoryStore`] takes between clearing out stale windows
+const SWEEP_INTERVAL: u32 = 1000;
+
+/// Store windows in memory (per-instance)
+#[derive(Default)]
+pub struct MemoryStore {
+    windows: Mutex<HashMap<String, Window>>,
+    hits: AtomicU32,
+}
+
+impl RateLimitStore for MemoryStore {
+    fn hit<'a>(&'a self, key: &'a str, window: u128) -> BoxFuture<'a, (u32, u128)> {
+        Box::pin(std::future::ready(self.hit_sync(key, window)))
+    }
+}
+
+impl MemoryStore {
+    fn hit_sync(&self, key: &str, window: u128) -> (u32, u128) {
+        let now = dorsal::utility::unix_epoch_timestamp();
+        let mut windows = self.windows.lock().unwrap();
+
+        // clear out stale windows every once in a while (every window expires
+        // by its own length, keys can use different budgets)
+        if self
+            .hits
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(SWEEP_INTERVAL)
+        {
+            windows.retain(|_, w| now.saturating_sub(w.0) < w.2);
+        }
+
+        let entry = windows.entry(key.to_string()).or_insert((now, 0, window));
+
+        // the clock could have stepped backwards since the window started
+        if now.saturating_sub(entry.0) >= entry.2 {
+            // window has expired, start a new one
+            *entry = (now, 0, window);
+        }
+
+        entry.1 = entry.1.saturating_add(1);
+        (entry.1, entry.0 + window)
+    }
+}
+
+/// Store windows in redis (shared between instances)
+pub struct RedisStore {
+    pub redis: AsyncRedis,
+}
+
+impl RateLimitStore for RedisStore {
+    fn hit<'a>(&'a self, key: &'a str, window: u128) -> BoxFuture<'a, (u32, u128)> {
+        Box::pin(async move {
+            let now = dorsal::utility::unix_epoch_timestamp();
+
+            let mut c = match self.redis.get().await {
+                Ok(c) => c,
+                // fail open, we'd rather not take the whole API down with redis
+                Err(_) => return (0, now + window),
+            };
+
+            let key = format!("ratelimit:{key}");
+            let hits: u32 = c.incr(&key, 1).await.unwrap_or(0);
+
+            let mut ttl: i64 = c.pttl(&key).await.unwrap_or(-1);
+
+            if (hits == 1) | (ttl < 0) {
+                // first hit in this window
+                let _: Result<bool, redis::RedisError> = c.pexpire(&key, window as i64).await;
+                ttl = window as i64;
+            }
+
+            (hits, now + ttl as u128)
+        })
+    }
+}
+
+/// Default budgets (`register` and `login` are checked by their handlers, every
+/// other budget is used by [`middleware`])
+pub const DEFAULT_BUDGETS: [(&str, Budget); 9] = [
+    ("read", Budget::new(300, 60)),
+    ("write", Budget::new(60, 60)),
+    ("follow", Budget::new(20, 60)),
+    ("avatar", Budget::new(60, 60)),
+    ("upload", Budget::new(10, 60 * 10)),
+    ("verify", Budget::new(10, 60 * 10)),
+    ("introspect", Budget::new(1200, 60)),
+    ("register", Budget::new(5, 60 * 60)),
+    ("login", Budget::new(20, 60 * 10)),
+];
+
+/// Fixed-window rate limiter
+///
+/// Cloning a [`RateLimiter`] shares the underlying state, so one limiter can
+/// be created before the server starts and then handed to every worker.
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    budgets: Arc<HashMap<String, Budget>>,
+}
+
+impl RateLimiter {
+    /// Create a new [`RateLimiter`]
+    ///
+    /// # Arguments:
+    /// * `store` - where windows are stored
+    /// * `budgets` - named budgets used by [`middleware`]
+    pub fn new(store: Arc<dyn RateLimitStore>, budgets: HashMap<String, Budget>) -> RateLimiter {
+        RateLimiter {
+            store,
+            budgets: Arc::new(budgets),
+        }
+    }
+
+    /// Create the [`RateLimiter`] described by a [`RateLimitConfig`]
+    ///
+    /// # Arguments:
+    /// * `config` - [`RateLimitConfig`]
+    /// * `redis` - the redis client used if `config.store` is `redis`
+    pub fn from_config(config: &RateLimitConfig, redis: redis::Client) -> RateLimiter {
+        let store: Arc<dyn RateLimitStore> = match config.store.as_str() {
+            "redis" => Arc::new(RedisStore {
+                redis: AsyncRedis::new(redis),
+            }),
+            _ => Arc::new(MemoryStore::default()),
+        };
+
+        // budgets
+        let mut budgets: HashMap<String, Budget> = HashMap::new();
+
+        for (name, budget) in DEFAULT_BUDGETS {
+            budgets.insert(name.to_string(), budget);
+        }
+
+        for (name, budget) in &config.budgets {
+            budgets.insert(name.clone(), *budget);
+        }
+
+        RateLimiter::new(store, budgets)
+    }
+
+    /// Get a named budget
+    ///
+    /// # Arguments:
+    /// * `name` - the name of the budget
+    pub fn budget(&self, name: &str) -> Option<Budget> {
+        self.budgets.get(name).copied().or(DEFAULT_BUDGETS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, b)| *b))
+    }
+
+    /// Record a hit on `key`
+    ///
+    /// # Arguments:
+    /// * `key` - the identifier being limited
+    /// * `budget` - the [`Budget`] for `key`
+    pub async fn hit(&self, key: String, budget: &Budget) -> RateLimitState {
+        let (hits, reset) = self.store.hit(&key, budget.window).await;
+
+        RateLimitState {
+            allowed: hits <= budget.limit,
+            limit: budget.limit,
+            remaining: budget.limit.saturating_sub(hits),
+            reset,
+        }
+    }
+
+    /// Record a hit on `key`. Returns `false` if `key` has already used its budget.
+    ///
+    /// # Arguments:
+    /// * `key` - the identifier being limited
+    /// * `budget` - the [`Budget`] for `key`
+    pub async fn check(&self, key: String, budget: &Budget) -> bool {
+        self.hit(key, budget).await.allowed
+    }
+}
+
+/// Get the IP address of the client that sent `req`
+///
+/// This is the address of the connection, unless it comes from one of
+/// `ratelimit.trusted_proxies`. Then the closest address in `X-Forwarded-For`
+/// which isn't a trusted proxy is used (everything before it could be made up
+/// by the client).
+pub fn get_ip(req: &HttpRequest) -> String {
+    let peer = match req.peer_addr() {
+        Some(a) => a.ip(),
+        None => return String::from("unknown"),
+    };
+
+    let data = req.app_data::<web::Data<AppData>>();
+    let trusted: &[IpAddr] = match data {
+        Some(data) => &data.config.ratelimit.trusted_proxies,
+        None => &[],
+    };
+
+    if !trusted.contains(&peer) {
+        return peer.to_string();
+    }
+
+    let forwarded: Vec<Option<IpAddr>> = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(","))
+        .map(|ip| ip.trim().parse::<IpAddr>().ok())
+        .collect();
+
+    for ip in forwarded.into_iter().rev() {
+        match ip {
+            Some(ip) if trusted.contains(&ip) => continue,
+            Some(ip) => return ip.to_string(),
+            // not an address, don't trust anything before it
+            None => break,
+        }
+    }
+
+    peer.to_string()
+}
+
+/// Get the name of the budget a route uses (`None` if the route isn't limited)
+///
+/// # Arguments:
+/// * `method` - the request method
+/// * `path` - the request path
+pub fn route_budget(method: &Method, path: &str) -> Option<&'static str> {
+    if !path.starts_with("/api/") | path.starts_with("/api/docs") {
+        return Option::None;
+    }
+
+    if (method != Method::GET) & path.ends_with("/follow") {
+        return Option::Some("follow");
+    }
+
+    if (method == Method::POST) & path.ends_with("/verify") {
+        // fetches a remote page
+        return Option::Some("verify");
+    }
+
+    if (path == "/api/v1/auth/introspect") | (path == "/api/v1/auth/forward") {
+        // called by other services (or proxies) on every request they handle
+        return Option::Some("introspect");
+    }
+
+    if path.ends_with("/identicon") {
+        return Option::Some("avatar");
+    }
+
+    if path.ends_with("/avatar") | path.ends_with("/banner") {
+        if method == Method::GET {
+            return Option::Some("avatar");
+        }
+
+        return Option::Some("upload");
+    }
+
+    if method == Method::GET {
+        Option::Some("read")
+    } else {
+        Option::Some("write")
+    }
+}
+
+/// Rate limiting middleware for every `/api/` route
+///
+/// Requests are counted against the client IP, and also against the user
+/// (by token) if the request is authenticated. The most restrictive result
+/// is reported in the `X-RateLimit-*` headers.
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let data = match req.app_data::<web::Data<AppData>>() {
+        Some(d) => d.clone(),
+        None => return next.call(req).await.map(|r| r.map_into_left_body()),
+    };
+
+    // relative to where Shuttle is mounted (see [`crate::configure`])
+    let name = route_budget(req.method(), req.match_info().unprocessed());
+    let budget = match name.and_then(|n| data.limiter.budget(n)) {
+        Some(b) => b,
+        None => return next.call(req).await.map(|r| r.map_into_left_body()),
+    };
+
+    let name = name.unwrap();
+
+    // count hit
+    let ip = get_ip(req.request());
+    let mut state = data.limiter.hit(format!("{name}:ip:{ip}"), &budget).await;
+
+    if let Some(token) = req.cookie("__Secure-Token") {
+        // never store the raw token
+        let user = dorsal::utility::hash(token.value().to_string());
+        let user_state = data
+            .limiter
+            .hit(format!("{name}:user:{user}"), &budget)
+            .await;
+
+        if !user_state.allowed | (state.allowed & (user_state.remaining < state.remaining)) {
+            state = user_state;
+        }
+    }
+
+    let now = dorsal::utility::unix_epoch_timestamp();
+    let reset_in = (state.reset.saturating_sub(now) / 1000) + 1; // seconds
+
+    if !state.allowed {
+        let res = HttpResponse::TooManyRequests()
+            .append_header(("Retry-After", reset_in.to_string()))
+            .append_header(("X-RateLimit-Limit", state.limit.to_string()))
+            .append_header(("X-RateLimit-Remaining", "0"))
+            .append_header(("X-RateLimit-Reset", reset_in.to_string()))
+            .append_header(("Content-Type", "application/json"))
+            .body(
+                serde_json::to_string::<DefaultReturn<()>>(&DefaultReturn {
+                    success: false,
+                    message: String::from("Too many requests, please try again later."),
+                    payload: (),
+                })
+                .unwrap(),
+            );
+
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+
+    // ...
+    let mut res = next.call(req).await?;
+    let headers = res.headers_mut();
+
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-limit"),
+        HeaderValue::from(state.limit),
+    );
+
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-remaining"),
+        HeaderValue::from(state.remaining),
+    );
+
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-reset"),
+        HeaderValue::from(reset_in as u64),
+    );
+
+    Ok(res.map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_parses() {
+        assert_eq!(Budget::parse("5/3600"), Option::Some(Budget::new(5, 3600)));
+        assert_eq!(Budget::parse(" 5 / 60 "), Option::Some(Budget::new(5, 60)));
+        assert_eq!(String::from(Budget::new(20, 600)), "20/600");
+    }
+
+    #[test]
+    fn budget_rejects_malformed() {
+        for input in [
+            "", "5", "5/", "/60", "a/60", "5/b", "-1/60", "5/-60", "5/60/2", "5.5/60",
+        ] {
+            assert_eq!(Budget::parse(input), Option::None, "{input:?}");
+        }
+
+        assert!(Budget::try_from(String::from("5:60")).is_err());
+    }
+
+    #[test]
+    fn memory_store_counts_hits() {
+        let store = MemoryStore::default();
+
+        assert_eq!(store.hit_sync("a", 60_000).0, 1);
+        assert_eq!(store.hit_sync("a", 60_000).0, 2);
+        assert_eq!(store.hit_sync("b", 60_000).0, 1);
+
+        // a zero length window is always expired
+        assert_eq!(store.hit_sync("c", 0).0, 1);
+        assert_eq!(store.hit_sync("c", 0).0, 1);
+    }
+}
@@ -0,0 +1,91 @@
+//! # Services
+//!
+//! Client credentials for other backend services which use Shuttle as their
+//! identity provider (see `api::introspect`).
+//!
+//! Clients are configured in `[services.clients]` as `id = "secret"` pairs (or
+//! through `SERVICE_CLIENTS`, ex: `billing:hunter2,chat:correcthorse`), and
+//! authenticate using HTTP basic authentication (`Authorization: Basic base64(id:secret)`).
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use actix_web::HttpRequest;
+use base64::Engine;
+
+/// Registered service clients
+///
+/// Cloning a [`ServiceClients`] shares the underlying credentials.
+#[derive(Clone, Default)]
+pub struct ServiceClients {
+    /// Client ID -> hashed client secret
+    clients: Arc<HashMap<String, String>>,
+}
+
+impl ServiceClients {
+    /// Create a new [`ServiceClients`]
+    ///
+    /// # Arguments:
+    /// * `clients` - client ID -> unhashed client secret
+    pub fn new(clients: impl IntoIterator<Item = (String, String)>) -> ServiceClients {
+        ServiceClients {
+            clients: Arc::new(
+                clients
+                    .into_iter()
+                    .map(|(id, secret)| (id, dorsal::utility::hash(secret)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Check if no clients are registered
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// Check a client's credentials
+    ///
+    /// # Arguments:
+    /// * `id` - the client ID
+    /// * `secret` - the unhashed client secret
+    pub fn verify(&self, id: &str, secret: &str) -> bool {
+        match self.clients.get(id) {
+            Some(hashed) => constant_time_eq(
+                hashed.as_bytes(),
+                dorsal::utility::hash(secret.to_string()).as_bytes(),
+            ),
+            None => false,
+        }
+    }
+
+    /// Get the ID of the client authenticated by a request (`None` if the
+    /// credentials are missing or invalid)
+    ///
+    /// # Arguments:
+    /// * `req` - the request
+    pub fn authenticate(&self, req: &HttpRequest) -> Option<String> {
+        let header = req.headers().get("Authorization")?.to_str().ok()?;
+        let encoded = header.strip_prefix("Basic ")?;
+
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .ok()?;
+
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (id, secret) = decoded.split_once(":")?;
+
+        if !self.verify(id, secret) {
+            return Option::None;
+        }
+
+        Option::Some(id.to_string())
+    }
+}
+
+/// Compare two byte strings without returning early
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,95 @@
+//! # Setup
+//!
+//! First-run bootstrap. When no account has `ManageUsers`, a one-time setup token
+//! is logged at startup; it unlocks `/flow/setup`, which creates the first admin
+//! and seeds the default role levels (see [`default_levels`]).
+use std::sync::{Arc, Mutex};
+
+use crate::db::RoleLevel;
+
+/// The role given to the account created during setup
+pub const ADMIN_ROLE: &str = "admin";
+
+/// The role levels seeded during setup
+///
+/// Users can only be banned if their level has an elevation of 0, so moderators
+/// and admins can't ban each other.
+pub fn default_levels() -> Vec<RoleLevel> {
+    vec![
+        RoleLevel {
+            name: String::from("member"),
+            elevation: 0,
+            permissions: Vec::new(),
+        },
+        RoleLevel {
+            name: String::from("moderator"),
+            elevation: 1,
+            permissions: vec![String::from("ManageUsers")],
+        },
+        RoleLevel {
+            name: String::from(ADMIN_ROLE),
+            elevation: 2,
+            permissions: vec![String::from("ManageUsers")],
+        },
+        RoleLevel {
+            name: String::from("banned"),
+            elevation: -1,
+            permissions: Vec::new(),
+        },
+    ]
+}
+
+/// The one-time setup token (if setup hasn't been finished)
+///
+/// Cloning a [`SetupToken`] shares the underlying token, so finishing setup on
+/// one worker disables it for every worker.
+#[derive(Clone, Default)]
+pub struct SetupToken {
+    token: Arc<Mutex<Option<String>>>,
+}
+
+impl SetupToken {
+    /// Create a new [`SetupToken`] with a random token
+    pub fn generate() -> SetupToken {
+        SetupToken {
+            token: Arc::new(Mutex::new(Option::Some(dorsal::utility::uuid()))),
+        }
+    }
+
+    /// Get the token (`None` if setup is finished or wasn't needed)
+    pub fn get(&self) -> Option<String> {
+        self.token.lock().unwrap().clone()
+    }
+
+    /// Check if setup is still pending
+    pub fn is_pending(&self) -> bool {
+        self.token.lock().unwrap().is_some()
+    }
+
+    /// Consume the token if `token` matches it. Returns `false` if it doesn't
+    /// match (or setup is already finished)
+    ///
+    /// # Arguments:
+    /// * `token` - the token given by the user
+    pub fn take(&self, token: &str) -> bool {
+        let mut current = self.token.lock().unwrap();
+
+        match current.as_ref() {
+            Some(t)
+                if dorsal::utility::hash(t.clone()) == dorsal::utility::hash(token.to_string()) =>
+            {
+                *current = Option::None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Put a consumed token back (if setup failed after [`SetupToken::take`])
+    ///
+    /// # Arguments:
+    /// * `token` - the token
+    pub fn restore(&self, token: String) {
+        *self.token.lock().unwrap() = Option::Some(token);
+    }
+}
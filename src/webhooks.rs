@@ -0,0 +1,502 @@
+//! # Webhooks
+//!
+//! Outgoing webhooks for account lifecycle events.
+//!
+//! Staff register webhooks (see `api::webhooks`) with a URL, a secret and the
+//! events they want. Events published to the [`EventBus`](crate::events::EventBus)
+//! are delivered as JSON `POST` requests signed with HMAC-SHA256:
+//!
+//! ```text
+//! X-Shuttle-Event: user.followed
+//! X-Shuttle-Delivery: {event id}
+//! X-Shuttle-Signature: sha256={hex(hmac_sha256(secret, body))}
+//! ```
+//!
+//! Failed deliveries are retried with exponential backoff, and every attempt is
+//! recorded as a [`WebhookDelivery`].
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use actix_web::http::Uri;
+
+use hex_fmt::HexFmt;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::WebhooksConfig;
+use crate::db::Database;
+use crate::events::Event;
+use crate::net::{self, NetError};
+
+/// Every event a webhook can subscribe to
+pub const EVENTS: [&str; 4] = [
+    "user.registered",
+    "user.updated",
+    "user.banned",
+    "user.followed",
+];
+
+/// How long to wait for a receiver to respond
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A registered webhook
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String, // only shown once, when the webhook is created
+    pub events: Vec<String>, // see [`EVENTS`] (`*` for every event)
+    pub created_by: String,
+    pub timestamp: u128,
+}
+
+impl Webhook {
+    /// Check if this webhook wants the given event
+    pub fn wants(&self, event: &str) -> bool {
+        self.events.iter().any(|e| (e == "*") | (e == event))
+    }
+}
+
+/// A single attempt at delivering an event to a [`Webhook`]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct WebhookDelivery {
+    pub webhook: String,     // the ID of the webhook
+    pub delivery: String,    // the ID of the event being delivered
+    pub event: String,       // the name of the event being delivered
+    pub attempt: u32,        // starts at 1
+    pub status: Option<u16>, // the response status (if a response was received)
+    pub error: Option<String>,
+    pub success: bool,
+}
+
+/// The body sent to webhooks
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WebhookPayload {
+    pub id: String,
+    pub event: String,
+    pub timestamp: u128,
+    pub username: String,      // the user the event is about
+    pub actor: Option<String>, // the user that caused the event
+}
+
+/// Get the webhook event name for an [`Event`] kind (`None` if it isn't sent to webhooks)
+pub fn event_name(kind: &str) -> Option<&'static str> {
+    match kind {
+        "register" => Option::Some("user.registered"),
+        "profile_update" => Option::Some("user.updated"),
+        "ban" => Option::Some("user.banned"),
+        "follow" => Option::Some("user.followed"),
+        _ => Option::None,
+    }
+}
+
+/// Compute the HMAC-SHA256 of `message` (RFC 2104)
+///
+/// # Arguments:
+/// * `key` - the secret key
+/// * `message` - the signed message
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// Get the `X-Shuttle-Signature` header value for a body
+///
+/// # Arguments:
+/// * `secret` - the webhook's secret
+/// * `body` - the request body
+pub fn sign(secret: &str, body: &str) -> String {
+    format!(
+        "sha256={}",
+        HexFmt(hmac_sha256(secret.as_bytes(), body.as_bytes()))
+    )
+}
+
+/// Webhook delivery settings (see [`WebhooksConfig`])
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub allow_private_addresses: bool,
+}
+
+impl RetryPolicy {
+    /// Create the [`RetryPolicy`] described by a [`WebhooksConfig`]
+    pub fn from_config(config: &WebhooksConfig) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: config.max_attempts.max(1),
+            base_delay: Duration::from_secs(config.retry_delay),
+            allow_private_addresses: config.allow_private_addresses,
+        }
+    }
+
+    /// How long to wait after the given (failed) attempt
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+/// Deliver events from the database's [`EventBus`](crate::events::EventBus) to
+/// webhooks (runs until the bus is closed)
+///
+/// Must be spawned on the actix runtime, since `awc` clients aren't `Send`.
+///
+/// # Arguments:
+/// * `db` - [`Database`]
+/// * `client` - the client used to deliver events
+/// * `policy` - [`RetryPolicy`]
+pub async fn dispatch(db: Database, client: awc::Client, policy: RetryPolicy) {
+    let mut receiver = db.events.subscribe();
+
+    loop {
+        let event = match receiver.recv().await {
+            // remote events are delivered by the instance that published them
+            Ok(event) if event.remote => continue,
+            Ok(event) => event,
+            Err(RecvError::Lagged(count)) => {
+                log::warn!(target: "shuttle::webhooks", "missed {count} events");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let name = match event_name(&event.kind) {
+            Some(n) => n,
+            None => continue,
+        };
+
+        for webhook in db.get_webhooks().await {
+            if !webhook.wants(name) {
+                continue;
+            }
+
+            actix_web::rt::spawn(deliver(
+                db.clone(),
+                client.clone(),
+                webhook,
+                payload(name, &event),
+                policy,
+            ));
+        }
+    }
+}
+
+/// Build the [`WebhookPayload`] for an event
+fn payload(name: &str, event: &Event) -> WebhookPayload {
+    WebhookPayload {
+        id: event.id.clone(),
+        event: name.to_string(),
+        timestamp: event.timestamp,
+        username: event.username.clone(),
+        actor: event.actor.clone(),
+    }
+}
+
+/// Resolve the host of a webhook's URL, only to public addresses unless
+/// `allow_private` is set (see [`net::resolve_public`])
+///
+/// # Arguments:
+/// * `url` - the webhook's URL
+/// * `allow_private` - [`WebhooksConfig::allow_private_addresses`]
+pub async fn resolve(url: &str, allow_private: bool) -> net::Result<SocketAddr> {
+    let uri = match url.parse::<Uri>() {
+        Ok(u) => u,
+        Err(_) => return Err(NetError::InvalidUrl),
+    };
+
+    if allow_private {
+        net::resolve(&uri).await
+    } else {
+        net::resolve_public(&uri).await
+    }
+}
+
+/// Deliver a payload to a webhook, retrying until it succeeds or `policy` runs out
+///
+/// Returns the last [`WebhookDelivery`].
+///
+/// # Arguments:
+/// * `db` - [`Database`] (attempts are logged)
+/// * `client` - the client used to deliver the payload
+/// * `webhook` - the [`Webhook`] to deliver to
+/// * `payload` - the [`WebhookPayload`]
+/// * `policy` - [`RetryPolicy`]
+pub async fn deliver(
+    db: Database,
+    client: awc::Client,
+    webhook: Webhook,
+    payload: WebhookPayload,
+    policy: RetryPolicy,
+) -> WebhookDelivery {
+    send(&client, &webhook, &payload, policy, |delivery| {
+        let db = db.clone();
+        async move {
+            let _ = db.log_webhook_delivery(&delivery).await;
+        }
+    })
+    .await
+}
+
+/// Deliver a payload to a webhook (see [`deliver`]), passing every attempt to `record`
+///
+/// # Arguments:
+/// * `client` - the client used to deliver the payload
+/// * `webhook` - the [`Webhook`] to deliver to
+/// * `payload` - the [`WebhookPayload`]
+/// * `policy` - [`RetryPolicy`]
+/// * `record` - called with every [`WebhookDelivery`]
+pub async fn send<F, Fut>(
+    client: &awc::Client,
+    webhook: &Webhook,
+    payload: &WebhookPayload,
+    policy: RetryPolicy,
+    mut record: F,
+) -> WebhookDelivery
+where
+    F: FnMut(WebhookDelivery) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let body = serde_json::to_string(&payload).unwrap();
+    let signature = sign(&webhook.secret, &body);
+
+    let mut attempt: u32 = 1;
+
+    loop {
+        // resolved on every attempt, the host could have been pointed somewhere else
+        let mut give_up = false;
+
+        let res = match resolve(&webhook.url, policy.allow_private_addresses).await {
+            Ok(addr) => client
+                .post(&webhook.url)
+                .address(addr) // connect to the address we checked, not whatever DNS says now
+                .timeout(TIMEOUT)
+                .insert_header(("Content-Type", "application/json"))
+                .insert_header(("User-Agent", "Shuttle-Webhooks"))
+                .insert_header(("X-Shuttle-Event", payload.event.as_str()))
+                .insert_header(("X-Shuttle-Delivery", payload.id.as_str()))
+                .insert_header(("X-Shuttle-Signature", signature.as_str()))
+                .send_body(body.clone())
+                .await
+                .map_err(|e| e.to_string()),
+            Err(NetError::Unresolvable) => Err(String::from("Failed to resolve host")),
+            Err(_) => {
+                // retrying won't help
+                give_up = true;
+                Err(String::from("URL points to a forbidden address"))
+            }
+        };
+
+        let delivery = WebhookDelivery {
+            webhook: webhook.id.clone(),
+            delivery: payload.id.clone(),
+            event: payload.event.clone(),
+            attempt,
+            status: res.as_ref().ok().map(|r| r.status().as_u16()),
+            error: res.as_ref().err().cloned(),
+            success: res.as_ref().is_ok_and(|r| r.status().is_success()),
+        };
+
+        record(delivery.clone()).await;
+
+        if delivery.success | give_up | (attempt >= policy.max_attempts) {
+            return delivery;
+        }
+
+        actix_web::rt::time::sleep(policy.delay(attempt)).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+    #[test]
+    fn sign_matches_rfc4231() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn retry_delay_doubles() {
+        let policy = RetryPolicy::from_config(&WebhooksConfig::default());
+
+        assert_eq!(policy.delay(1), Duration::from_secs(5));
+        assert_eq!(policy.delay(2), Duration::from_secs(10));
+        assert_eq!(policy.delay(3), Duration::from_secs(20));
+
+        // doesn't overflow
+        assert!(policy.delay(u32::MAX) > policy.delay(3));
+    }
+
+    #[test]
+    fn retry_policy_makes_at_least_one_attempt() {
+        let policy = RetryPolicy::from_config(&WebhooksConfig {
+            max_attempts: 0,
+            ..Default::default()
+        });
+
+        assert_eq!(policy.max_attempts, 1);
+    }
+
+    /// Start a receiver failing the first `failures` requests, returning its URL
+    /// and the `X-Shuttle-Signature` of every request it received
+    fn receiver(failures: usize) -> (String, Arc<Mutex<Vec<String>>>) {
+        let received: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let state = web::Data::from(received.clone());
+
+        let server = HttpServer::new(move || {
+            App::new().app_data(state.clone()).default_service(web::to(
+                move |req: HttpRequest, received: web::Data<Mutex<Vec<String>>>| async move {
+                    let mut received = received.lock().unwrap();
+
+                    received.push(
+                        req.headers()
+                            .get("X-Shuttle-Signature")
+                            .and_then(|v| v.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string(),
+                    );
+
+                    if received.len() <= failures {
+                        HttpResponse::InternalServerError().finish()
+                    } else {
+                        HttpResponse::Ok().finish()
+                    }
+                },
+            ))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+
+        let url = format!("http://{}/hook", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        (url, received)
+    }
+
+    fn webhook(url: String) -> (Webhook, WebhookPayload) {
+        (
+            Webhook {
+                id: String::from("webhook"),
+                url,
+                secret: String::from("secret"),
+                events: vec![String::from("*")],
+                ..Default::default()
+            },
+            WebhookPayload {
+                id: String::from("delivery"),
+                event: String::from("ping"),
+                timestamp: 0,
+                username: String::from("user"),
+                actor: Option::None,
+            },
+        )
+    }
+
+    fn policy(max_attempts: u32, allow_private_addresses: bool) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::ZERO,
+            allow_private_addresses,
+        }
+    }
+
+    #[actix_web::test]
+    async fn deliver_retries_until_success() {
+        let (url, received) = receiver(2);
+        let (webhook, payload) = webhook(url);
+
+        let attempts = Rc::new(RefCell::new(Vec::new()));
+        let delivery = send(
+            &awc::Client::default(),
+            &webhook,
+            &payload,
+            policy(5, true),
+            {
+                let attempts = attempts.clone();
+                move |d| {
+                    attempts.borrow_mut().push(d);
+                    async {}
+                }
+            },
+        )
+        .await;
+
+        assert!(delivery.success);
+        assert_eq!(delivery.attempt, 3);
+        assert_eq!(delivery.status, Option::Some(200));
+
+        let attempts = attempts.borrow();
+        assert_eq!(attempts.len(), 3);
+        assert_eq!(attempts[0].status, Option::Some(500));
+        assert!(!attempts[1].success);
+
+        // every attempt is signed with the webhook's secret
+        let signature = sign(&webhook.secret, &serde_json::to_string(&payload).unwrap());
+        assert_eq!(*received.lock().unwrap(), vec![signature; 3]);
+    }
+
+    #[actix_web::test]
+    async fn deliver_stops_after_max_attempts() {
+        let (url, received) = receiver(usize::MAX);
+        let (webhook, payload) = webhook(url);
+
+        let mut attempts = 0;
+        let delivery = send(
+            &awc::Client::default(),
+            &webhook,
+            &payload,
+            policy(2, true),
+            |_| {
+                attempts += 1;
+                async {}
+            },
+        )
+        .await;
+
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempt, 2);
+        assert_eq!(attempts, 2);
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn deliver_refuses_private_addresses() {
+        let (url, received) = receiver(0);
+        let (webhook, payload) = webhook(url);
+
+        let delivery = send(
+            &awc::Client::default(),
+            &webhook,
+            &payload,
+            policy(5, false),
+            |_| async {},
+        )
+        .await;
+
+        // not retried
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempt, 1);
+        assert_eq!(
+            delivery.error.as_deref(),
+            Option::Some("URL points to a forbidden address")
+        );
+        assert!(received.lock().unwrap().is_empty());
+    }
+}
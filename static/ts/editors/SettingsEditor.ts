@@ -15,6 +15,9 @@ export function user_settings(
     let current_property: string = "";
     let option_render: string = "";
 
+    // only fields which were changed are sent
+    let changed: { [key: string]: any } = {};
+
     // handlers
     (window as any).change_current_property = (e: any) => {
         const selected = e.target.options[
@@ -70,7 +73,31 @@ export function user_settings(
 
                 (window as any).user_settings_field_input = (e: any) => {
                     metadata[current_property] = e.target.value;
+                    changed[current_property] = e.target.value;
                 };
+
+                // USER ONLY - custom field links
+                if (
+                    _type === "user" &&
+                    current_property.startsWith("fields.") &&
+                    typeof meta_value === "string" &&
+                    meta_value.startsWith("http")
+                ) {
+                    const key = current_property.replace("fields.", "");
+                    option_render += `<button class="button round theme:primary" onclick="window.send_field_verify_request();">Verify Link</button>`;
+
+                    (window as any).send_field_verify_request = async () => {
+                        const res = await fetch(
+                            `/api/v1/auth/users/${name}/fields/${key}/verify`,
+                            {
+                                method: "POST",
+                            }
+                        );
+
+                        const res_ = await res.json();
+                        alert(res_.message);
+                    };
+                }
             }
         }
 
@@ -86,10 +113,22 @@ export function user_settings(
     update_form.addEventListener("submit", async (e) => {
         e.preventDefault();
 
+        // custom fields are sent as an object (empty values remove the field)
+        const body: { [key: string]: any } = {};
+
+        for (const [key, value] of Object.entries(changed)) {
+            if (key.startsWith("fields.")) {
+                if (!body.fields) body.fields = {};
+                body.fields[key.replace("fields.", "")] = value || null;
+            } else {
+                body[key] = value;
+            }
+        }
+
         // user
         const res = await fetch(`/api/v1/auth/users/${name}/update`, {
             method: "POST",
-            body: JSON.stringify(metadata),
+            body: JSON.stringify(body),
             headers: {
                 "Content-Type": "application/json",
             },
@@ -98,6 +137,15 @@ export function user_settings(
         const json = await res.json();
 
         if (json.success === false) {
+            // field errors
+            if (json.payload && typeof json.payload === "object") {
+                return alert(
+                    `${json.message}\n\n${Object.entries(json.payload)
+                        .map(([field, error]) => `${field}: ${error}`)
+                        .join("\n")}`
+                );
+            }
+
             return alert(json.message);
         } else {
             window.location.reload();
@@ -106,15 +154,226 @@ export function user_settings(
 
     // handle add field
     add_field.addEventListener("click", () => {
-        const name = prompt("Enter field name:");
+        const name = prompt("Enter field name (a-z, 0-9, _, -):");
         if (!name) return;
 
-        metadata[name] = "unknown";
+        // custom field
+        metadata[`fields.${name}`] = "";
         options = build_options(metadata, current_property);
         render_user_settings_fields(field, options, option_render);
     });
 }
 
+export function media_uploads(container: HTMLElement): void {
+    // upload
+    for (const input of Array.from(
+        container.querySelectorAll("input[type=file]"),
+    ) as HTMLInputElement[]) {
+        input.addEventListener("change", async () => {
+            const file = (input.files || [])[0];
+            if (!file) return;
+
+            const res = await fetch(input.getAttribute("data-endpoint")!, {
+                method: "POST",
+                body: file,
+                headers: {
+                    "Content-Type": file.type,
+                },
+            });
+
+            const json = await res.json();
+
+            if (json.success === false) {
+                return alert(json.message);
+            } else {
+                window.location.reload();
+            }
+        });
+    }
+
+    // remove
+    for (const button of Array.from(
+        container.querySelectorAll("button[data-remove]"),
+    ) as HTMLButtonElement[]) {
+        button.addEventListener("click", async () => {
+            const res = await fetch(button.getAttribute("data-remove")!, {
+                method: "DELETE",
+            });
+
+            const json = await res.json();
+
+            if (json.success === false) {
+                return alert(json.message);
+            } else {
+                window.location.reload();
+            }
+        });
+    }
+}
+
+export async function follow_requests(
+    container: HTMLElement,
+    name: string,
+    after?: string
+): Promise<void> {
+    const list = container.querySelector("#requests-list") as HTMLElement;
+    const more = container.querySelector("#requests-more") as HTMLButtonElement;
+    const private_toggle = container.querySelector(
+        "#private-account"
+    ) as HTMLInputElement;
+
+    if (!after) {
+        // private account toggle
+        private_toggle.onchange = async () => {
+            const res = await fetch(`/api/v1/auth/users/${name}/update`, {
+                method: "POST",
+                body: JSON.stringify({
+                    private: private_toggle.checked,
+                }),
+                headers: {
+                    "Content-Type": "application/json",
+                },
+            });
+
+            const json = await res.json();
+
+            if (json.success === false) {
+                private_toggle.checked = !private_toggle.checked;
+                return alert(json.message);
+            }
+        };
+    }
+
+    const res = await fetch(
+        `/api/v1/auth/users/${name}/follow-requests${after ? `?after=${after}` : ""}`
+    );
+
+    const json = await res.json();
+
+    if (json.success === false) {
+        list.innerText = json.message;
+        return;
+    }
+
+    if (!after) list.innerHTML = "";
+
+    for (const request of json.payload.items) {
+        const row = document.createElement("div");
+        row.className = "flex justify-between items-center gap-2";
+
+        const link = document.createElement("a");
+        link.href = `/${request.username}`;
+        link.innerText = request.username;
+
+        const buttons = document.createElement("div");
+        buttons.className = "flex gap-2";
+
+        for (const [label, method, style] of [
+            ["Approve", "POST", "round theme:primary"],
+            ["Reject", "DELETE", "round red tertiary"],
+        ]) {
+            const button = document.createElement("button");
+            button.className = style;
+            button.innerText = label;
+
+            button.addEventListener("click", async () => {
+                const res = await fetch(
+                    `/api/v1/auth/users/${name}/follow-requests/${request.username}`,
+                    {
+                        method,
+                    }
+                );
+
+                const json = await res.json();
+
+                if (json.success === false) {
+                    return alert(json.message);
+                }
+
+                row.remove();
+            });
+
+            buttons.appendChild(button);
+        }
+
+        row.appendChild(link);
+        row.appendChild(buttons);
+        list.appendChild(row);
+    }
+
+    if (list.children.length === 0) {
+        list.innerText = "No pending follow requests.";
+    }
+
+    // load more
+    more.style.display = json.payload.next ? "block" : "none";
+    more.onclick = () => follow_requests(container, name, json.payload.next);
+}
+
+export async function blocked_users(
+    container: HTMLElement,
+    name: string,
+    after?: string
+): Promise<void> {
+    const list = container.querySelector("#blocked-list") as HTMLElement;
+    const more = container.querySelector("#blocked-more") as HTMLButtonElement;
+
+    const res = await fetch(
+        `/api/v1/auth/users/${name}/blocks${after ? `?after=${after}` : ""}`
+    );
+
+    const json = await res.json();
+
+    if (json.success === false) {
+        list.innerText = json.message;
+        return;
+    }
+
+    if (!after) list.innerHTML = "";
+
+    for (const block of json.payload.items) {
+        const row = document.createElement("div");
+        row.className = "flex justify-between items-center gap-2";
+
+        const link = document.createElement("a");
+        link.href = `/${block.blocked}`;
+        link.innerText = block.blocked;
+
+        const button = document.createElement("button");
+        button.className = "round red tertiary";
+        button.innerText = "Unblock";
+
+        button.addEventListener("click", async () => {
+            const res = await fetch(
+                `/api/v1/auth/users/${block.blocked}/block`,
+                {
+                    method: "DELETE",
+                }
+            );
+
+            const json = await res.json();
+
+            if (json.success === false) {
+                return alert(json.message);
+            }
+
+            row.remove();
+        });
+
+        row.appendChild(link);
+        row.appendChild(button);
+        list.appendChild(row);
+    }
+
+    if (list.children.length === 0) {
+        list.innerText = "You haven't blocked anyone.";
+    }
+
+    // load more
+    more.style.display = json.payload.next ? "block" : "none";
+    more.onclick = () => blocked_users(container, name, json.payload.next);
+}
+
 function build_options(
     metadata: { [key: string]: string },
     current_property: string
@@ -148,4 +407,4 @@ function render_user_settings_fields(
 }
 
 // default export
-export default { user_settings };
+export default { user_settings, media_uploads, follow_requests, blocked_users };
@@ -15,18 +15,71 @@ const login_st_form: HTMLFormElement | null = document.getElementById(
     "login-user-st",
 ) as HTMLFormElement | null;
 
+const setup_form: HTMLFormElement | null = document.getElementById(
+    "setup-user",
+) as HTMLFormElement | null;
+
 const callback = document.getElementById("callback")!.innerText;
+const next = document.getElementById("next")?.innerText || "";
+
+/// the link followed after logging in
+function continue_url(token: string): string {
+    const url = `${callback}?uid=${token}`;
+
+    if (!next) {
+        return url;
+    }
+
+    return `${url}&next=${encodeURIComponent(next)}`;
+}
+
+/// find a solution for a proof of work challenge (`sha256(challenge + solution)` starting with `difficulty` zeros)
+async function solve_challenge(
+    challenge: string,
+    difficulty: number,
+): Promise<string> {
+    const prefix = "0".repeat(difficulty);
+    const encoder = new TextEncoder();
+
+    for (let nonce = 0; ; nonce++) {
+        const hash = await crypto.subtle.digest(
+            "SHA-256",
+            encoder.encode(`${challenge}${nonce}`),
+        );
+
+        const hex = Array.from(new Uint8Array(hash))
+            .map((b) => b.toString(16).padStart(2, "0"))
+            .join("");
+
+        if (hex.startsWith(prefix)) {
+            return nonce.toString();
+        }
+    }
+}
 
 if (register_form) {
     // register
     register_form.addEventListener("submit", async (e) => {
         e.preventDefault();
+
+        // get challenge
+        const challenge = (
+            await (await fetch("/api/v1/auth/challenge")).json()
+        ).payload;
+
+        const solution = challenge
+            ? await solve_challenge(challenge.challenge, challenge.difficulty)
+            : undefined;
+
+        // ...
         const res = await fetch("/api/v1/auth/register", {
             method: "POST",
             body: JSON.stringify({
                 username: register_form.username.value,
                 invite_code: (register_form.invite_code || { value: undefined })
                     .value,
+                challenge: challenge ? challenge.challenge : undefined,
+                solution,
             }),
             headers: {
                 "Content-Type": "application/json",
@@ -47,7 +100,7 @@ if (register_form) {
             <p><b>Do not lose it!</b> This code is required for you to sign into your account, <b>it cannot be reset!</b></p>
             
             <hr />
-            <a href="${callback}?uid=${json.message}" class="button round theme:primary">Continue</a>`;
+            <a href="${continue_url(json.message)}" class="button round theme:primary">Continue</a>`;
             forms.style.display = "none";
         }
     });
@@ -75,7 +128,7 @@ if (register_form) {
             success.innerHTML = `<p>Successfully logged into account.</p>
                 
                 <hr />
-                <a href="${callback}?uid=${json.message}" class="button round theme:primary">Continue</a>`;
+                <a href="${continue_url(json.message)}" class="button round theme:primary">Continue</a>`;
             forms.style.display = "none";
 
             if (switch_button) {
@@ -107,7 +160,7 @@ if (register_form) {
             success.innerHTML = `<p>Successfully logged into account.</p>
                 
                 <hr />
-                <a href="${callback}?uid=${json.message}" class="button round theme:primary">Continue</a>`;
+                <a href="${continue_url(json.message)}" class="button round theme:primary">Continue</a>`;
             forms.style.display = "none";
 
             if (switch_button) {
@@ -117,5 +170,40 @@ if (register_form) {
     });
 }
 
+if (setup_form) {
+    // setup (first admin account)
+    setup_form.addEventListener("submit", async (e) => {
+        e.preventDefault();
+        const res = await fetch("/api/v1/setup", {
+            method: "POST",
+            body: JSON.stringify({
+                token: setup_form.token.value,
+                username: setup_form.username.value,
+            }),
+            headers: {
+                "Content-Type": "application/json",
+            },
+        });
+
+        const json = await res.json();
+
+        if (json.success === false) {
+            error.style.display = "block";
+            error.innerHTML = `<div class="mdnote-title">${json.message}</div>`;
+        } else {
+            success.style.display = "flex";
+            success.innerHTML = `<p>Admin account created! You can login using this code:</p>
+
+            <p class="card secondary round flex justify-center align-center">${json.message}</p>
+
+            <p><b>Do not lose it!</b> This code is required for you to sign into your account, <b>it cannot be reset!</b></p>
+            
+            <hr />
+            <a href="/" class="button round theme:primary">Continue</a>`;
+            forms.style.display = "none";
+        }
+    });
+}
+
 // default export
 export default {};
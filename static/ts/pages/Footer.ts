@@ -52,6 +52,22 @@ if (
     document.body.appendChild(style);
 }
 
+// unread notifications
+const notifications_count: HTMLElement | null = document.getElementById(
+    "notifications_count",
+);
+
+if (notifications_count) {
+    fetch("/api/v1/notifications/count")
+        .then((res) => res.json())
+        .then((json) => {
+            if (json.success === false || json.payload === 0) return;
+            notifications_count.innerText = String(json.payload);
+            notifications_count.style.display = "inline";
+        })
+        .catch(() => {});
+}
+
 // localize dates
 setTimeout(() => {
     for (const element of Array.from(
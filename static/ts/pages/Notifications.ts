@@ -0,0 +1,30 @@
+const error: HTMLElement = document.getElementById("error")!;
+
+// notification actions ("read", "{id}/read", "{id}" or "")
+for (const button of Array.from(
+    document.querySelectorAll("[data-notifications-action]"),
+) as HTMLButtonElement[]) {
+    button.addEventListener("click", async (e) => {
+        e.preventDefault();
+        const action = button.getAttribute("data-notifications-action")!;
+
+        const res = await fetch(
+            `/api/v1/notifications${action ? `/${action}` : ""}`,
+            {
+                method: button.getAttribute("data-method")!,
+            },
+        );
+
+        const json = await res.json();
+
+        if (json.success === false) {
+            error.style.display = "block";
+            error.innerHTML = `<div class="mdnote-title">${json.message}</div>`;
+        } else {
+            window.location.reload();
+        }
+    });
+}
+
+// default export
+export default {};
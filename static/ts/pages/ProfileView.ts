@@ -41,8 +41,12 @@ if (follow_button) {
     // follow user
     follow_button.addEventListener("click", async (e) => {
         e.preventDefault();
+        const following =
+            follow_button.getAttribute("data-following") === "true" ||
+            follow_button.getAttribute("data-requested") === "true";
+
         const res = await fetch(follow_button.getAttribute("data-endpoint")!, {
-            method: "POST",
+            method: following ? "DELETE" : "POST",
             headers: {
                 "Content-Type": "application/json",
             },
@@ -56,6 +60,71 @@ if (follow_button) {
         } else {
             success.style.display = "block";
             success.innerHTML = `<div class="mdnote-title">${json.message}</div>`;
+
+            // update button with the resulting state
+            update_follow_button(json.payload);
+        }
+    });
+}
+
+function update_follow_button(relationship: {
+    following: boolean;
+    requested: boolean;
+}): void {
+    if (!follow_button) return;
+
+    follow_button.setAttribute("data-following", String(relationship.following));
+    follow_button.setAttribute("data-requested", String(relationship.requested));
+
+    follow_button.innerText = relationship.following
+        ? "Unfollow"
+        : relationship.requested
+          ? "Requested"
+          : "Follow";
+}
+
+// block
+const block_button: HTMLButtonElement | null = document.getElementById(
+    "block-user",
+) as HTMLButtonElement | null;
+
+if (block_button) {
+    // block user
+    block_button.addEventListener("click", async (e) => {
+        e.preventDefault();
+        const blocking = block_button.getAttribute("data-blocking") === "true";
+
+        if (
+            !blocking &&
+            !confirm(
+                "Are you sure you want to block this user? Follows between you will be removed.",
+            )
+        ) {
+            return;
+        }
+
+        const res = await fetch(block_button.getAttribute("data-endpoint")!, {
+            method: blocking ? "DELETE" : "POST",
+        });
+
+        const json = await res.json();
+
+        if (json.success === false) {
+            error.style.display = "block";
+            error.innerHTML = `<div class="mdnote-title">${json.message}</div>`;
+        } else {
+            success.style.display = "block";
+            success.innerHTML = `<div class="mdnote-title">${json.message}</div>`;
+
+            // update buttons with the resulting state
+            block_button.setAttribute(
+                "data-blocking",
+                String(json.payload.blocking),
+            );
+
+            block_button.innerText = json.payload.blocking ? "Unblock" : "Block";
+
+            update_follow_button(json.payload);
         }
     });
 }
@@ -13,6 +13,7 @@ const output = await build({
         "./static/ts/pages/Footer.ts",
         "./static/ts/editors/SettingsEditor.ts",
         "./static/ts/pages/ProfileView.ts",
+        "./static/ts/pages/Notifications.ts",
     ],
     minify: {
         identifiers: true,